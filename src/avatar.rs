@@ -0,0 +1,55 @@
+//! Avatar URLs for profiles.
+//!
+//! OneBot has no avatar fields, QQ serves them from fixed CDN URLs built from the id, so
+//! profiles get those.
+
+use hyper::Uri;
+
+/// How avatar URLs are filled into `UserProfile`s and `GroupProfile`s.
+///
+/// `size` is the edge in pixels, QQ serves 40, 100, 140 and 640. With `enabled` off avatars
+/// stay `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AvatarConfig {
+    pub enabled: bool,
+    pub size: u32,
+}
+
+impl Default for AvatarConfig {
+    fn default() -> Self {
+        AvatarConfig {
+            enabled: true,
+            size: 640,
+        }
+    }
+}
+
+impl AvatarConfig {
+    pub const DISABLED: AvatarConfig = AvatarConfig {
+        enabled: false,
+        size: 640,
+    };
+
+    /// `https://q1.qlogo.cn/g?b=qq&nk=<user_id>&s=<size>`
+    pub fn user(&self, user_id: impl std::fmt::Display) -> Option<Uri> {
+        self.uri(format!(
+            "https://q1.qlogo.cn/g?b=qq&nk={}&s={}",
+            user_id, self.size
+        ))
+    }
+
+    /// `https://p.qlogo.cn/gh/<group_id>/<group_id>/<size>`
+    pub fn group(&self, group_id: impl std::fmt::Display) -> Option<Uri> {
+        self.uri(format!(
+            "https://p.qlogo.cn/gh/{0}/{0}/{1}",
+            group_id, self.size
+        ))
+    }
+
+    fn uri(&self, uri: String) -> Option<Uri> {
+        if !self.enabled {
+            return None;
+        }
+        uri.parse().ok()
+    }
+}
@@ -0,0 +1,1519 @@
+use anyhow::Result;
+use chrono::DateTime;
+use onebot_v11::api::{
+    payload::{
+        ApiPayload, DeleteMsg, GetFile, GetGroupFileCount, GetGroupFileList, GetGroupMemberList,
+        GetLoginInfo, GetStrangerInfo, SetFriendAddRequest, SetGroupAddRequest, SetGroupAdmin,
+        SetGroupBan, SetGroupCard, SetGroupFileFolder, SetGroupKick, SetGroupName,
+        SetGroupWholeBan, SetMsgEmojiLike, SetQQAvatar,
+    },
+    resp::{ApiResp, ApiRespBuilder, ApiRespData, SendGroupMsgResponse, SendPrivateMsgResponse},
+};
+use onebot_v11::traits::EndPoint as _;
+use oxidebot::{
+    api::{
+        payload::{GroupAdminChangeType, GroupMuteType, RequestResponse, SendMessageTarget},
+        BotGetFriendListResponse, BotGetGroupListResponse, BotGetProfileResponse, CallApiTrait,
+        GetMessageDetailResponse, GroupGetFileCountResponse, GroupGetFsListResponse,
+        GroupGetProfileResponse, GroupMemberListResponse, SendMessageResponse,
+        UserGetProfileResponse,
+    },
+    bot::BotObject,
+    event::{Event, MessageEvent},
+    matcher::Matcher,
+    source::{
+        bot::BotInfo,
+        group::GroupProfile,
+        message::{File, Folder, FsNode, Message, MessageSegment},
+        user::{Sex, User, UserGroupInfo, UserProfile},
+    },
+    BotTrait,
+};
+
+use std::{any::Any, collections::HashMap, sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+use tracing::warn;
+
+use crate::{
+    avatar::AvatarConfig,
+    cache::{MemberCache, MemberCacheConfig},
+    error::{payload_value, OnebotError},
+    event::{event_self_id, merge_profile, EventContext, EventWrapper},
+    forward::{self, ForwardConfig},
+    history::{HistoryBackend, StoredMessage},
+    id::{GroupId, MessageId, UserId},
+    media::{self, FetchConfig, Fetcher, MediaConfig, MediaServer},
+    record::{self, Recorder},
+    reply::{ReplyResolver, ReplyResolverConfig},
+    segment::{cast_segment, parse_segment},
+    split::{self, SplitConfig},
+    store::{Lookup, MessageStore, MessageStoreConfig},
+    PLATFORM,
+};
+
+use super::connect::OnebotConnect;
+
+/// A OneBot v11 bot over any [`OnebotConnect`] transport.
+pub struct OnebotV11Bot<C: OnebotConnect> {
+    pub(crate) connect: Arc<C>,
+    pub(crate) recorder: Option<Arc<Recorder>>,
+    pub(crate) avatars: AvatarConfig,
+    pub(crate) members: Option<Arc<MemberCache>>,
+    pub(crate) messages: Option<Arc<MessageStore>>,
+    pub(crate) history: Option<Arc<dyn HistoryBackend>>,
+    pub(crate) replies: Option<Arc<ReplyResolver>>,
+    pub(crate) forwards: ForwardConfig,
+    pub(crate) splitter: Option<SplitConfig>,
+    pub(crate) media: MediaConfig,
+    pub(crate) media_server: Option<Arc<MediaServer>>,
+    pub(crate) fetcher: Arc<Fetcher>,
+}
+
+impl<C: OnebotConnect> Clone for OnebotV11Bot<C> {
+    fn clone(&self) -> Self {
+        Self {
+            connect: Arc::clone(&self.connect),
+            recorder: self.recorder.clone(),
+            avatars: self.avatars,
+            members: self.members.clone(),
+            messages: self.messages.clone(),
+            history: self.history.clone(),
+            replies: self.replies.clone(),
+            forwards: self.forwards,
+            splitter: self.splitter,
+            media: self.media,
+            media_server: self.media_server.clone(),
+            fetcher: self.fetcher.clone(),
+        }
+    }
+}
+
+impl<C: OnebotConnect> OnebotV11Bot<C> {
+    pub fn from_connect(connect: Arc<C>) -> Self {
+        Self {
+            connect,
+            recorder: None,
+            avatars: AvatarConfig::default(),
+            members: None,
+            messages: None,
+            history: None,
+            replies: None,
+            forwards: ForwardConfig::default(),
+            splitter: None,
+            media: MediaConfig::default(),
+            media_server: None,
+            fetcher: Arc::new(Fetcher::new(FetchConfig::default())),
+        }
+    }
+
+    /// Record every event received and every api call made through this bot, and the bots
+    /// handed to event handlers, see [`crate::record`].
+    pub fn with_recorder(mut self, recorder: Recorder) -> Self {
+        let recorder = Arc::new(recorder);
+        self.connect.record_events(recorder.clone());
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn recorder(&self) -> Option<&Recorder> {
+        self.recorder.as_deref()
+    }
+
+    /// How avatar URLs are filled into profiles, in events and api responses.
+    pub fn with_avatars(mut self, avatars: AvatarConfig) -> Self {
+        self.avatars = avatars;
+        self
+    }
+
+    /// Cache group member and friend lists, filling in the users of notices from them, see
+    /// [`crate::cache`].
+    pub fn with_member_cache(mut self, config: MemberCacheConfig) -> Self {
+        self.members = Some(Arc::new(MemberCache::new(config)));
+        self
+    }
+
+    pub fn member_cache(&self) -> Option<&MemberCache> {
+        self.members.as_deref()
+    }
+
+    /// Keep received messages, so recall and essence notices reach handlers with the content
+    /// of their message, see [`crate::store`].
+    pub fn with_message_store(mut self, config: MessageStoreConfig) -> Self {
+        self.messages = Some(Arc::new(MessageStore::new(config)));
+        self
+    }
+
+    pub fn message_store(&self) -> Option<&MessageStore> {
+        self.messages.as_deref()
+    }
+
+    /// Save every message received and sent to `backend`, see [`crate::history`].
+    pub fn with_history(mut self, backend: impl HistoryBackend) -> Self {
+        self.history = Some(Arc::new(backend));
+        self
+    }
+
+    pub fn history(&self) -> Option<&dyn HistoryBackend> {
+        self.history.as_deref()
+    }
+
+    /// Resolve the messages replies quote in the background, see [`crate::reply`].
+    pub fn with_reply_resolver(mut self, config: ReplyResolverConfig) -> Self {
+        self.replies = Some(Arc::new(ReplyResolver::new(config)));
+        self
+    }
+
+    pub fn reply_resolver(&self) -> Option<&ReplyResolver> {
+        self.replies.as_deref()
+    }
+
+    /// How forward messages are expanded, see [`crate::forward`].
+    pub fn with_forwards(mut self, forwards: ForwardConfig) -> Self {
+        self.forwards = forwards;
+        self
+    }
+
+    /// Split messages too long for a single one, see [`crate::split`].
+    pub fn with_splitter(mut self, splitter: SplitConfig) -> Self {
+        self.splitter = Some(splitter);
+        self
+    }
+
+    /// How local files and base64 content are sent, see [`crate::media`].
+    pub fn with_media(mut self, media: MediaConfig) -> Self {
+        self.media = media;
+        self
+    }
+
+    /// Hand base64 content, and local files unless [`MediaConfig::shared_fs`], to the
+    /// implementation by URL, see [`MediaServer`].
+    pub fn with_media_server(mut self, server: Arc<MediaServer>) -> Self {
+        self.media_server = Some(server);
+        self
+    }
+
+    /// Limits and caching of [`OnebotV11Bot::fetch_file`].
+    pub fn with_fetch(mut self, fetch: FetchConfig) -> Self {
+        self.fetcher = Arc::new(Fetcher::new(fetch));
+        self
+    }
+
+    async fn save_history(&self, message: StoredMessage) {
+        if let Some(history) = &self.history {
+            let id = message.id.clone();
+            if let Err(e) = history.save(message).await {
+                tracing::warn!("Onebotv11: Failed to save message {} to history: {}", id, e);
+            }
+        }
+    }
+
+    /// Parse `event` with what the bot knows beyond it and send it to handlers.
+    async fn dispatch(
+        &self,
+        event: onebot_v11::Event,
+        bot: BotObject,
+        message: Option<MessageEvent>,
+        sender: &broadcast::Sender<Matcher>,
+    ) {
+        let quoted = self
+            .replies
+            .as_ref()
+            .and_then(|replies| replies.resolve(&event, self.messages.as_deref(), &bot));
+        let forwards = if self.forwards.expand_incoming {
+            self.expand_incoming(&event).await
+        } else {
+            HashMap::new()
+        };
+        let context = EventContext {
+            avatars: self.avatars,
+            members: self.members.clone(),
+            message,
+            quoted,
+            forwards: Arc::new(forwards),
+            report: self.connect.held_report(&event).await,
+        };
+        let event = Arc::new(event);
+        let matchers = Matcher::new(Box::new(EventWrapper(Arc::clone(&event), context)), bot);
+        // After parsing, so the event still finds a member it says has left.
+        if let Some(members) = &self.members {
+            members.observe(&event);
+        }
+        if let Some(messages) = &self.messages {
+            let self_id = event_self_id(&event).unwrap_or_default().to_string();
+            for matcher in &matchers {
+                if let Event::MessageEvent(message) = matcher.event.as_ref() {
+                    messages.insert(&self_id, message.clone());
+                }
+            }
+        }
+        for matcher in matchers {
+            match sender.send(matcher) {
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!("Onebotv11: Failed to send event: {:?}", e);
+                }
+            }
+        }
+    }
+
+    /// The account this bot's messages are kept under in the history, from the implementation
+    /// when the connection doesn't know it.
+    async fn history_account(&self) -> String {
+        match self.connect.account_key().await {
+            Some(key) if !key.is_empty() => key,
+            _ => match self.login_info().await {
+                Ok((id, _)) => id,
+                Err(e) => {
+                    tracing::warn!("Onebotv11: Failed to get the account for history: {}", e);
+                    String::new()
+                }
+            },
+        }
+    }
+
+    /// The sender of a message from the history, with what the member cache knows of them.
+    fn stored_sender(&self, stored: &StoredMessage) -> User {
+        let mut sender = User {
+            id: stored.user_id.clone(),
+            profile: Some(UserProfile {
+                nickname: stored.nickname.clone(),
+                avatar: self.avatars.user(&stored.user_id),
+                ..Default::default()
+            }),
+            group_info: None,
+        };
+        let cached = self
+            .members
+            .as_ref()
+            .and_then(|members| match &stored.group_id {
+                Some(group_id) => members.group_member(group_id, &stored.user_id),
+                None => members.friend(&stored.user_id),
+            });
+        if let Some(cached) = cached {
+            merge_profile(&mut sender.profile, cached.profile);
+            sender.group_info = cached.group_info;
+        }
+        sender
+    }
+
+    /// The bot's account and nickname, asking the implementation when the connection wasn't
+    /// told its account.
+    async fn login_info(&self) -> Result<(String, Option<String>)> {
+        let bot = self.connect.bot_info().await;
+        if let Some(id) = bot.id.filter(|id| !id.is_empty()) {
+            return Ok((id, bot.nickname));
+        }
+        let payload = ApiPayload::GetLoginInfo(GetLoginInfo {});
+        match self.call_api(payload.clone()).await?.data {
+            ApiRespData::GetLoginInfoResponse(resp) => {
+                Ok((resp.user_id.to_string(), Some(resp.nickname)))
+            }
+            data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+        }
+    }
+
+    /// The bot itself as the sender of forward nodes.
+    async fn forward_sender(&self) -> Result<User> {
+        let (id, nickname) = self.login_info().await?;
+        Ok(User {
+            id,
+            profile: Some(UserProfile {
+                nickname,
+                ..Default::default()
+            }),
+            group_info: None,
+        })
+    }
+
+    /// Save a message this bot sent to the history, if it keeps one.
+    pub(crate) async fn save_sent(
+        &self,
+        id: String,
+        group_id: Option<String>,
+        peer_id: Option<String>,
+        message: Vec<onebot_v11::MessageSegment>,
+    ) {
+        if self.history.is_some() {
+            let self_id = self.history_account().await;
+            self.save_history(StoredMessage {
+                user_id: self_id.clone(),
+                self_id,
+                id,
+                group_id,
+                peer_id,
+                nickname: self.connect.bot_info().await.nickname,
+                time: chrono::Utc::now(),
+                message,
+                outgoing: true,
+            })
+            .await;
+        }
+    }
+
+    /// Send `message` as a single message, answering with its id.
+    async fn send_part(
+        &self,
+        message: Vec<MessageSegment>,
+        target: &SendMessageTarget,
+    ) -> Result<String, OnebotError> {
+        let message: Vec<_> = message.into_iter().map(parse_segment).collect();
+        let mut prepared = message.clone();
+        let server = self.media_server.as_ref();
+        // What was published is kept until the implementation has answered, by when it has
+        // fetched it.
+        let (payload, group_id, peer_id, _published) = match target {
+            oxidebot::api::payload::SendMessageTarget::Group(id) => {
+                let group_id = GroupId::parse_for("send_group_msg", id)?;
+                let published =
+                    media::prepare(&mut prepared, &self.media, server, "send_group_msg").await?;
+                (
+                    onebot_v11::api::payload::ApiPayload::SendGroupMsg(
+                        onebot_v11::api::payload::SendGroupMsg {
+                            group_id: group_id.into(),
+                            message: prepared,
+                            auto_escape: true,
+                        },
+                    ),
+                    Some(group_id.to_string()),
+                    None,
+                    published,
+                )
+            }
+            oxidebot::api::payload::SendMessageTarget::Private(id) => {
+                let user_id = UserId::parse_for("send_private_msg", id)?;
+                let published =
+                    media::prepare(&mut prepared, &self.media, server, "send_private_msg").await?;
+                (
+                    onebot_v11::api::payload::ApiPayload::SendPrivateMsg(
+                        onebot_v11::api::payload::SendPrivateMsg {
+                            user_id: user_id.into(),
+                            message: prepared,
+                            auto_escape: true,
+                        },
+                    ),
+                    None,
+                    Some(user_id.to_string()),
+                    published,
+                )
+            }
+        };
+        let resp = self.call_api(payload.clone()).await?;
+        match resp.data {
+            onebot_v11::api::resp::ApiRespData::SendPrivateMsgResponse(
+                SendPrivateMsgResponse { message_id },
+            )
+            | onebot_v11::api::resp::ApiRespData::SendGroupMsgResponse(SendGroupMsgResponse {
+                message_id,
+            }) => {
+                self.save_sent(message_id.to_string(), group_id, peer_id, message)
+                    .await;
+                Ok(message_id.to_string())
+            }
+            data => Err(OnebotError::unexpected_response(&payload, &data)),
+        }
+    }
+
+    /// This bot's options on another connection.
+    pub(crate) fn with_connect<D: OnebotConnect>(&self, connect: Arc<D>) -> OnebotV11Bot<D> {
+        OnebotV11Bot {
+            connect,
+            recorder: self.recorder.clone(),
+            avatars: self.avatars,
+            members: self.members.clone(),
+            messages: self.messages.clone(),
+            history: self.history.clone(),
+            replies: self.replies.clone(),
+            forwards: self.forwards,
+            splitter: self.splitter,
+            media: self.media,
+            media_server: self.media_server.clone(),
+            fetcher: self.fetcher.clone(),
+        }
+    }
+
+    /// Subscribe to the raw OneBot events, before they are turned into oxidebot events.
+    pub async fn subscribe(&self) -> broadcast::Receiver<onebot_v11::Event> {
+        self.connect.subscribe().await
+    }
+
+    /// Call any OneBot api, failing with an [`OnebotError`] unless the implementation
+    /// answers with status `ok`.
+    pub async fn call_api(&self, payload: ApiPayload) -> Result<ApiResp, OnebotError> {
+        let action = payload.endpoint();
+        let resp_type = payload.to_resp_type();
+        let error_payload = payload.clone();
+        let raw = self.call_api_raw(payload).await?;
+        serde_json::from_value::<ApiRespBuilder>(raw.clone())
+            .map_err(anyhow::Error::from)
+            .and_then(|builder| builder.build(resp_type))
+            .map_err(|_| OnebotError::UnexpectedResponse {
+                action,
+                payload: payload_value(&error_payload),
+                response: raw,
+            })
+    }
+
+    /// [`Self::call_api`], returning the response as the implementation sent it, for
+    /// responses whose shape differs between implementations.
+    pub(crate) async fn call_api_raw(
+        &self,
+        payload: ApiPayload,
+    ) -> Result<serde_json::Value, OnebotError> {
+        let action = payload.endpoint();
+        let error_payload = payload.clone();
+        let raw = match &self.recorder {
+            Some(recorder) => {
+                let recorded_at = record::now();
+                let raw = self.connect.clone().call_api(payload).await;
+                recorder.call(recorded_at, &error_payload, &raw);
+                raw?
+            }
+            None => self.connect.clone().call_api(payload).await?,
+        };
+        let status = raw["status"].as_str().unwrap_or("failed").to_string();
+        if status != "ok" {
+            return Err(OnebotError::Api {
+                action,
+                payload: payload_value(&error_payload),
+                status,
+                retcode: raw["retcode"].as_i64().unwrap_or_default(),
+                message: raw["message"].as_str().map(|s| s.to_string()),
+                wording: raw["wording"].as_str().map(|s| s.to_string()),
+            });
+        }
+        Ok(raw)
+    }
+}
+
+impl<C: OnebotConnect> BotTrait for OnebotV11Bot<C> {
+    fn bot_info<'life0, 'async_trait>(
+        &'life0 self,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = BotInfo> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move { self.connect.bot_info().await })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn start_sending_events<'life0, 'async_trait>(
+        &'life0 self,
+        sender: broadcast::Sender<Matcher>,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = ()> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let mut subscriber = self.connect.subscribe().await;
+            loop {
+                let event = match subscriber.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(
+                            "Onebotv11: Event receiver lagged, {} events skipped",
+                            skipped
+                        );
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                self.connect.event_taken(&event);
+                let account = self.connect.event_account(&event).await;
+                if let Some(message) = StoredMessage::from_event(&event) {
+                    if self.history.is_some() {
+                        let key = match &account {
+                            Some(account) => account.account_key().await,
+                            None => self.connect.account_key().await,
+                        };
+                        // Without a configured account, the event says which one received it.
+                        let self_id = key
+                            .filter(|key| !key.is_empty())
+                            .unwrap_or_else(|| message.self_id.clone());
+                        self.save_history(StoredMessage { self_id, ..message })
+                            .await;
+                    }
+                }
+                let bot: BotObject = match account {
+                    Some(account) => Box::new(self.with_connect(account)),
+                    None => <Self as BotTrait>::clone_box(self),
+                };
+                match self
+                    .messages
+                    .as_ref()
+                    .and_then(|messages| messages.lookup(&event, &bot))
+                {
+                    // Dispatched by a task of its own, so the events behind it don't wait.
+                    Some(Lookup::Fetching(fetch)) => {
+                        let this = self.clone();
+                        let sender = sender.clone();
+                        tokio::spawn(async move {
+                            let message = fetch.await;
+                            this.dispatch(event, bot, message, &sender).await;
+                        });
+                    }
+                    Some(Lookup::Stored(message)) => {
+                        self.dispatch(event, bot, Some(*message), &sender).await
+                    }
+                    None => self.dispatch(event, bot, None, &sender).await,
+                }
+            }
+        })
+    }
+
+    fn clone_box(&self) -> BotObject {
+        Box::new(self.clone())
+    }
+
+    fn server(&self) -> &'static str {
+        PLATFORM
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<C: OnebotConnect> CallApiTrait for OnebotV11Bot<C> {
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn send_message<'life0, 'async_trait>(
+        &'life0 self,
+        message: Vec<MessageSegment>,
+        target: SendMessageTarget,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<Vec<SendMessageResponse>>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            if forward::is_forward(&message) {
+                let sent = self.send_forward_message(message, target).await?;
+                return Ok(vec![SendMessageResponse {
+                    sent_message_id: sent.message_id,
+                }]);
+            }
+            let parts = match &self.splitter {
+                Some(splitter) => split::split_message(message, splitter),
+                None => vec![message],
+            };
+            let forward_after = self.splitter.and_then(|splitter| splitter.forward_after);
+            if forward_after.is_some_and(|forward_after| parts.len() > forward_after) {
+                let user = self.forward_sender().await?;
+                let nodes = parts
+                    .into_iter()
+                    .map(|segments| MessageSegment::ForwardCustomNode {
+                        user: Some(user.clone()),
+                        message: Message {
+                            id: String::new(),
+                            segments,
+                        },
+                    })
+                    .collect();
+                let sent = self.send_forward_message(nodes, target).await?;
+                return Ok(vec![SendMessageResponse {
+                    sent_message_id: sent.message_id,
+                }]);
+            }
+            let mut sent = Vec::with_capacity(parts.len());
+            for part in parts {
+                sent.push(SendMessageResponse {
+                    sent_message_id: self.send_part(part, &target).await?,
+                });
+            }
+            Ok(sent)
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn delete_message<'life0, 'async_trait>(
+        &'life0 self,
+        message_id: String,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = onebot_v11::api::payload::ApiPayload::DeleteMsg(DeleteMsg {
+                message_id: MessageId::parse_for("delete_msg", &message_id)?.into(),
+            });
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_message_detail<'life0, 'async_trait>(
+        &'life0 self,
+        message_id: String,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<GetMessageDetailResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload =
+                onebot_v11::api::payload::ApiPayload::GetMsg(onebot_v11::api::payload::GetMsg {
+                    message_id: MessageId::parse_for("get_msg", &message_id)?.into(),
+                });
+            if let Some(history) = &self.history {
+                let self_id = self.history_account().await;
+                match history.get(&self_id, &message_id).await {
+                    Ok(Some(stored)) => {
+                        return Ok(GetMessageDetailResponse {
+                            message: stored.segments(),
+                            sender: Some(self.stored_sender(&stored)),
+                            time: Some(stored.time),
+                        })
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!(
+                        "Onebotv11: Failed to read message {} from history: {}",
+                        message_id, e
+                    ),
+                }
+            }
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetMsgResponse(resp) => {
+                    Ok(GetMessageDetailResponse {
+                        message: resp.message.into_iter().map(cast_segment).collect(),
+                        sender: Some(User {
+                            id: resp.sender.user_id.unwrap_or_default().to_string(),
+                            profile: Some(UserProfile {
+                                nickname: resp.sender.nickname,
+                                sex: Some(Sex::from(resp.sender.sex.unwrap_or_default().as_str())),
+                                age: resp.sender.age.map(|a| a as u64),
+                                avatar: resp.sender.user_id.and_then(|id| self.avatars.user(id)),
+                                email: None,
+                                phone: None,
+                                signature: None,
+                                level: None,
+                            }),
+                            group_info: None,
+                        }),
+                        time: DateTime::from_timestamp(resp.time, 0),
+                    })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn set_message_reaction<'life0, 'async_trait>(
+        &'life0 self,
+        message_id: String,
+        reaction_id: String,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = onebot_v11::api::payload::ApiPayload::SetMsgEmojiLike(SetMsgEmojiLike {
+                message_id: MessageId::parse_for("set_msg_emoji_like", &message_id)?.to_string(),
+                emoji_id: reaction_id,
+            });
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_group_member_list<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<GroupMemberListResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let group_id = GroupId::parse_for("get_group_member_list", &group_id)?;
+            if let Some(members) = self
+                .members
+                .as_ref()
+                .and_then(|cache| cache.group_members(&group_id.to_string()))
+            {
+                return Ok(GroupMemberListResponse { members });
+            }
+            let payload =
+                onebot_v11::api::payload::ApiPayload::GetGroupMemberList(GetGroupMemberList {
+                    group_id: group_id.into(),
+                });
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetGroupMemberListResponse(resp) => {
+                    let members: Vec<User> = resp
+                        .into_iter()
+                        .map(|m| User {
+                            id: m.user_id.to_string(),
+                            profile: Some(UserProfile {
+                                nickname: Some(m.nickname),
+                                sex: Some(Sex::from(m.sex.as_str())),
+                                age: Some(m.age as u64),
+                                avatar: self.avatars.user(m.user_id),
+                                email: None,
+                                phone: None,
+                                signature: None,
+                                level: None,
+                            }),
+                            group_info: Some(UserGroupInfo {
+                                role: Some(match m.role.to_lowercase().as_str() {
+                                    "owner" => oxidebot::source::user::Role::Owner,
+                                    "admin" => oxidebot::source::user::Role::Admin,
+                                    "member" => oxidebot::source::user::Role::Member,
+                                    _ => oxidebot::source::user::Role::Unknown,
+                                }),
+                                join_time: DateTime::from_timestamp(m.join_time, 0),
+                                last_active_time: DateTime::from_timestamp(m.last_sent_time, 0),
+                                level: Some(m.level),
+                                alias: Some(m.title),
+                            }),
+                        })
+                        .collect();
+                    if let Some(cache) = &self.members {
+                        cache.set_group_members(group_id.to_string(), members.clone());
+                    }
+                    Ok(GroupMemberListResponse { members })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn kick_group_member<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        user_id: String,
+        reject_add_request: Option<bool>,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = SetGroupKick {
+                group_id: GroupId::parse_for("set_group_kick", &group_id)?.into(),
+                user_id: UserId::parse_for("set_group_kick", &user_id)?.into(),
+                reject_add_request: reject_add_request.unwrap_or(false),
+            };
+            self.call_api(onebot_v11::api::payload::ApiPayload::SetGroupKick(payload))
+                .await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn mute_group<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        duration: Option<Duration>,
+        r#type: GroupMuteType,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        if duration.is_some() {
+            warn!("Onebotv11: Mute Group duration is not supported");
+        }
+        Box::pin(async move {
+            let payload = SetGroupWholeBan {
+                group_id: GroupId::parse_for("set_group_whole_ban", &group_id)?.into(),
+                enable: match r#type {
+                    oxidebot::api::payload::GroupMuteType::Mute => true,
+                    oxidebot::api::payload::GroupMuteType::Unmute => false,
+                },
+            };
+            self.call_api(onebot_v11::api::payload::ApiPayload::SetGroupWholeBan(
+                payload,
+            ))
+            .await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn mute_group_member<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        user_id: String,
+        r#type: GroupMuteType,
+        duration: Option<Duration>,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = SetGroupBan {
+                group_id: GroupId::parse_for("set_group_ban", &group_id)?.into(),
+                user_id: UserId::parse_for("set_group_ban", &user_id)?.into(),
+                duration: match r#type {
+                    GroupMuteType::Mute => {
+                        duration.unwrap_or(Duration::from_secs(60)).as_secs() as i64
+                    }
+                    GroupMuteType::Unmute => 0,
+                },
+            };
+            self.call_api(onebot_v11::api::payload::ApiPayload::SetGroupBan(payload))
+                .await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn change_group_admin<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        user_id: String,
+        r#type: GroupAdminChangeType,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = SetGroupAdmin {
+                group_id: GroupId::parse_for("set_group_admin", &group_id)?.into(),
+                user_id: UserId::parse_for("set_group_admin", &user_id)?.into(),
+                enable: match r#type {
+                    oxidebot::api::payload::GroupAdminChangeType::Set => true,
+                    oxidebot::api::payload::GroupAdminChangeType::Unset => false,
+                },
+            };
+            self.call_api(onebot_v11::api::payload::ApiPayload::SetGroupAdmin(payload))
+                .await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn set_group_member_alias<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        user_id: String,
+        new_alias: String,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = onebot_v11::api::payload::ApiPayload::SetGroupCard(SetGroupCard {
+                group_id: GroupId::parse_for("set_group_card", &group_id)?.into(),
+                user_id: UserId::parse_for("set_group_card", &user_id)?.into(),
+                card: new_alias,
+            });
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_group_profile<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<GroupGetProfileResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = onebot_v11::api::payload::ApiPayload::GetGroupInfo(
+                onebot_v11::api::payload::GetGroupInfo {
+                    group_id: GroupId::parse_for("get_group_info", &group_id)?.into(),
+                    no_cache: true,
+                },
+            );
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetGroupInfoResponse(resp) => {
+                    Ok(GroupGetProfileResponse {
+                        profile: oxidebot::source::group::GroupProfile {
+                            name: Some(resp.group_name),
+                            avatar: self.avatars.group(resp.group_id),
+                            member_count: Some(u64::try_from(resp.member_count).unwrap_or(0)),
+                        },
+                    })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn set_group_profile<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        new_profile: GroupProfile,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        if new_profile.avatar.is_some() {
+            tracing::warn!("Onebotv11: Set Group avatar is not supported");
+        }
+        Box::pin(async move {
+            let group_name = new_profile.name.ok_or_else(|| {
+                OnebotError::invalid_argument("set_group_name", "no new name to set")
+            })?;
+            let payload = onebot_v11::api::payload::ApiPayload::SetGroupName(SetGroupName {
+                group_id: GroupId::parse_for("set_group_name", &group_id)?.into(),
+                group_name,
+            });
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_group_file_count<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        parent_folder_id: Option<String>,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<GroupGetFileCountResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        if parent_folder_id.is_some() {
+            tracing::error!("Onebotv11: Get Group File Parent Fold ID is not supported");
+        }
+        Box::pin(async move {
+            let payload =
+                onebot_v11::api::payload::ApiPayload::GetGroupFileCount(GetGroupFileCount {
+                    group_id: GroupId::parse_for("get_group_file_count", &group_id)?.into(),
+                });
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetGroupFileCountResponse(resp) => {
+                    Ok(GroupGetFileCountResponse { count: resp.count })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_group_fs_list<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        start_index: u64,
+        count: u64,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<GroupGetFsListResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload =
+                onebot_v11::api::payload::ApiPayload::GetGroupFileList(GetGroupFileList {
+                    group_id: GroupId::parse_for("get_group_file_list", &group_id)?.into(),
+                    start_index: {
+                        if start_index > i64::MAX as u64 {
+                            tracing::error!("Onebotv11: Start index is too large: {}", start_index);
+                            i64::MAX
+                        } else {
+                            start_index as i64
+                        }
+                    },
+                    file_count: {
+                        if count > i64::MAX as u64 {
+                            tracing::error!("Onebotv11: Count is too large: {}", count);
+                            i64::MAX
+                        } else {
+                            count as i64
+                        }
+                    },
+                });
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data{
+                onebot_v11::api::resp::ApiRespData::GetGroupFileListResponse(resp)=>{
+                    Ok(GroupGetFsListResponse {
+                        fs_tree: resp
+                            .file_list
+                            .into_iter()
+                            .map(|f| {
+                                if let Some(folder_info) = f.folder_info {
+                                    FsNode::Folder(Folder {
+                                        id: folder_info.folder_id,
+                                        name: folder_info.folder_name,
+                                        file_amount: u64::try_from(folder_info.total_file_count).unwrap_or(0),
+                                        children: Vec::with_capacity(0),
+                                    })
+                                } else if let Some(file_info) = f.file_info {
+                                    FsNode::File(File {
+                                        id: Some(file_info.file_id),
+                                        name: file_info.file_name,
+                                        uri: None,
+                                        base64: None,
+                                        r#mime: None,
+                                        size: {
+                                            match file_info.file_size.parse::<u64>() {
+                                                Ok(size) => Some(size),
+                                                Err(e) =>{
+                                                    tracing::error!("Onebotv11: Failed to parse file size: {}, error: {}", file_info.file_size, e);
+                                                    None
+                                                },
+                                            }
+                                        },
+                                    })
+                                } else {
+                                    FsNode::Unknown
+                                }
+                            })
+                            .collect(),
+                    })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn delete_group_file<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        file_id: String,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = onebot_v11::api::payload::ApiPayload::DelGroupFile(
+                onebot_v11::api::payload::DelGroupFile {
+                    group_id: GroupId::parse_for("del_group_file", &group_id)?.into(),
+                    file_id,
+                },
+            );
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn delete_group_folder<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        folder_id: String,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = onebot_v11::api::payload::ApiPayload::DelGroupFileFolder(
+                onebot_v11::api::payload::DelGroupFileFolder {
+                    group_id: GroupId::parse_for("del_group_file_folder", &group_id)?.into(),
+                    folder_id,
+                },
+            );
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn create_group_folder<'life0, 'async_trait>(
+        &'life0 self,
+        group_id: String,
+        folder_name: String,
+        parent_folder_id: Option<String>,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        if parent_folder_id.is_some() {
+            tracing::error!("Onebotv11: Create Group Folder Parent Fold ID is not supported");
+        }
+        Box::pin(async move {
+            let payload =
+                onebot_v11::api::payload::ApiPayload::SetGroupFileFolder(SetGroupFileFolder {
+                    group_id: GroupId::parse_for("set_group_file_folder", &group_id)?.into(),
+                    folder_name,
+                });
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_user_profile<'life0, 'async_trait>(
+        &'life0 self,
+        user_id: String,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<UserGetProfileResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        Box::pin(async move {
+            let payload = onebot_v11::api::payload::ApiPayload::GetStrangerInfo(GetStrangerInfo {
+                user_id: UserId::parse_for("get_stranger_info", &user_id)?.into(),
+                no_cache: true,
+            });
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetStrangerInfoResponse(resp) => {
+                    Ok(UserGetProfileResponse {
+                        profile: UserProfile {
+                            nickname: Some(resp.nickname),
+                            sex: Some(Sex::from(resp.sex.as_str())),
+                            age: Some(resp.age as u64),
+                            avatar: self.avatars.user(resp.user_id),
+                            ..Default::default()
+                        },
+                    })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn set_bot_profile<'life0, 'async_trait>(
+        &'life0 self,
+        new_profile: UserProfile,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let payload = if let Some(avatar) = new_profile.avatar {
+            Ok(onebot_v11::api::payload::ApiPayload::SetQQAvatar(
+                SetQQAvatar {
+                    file: avatar.to_string(),
+                },
+            ))
+        } else {
+            Err(OnebotError::invalid_argument(
+                "set_qq_avatar",
+                "new bot avatar is None",
+            ))
+        };
+        Box::pin(async move {
+            self.call_api(payload?).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_bot_profile<'life0, 'async_trait>(
+        &'life0 self,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<BotGetProfileResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let payload = onebot_v11::api::payload::ApiPayload::GetLoginInfo(
+            onebot_v11::api::payload::GetLoginInfo {},
+        );
+        Box::pin(async move {
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetLoginInfoResponse(resp) => {
+                    Ok(BotGetProfileResponse {
+                        profile: UserProfile {
+                            nickname: Some(resp.nickname),
+                            avatar: self.avatars.user(resp.user_id),
+                            ..Default::default()
+                        },
+                    })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_bot_friend_list<'life0, 'async_trait>(
+        &'life0 self,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<BotGetFriendListResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let payload = onebot_v11::api::payload::ApiPayload::GetFriendList(
+            onebot_v11::api::payload::GetFriendList {},
+        );
+
+        Box::pin(async move {
+            let bot_id = match &self.members {
+                Some(_) => self.connect.account_key().await,
+                None => None,
+            };
+            if let Some(friends) = self
+                .members
+                .as_ref()
+                .zip(bot_id.as_deref())
+                .and_then(|(cache, bot_id)| cache.friends(bot_id))
+            {
+                return Ok(BotGetFriendListResponse { friends });
+            }
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetFriendListResponse(resp) => {
+                    let friends: Vec<User> = resp
+                        .into_iter()
+                        .map(|f| User {
+                            id: f.user_id.to_string(),
+                            profile: Some(UserProfile {
+                                nickname: Some(f.nickname),
+                                avatar: self.avatars.user(f.user_id),
+                                ..Default::default()
+                            }),
+                            group_info: None,
+                        })
+                        .collect();
+                    if let (Some(cache), Some(bot_id)) = (&self.members, bot_id) {
+                        cache.set_friends(bot_id, friends.clone());
+                    }
+                    Ok(BotGetFriendListResponse { friends })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_bot_group_list<'life0, 'async_trait>(
+        &'life0 self,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<BotGetGroupListResponse>>
+                + ::core::marker::Send
+                + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let payload = onebot_v11::api::payload::ApiPayload::GetGroupList(
+            onebot_v11::api::payload::GetGroupList {},
+        );
+        Box::pin(async move {
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetGroupListResponse(resp) => {
+                    Ok(BotGetGroupListResponse {
+                        groups: resp
+                            .into_iter()
+                            .map(|g| oxidebot::source::group::Group {
+                                id: g.group_id.to_string(),
+                                profile: Some(oxidebot::source::group::GroupProfile {
+                                    name: Some(g.group_name),
+                                    avatar: self.avatars.group(g.group_id),
+                                    member_count: Some(u64::try_from(g.member_count).unwrap_or(0)),
+                                }),
+                            })
+                            .collect(),
+                    })
+                }
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn handle_add_friend_request<'life0, 'async_trait>(
+        &'life0 self,
+        id: String,
+        response: RequestResponse,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let payload =
+            onebot_v11::api::payload::ApiPayload::SetFriendAddRequest(SetFriendAddRequest {
+                flag: id,
+                approve: match response {
+                    oxidebot::api::payload::RequestResponse::Approve => true,
+                    oxidebot::api::payload::RequestResponse::Reject => false,
+                },
+                remark: None,
+            });
+        Box::pin(async move {
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn handle_add_group_request<'life0, 'async_trait>(
+        &'life0 self,
+        id: String,
+        response: RequestResponse,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let payload =
+            onebot_v11::api::payload::ApiPayload::SetGroupAddRequest(SetGroupAddRequest {
+                flag: id,
+                sub_type: "add".to_string(),
+                reason: None,
+                approve: match response {
+                    oxidebot::api::payload::RequestResponse::Approve => true,
+                    oxidebot::api::payload::RequestResponse::Reject => false,
+                },
+            });
+        Box::pin(async move {
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn handle_invite_group_request<'life0, 'async_trait>(
+        &'life0 self,
+        id: String,
+        response: RequestResponse,
+    ) -> ::core::pin::Pin<
+        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let payload =
+            onebot_v11::api::payload::ApiPayload::SetGroupAddRequest(SetGroupAddRequest {
+                flag: id,
+                sub_type: "invite".to_string(),
+                reason: None,
+                approve: match response {
+                    oxidebot::api::payload::RequestResponse::Approve => true,
+                    oxidebot::api::payload::RequestResponse::Reject => false,
+                },
+            });
+        Box::pin(async move {
+            self.call_api(payload).await?;
+            Ok(())
+        })
+    }
+
+    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
+    fn get_file_info<'life0, 'async_trait>(
+        &'life0 self,
+        file_id: String,
+    ) -> ::core::pin::Pin<
+        Box<
+            dyn ::core::future::Future<Output = Result<File>> + ::core::marker::Send + 'async_trait,
+        >,
+    >
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        let payload = onebot_v11::api::payload::ApiPayload::GetFile(GetFile { file_id });
+        Box::pin(async move {
+            let resp = self.call_api(payload.clone()).await?;
+            match resp.data {
+                onebot_v11::api::resp::ApiRespData::GetFileResponse(resp) => Ok(File {
+                    name: resp.file_name,
+                    uri: None,
+                    mime: None,
+                    size: Some(resp.file_size),
+                    base64: Some(resp.base64),
+                    id: None,
+                }),
+                data => Err(OnebotError::unexpected_response(&payload, &data).into()),
+            }
+        })
+    }
+}
@@ -0,0 +1,219 @@
+use std::{future::Future, sync::Arc, time::Duration};
+
+use futures_util::{Sink, SinkExt as _, Stream, StreamExt as _};
+use onebot_v11::{
+    api::payload::ApiPayload,
+    connect::WsApiPayload,
+    event::meta::{Lifecycle, Meta},
+};
+use oxidebot::source::bot::BotInfo;
+use serde_json::Value;
+use tokio::sync::{broadcast, watch, Mutex};
+use tokio_tungstenite::tungstenite::{self, Message};
+
+use crate::{
+    error::OnebotError,
+    record::{EventTap, Recorder},
+};
+
+use super::http::HeldReport;
+
+/// A transport that can talk to a OneBot v11 implementation.
+///
+/// [`OnebotV11Bot`](super::api::OnebotV11Bot) is generic over this trait, so every
+/// `CallApiTrait` method is written once and shared by all transports.
+pub trait OnebotConnect: Send + Sync + 'static {
+    /// Send an api request and wait for the raw response, `status` is checked by the caller.
+    fn call_api(
+        self: Arc<Self>,
+        payload: ApiPayload,
+    ) -> impl Future<Output = Result<Value, OnebotError>> + Send;
+
+    /// Subscribe to the events pushed by the implementation.
+    fn subscribe(&self) -> impl Future<Output = broadcast::Receiver<onebot_v11::Event>> + Send;
+
+    /// The report `event` came in, while it's held open for a quick operation. Handing it out
+    /// moves it into the event, so it's only returned once.
+    fn held_report(
+        &self,
+        _event: &onebot_v11::Event,
+    ) -> impl Future<Output = Option<Arc<HeldReport>>> + Send {
+        async { None }
+    }
+
+    /// Information about the account behind this connection.
+    fn bot_info(&self) -> impl Future<Output = BotInfo> + Send;
+
+    /// Called as the bot takes `event` off its subscription, before handling it.
+    fn event_taken(&self, _event: &onebot_v11::Event) {}
+
+    /// Hand `recorder` every event as it arrives, before it's parsed, see [`crate::record`].
+    /// Connections that don't override this record no events.
+    fn record_events(&self, _recorder: Arc<Recorder>) {}
+
+    /// What per-account caches are keyed by, `None` when the account isn't known, as while
+    /// several accounts share one connection.
+    fn account_key(&self) -> impl Future<Output = Option<String>> + Send {
+        async { Some(self.bot_info().await.id.unwrap_or_default()) }
+    }
+
+    /// The connection of the account that received `event`, handlers get a bot built on it.
+    /// `None` hands them the bot built on this connection, connections serving several
+    /// accounts return the account's connection here.
+    fn event_account(
+        &self,
+        _event: &onebot_v11::Event,
+    ) -> impl Future<Output = Option<Arc<impl OnebotConnect>>> + Send
+    where
+        Self: Sized,
+    {
+        async { None::<Arc<Self>> }
+    }
+}
+
+/// The state shared by the websocket transports: the sending half of the socket, if any,
+/// and the channels that route incoming frames to event subscribers and api callers.
+pub(crate) struct WsChannel<S> {
+    write: Mutex<Option<S>>,
+    event_sender: broadcast::Sender<onebot_v11::Event>,
+    api_response_sender: broadcast::Sender<Arc<Value>>,
+    tap: Arc<EventTap>,
+    /// Ticks whenever the socket goes away, failing the calls still waiting for a response.
+    disconnected: watch::Sender<()>,
+}
+
+impl<S> WsChannel<S>
+where
+    S: Sink<Message, Error = tungstenite::Error> + Unpin + Send,
+{
+    pub(crate) fn new() -> Self {
+        Self::with_event_sender(broadcast::channel(100).0, Arc::default())
+    }
+
+    /// A channel that pushes its events into an existing stream, shared with other channels,
+    /// and records them with their recorder.
+    pub(crate) fn with_event_sender(
+        event_sender: broadcast::Sender<onebot_v11::Event>,
+        tap: Arc<EventTap>,
+    ) -> Self {
+        WsChannel {
+            write: Mutex::new(None),
+            event_sender,
+            api_response_sender: broadcast::channel(100).0,
+            tap,
+            disconnected: watch::channel(()).0,
+        }
+    }
+
+    pub(crate) fn record_events(&self, recorder: Arc<Recorder>) {
+        self.tap.set(recorder);
+    }
+
+    /// Set the sending half of the socket, `None` once it's gone.
+    pub(crate) async fn set_write(&self, write: Option<S>) {
+        let disconnected = write.is_none();
+        *self.write.lock().await = write;
+        if disconnected {
+            self.disconnected.send_replace(());
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<onebot_v11::Event> {
+        self.event_sender.subscribe()
+    }
+
+    pub(crate) async fn call_api(&self, payload: ApiPayload) -> Result<Value, OnebotError> {
+        let error_payload = payload.clone();
+        let ws_api_data: WsApiPayload = payload.into();
+        let echo = ws_api_data.echo.clone();
+        let ws_api_string = serde_json::to_string(&ws_api_data)
+            .map_err(|e| OnebotError::transport(&error_payload, e))?;
+        let mut subscriber = self.api_response_sender.subscribe();
+        let mut disconnected = self.disconnected.subscribe();
+        {
+            let mut write = self.write.lock().await;
+            let write = write
+                .as_mut()
+                .ok_or_else(|| OnebotError::disconnected(&error_payload))?;
+            write
+                .send(Message::Text(ws_api_string))
+                .await
+                .map_err(|e| OnebotError::transport(&error_payload, e))?;
+        }
+        let resp = tokio::time::timeout(Duration::from_secs(30), async {
+            loop {
+                tokio::select! {
+                    biased;
+                    resp = subscriber.recv() => match resp {
+                        Ok(resp) if resp["echo"].as_str() == Some(echo.as_str()) => break Ok(resp),
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => {
+                            break Err(OnebotError::disconnected(&error_payload))
+                        }
+                    },
+                    _ = disconnected.changed() => {
+                        break Err(OnebotError::disconnected(&error_payload))
+                    }
+                }
+            }
+        })
+        .await
+        .map_err(|_| OnebotError::timeout(&error_payload))??;
+        Ok(Arc::unwrap_or_clone(resp))
+    }
+
+    /// Route frames from `read` until the socket closes or errors.
+    pub(crate) async fn read_messages<R>(&self, read: &mut R)
+    where
+        R: Stream<Item = Result<Message, tungstenite::Error>> + Unpin,
+    {
+        while let Some(msg) = read.next().await {
+            let msg = match msg {
+                Ok(Message::Text(msg)) => msg,
+                Ok(Message::Close(_)) => break,
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::warn!("Onebotv11: Error receiving websocket message: {}", e);
+                    break;
+                }
+            };
+            let value = match serde_json::from_str::<Value>(&msg) {
+                Ok(value) => value,
+                Err(e) => {
+                    tracing::warn!("Onebotv11: Error parsing Event: {}, Raw: {}", e, msg);
+                    continue;
+                }
+            };
+            if value.get("post_type").is_none() {
+                // Nobody waiting for this response is not an error.
+                let _ = self.api_response_sender.send(Arc::new(value));
+                continue;
+            }
+            self.tap.record(&value);
+            match serde_json::from_value::<onebot_v11::Event>(value) {
+                Ok(event) => {
+                    if let Err(e) = self.event_sender.send(event) {
+                        tracing::warn!("Onebotv11: Error sending Event: {}", e);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!("Onebotv11: Error parsing Event: {}, Raw: {}", e, msg);
+                }
+            }
+        }
+    }
+
+    /// Push a synthetic lifecycle event, `parse_event` turns `connect` into
+    /// `MetaEvent::ConnectEvent` and `disable` into `MetaEvent::DisconnectEvent`.
+    pub(crate) fn send_lifecycle(&self, self_id: Option<&str>, sub_type: &str) {
+        let event = onebot_v11::Event::Meta(Meta::Lifecycle(Lifecycle {
+            time: chrono::Utc::now().timestamp(),
+            self_id: self_id.and_then(|id| id.parse().ok()).unwrap_or_default(),
+            post_type: "meta_event".to_string(),
+            meta_event_type: "lifecycle".to_string(),
+            sub_type: sub_type.to_string(),
+        }));
+        // Without subscribers there is nobody to tell about the transition.
+        let _ = self.event_sender.send(event);
+    }
+}
@@ -0,0 +1,459 @@
+use std::{
+    convert::Infallible,
+    sync::{Arc, Weak},
+    time::Duration,
+};
+
+use anyhow::Result;
+use hmac::{Hmac, Mac as _};
+use http_body_util::{BodyExt as _, Full, Limited};
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use onebot_v11::{api::payload::ApiPayload, connect::http::HttpConfig, traits::EndPoint as _};
+use oxidebot::{
+    matcher::Matcher,
+    source::{bot::BotInfo, message::MessageSegment},
+};
+use serde::Serialize;
+use serde_json::Value;
+use sha1::Sha1;
+use tokio::{
+    net::TcpListener,
+    sync::{broadcast, oneshot, Mutex},
+};
+
+use crate::{
+    error::{payload_value, ConnectError, OnebotError},
+    event::EventWrapper,
+    record::{EventTap, Recorder},
+    segment::parse_segment,
+};
+
+use super::{api::OnebotV11Bot, connect::OnebotConnect};
+
+/// The largest event report read, larger ones are refused before their signature is checked.
+const MAX_REPORT_SIZE: usize = 4 * 1024 * 1024;
+
+/// Config for [`OnebotV11HttpBot`].
+///
+/// `api` points at the implementation's HTTP API, while `post_host`/`post_port`/`post_suffix`
+/// is where this crate listens for the implementation's HTTP POST event reports.
+/// When `secret` is set, every report must carry a matching `X-Signature: sha1=<hmac>` header.
+/// When `quick_operation_timeout` is set, message and request reports are held open for at most
+/// that long so a handler can answer them with [`QuickOperation::answer`], they are released
+/// early once every handler is done with the event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OnebotV11HttpConfig {
+    pub api: HttpConfig,
+    pub post_host: String,
+    pub post_port: u16,
+    pub post_suffix: String,
+    pub secret: Option<String>,
+    pub quick_operation_timeout: Option<Duration>,
+    pub bot_id: Option<String>,
+    pub bot_nick_name: Option<String>,
+}
+
+impl Default for OnebotV11HttpConfig {
+    fn default() -> Self {
+        OnebotV11HttpConfig {
+            api: HttpConfig {
+                host: "127.0.0.1".to_string(),
+                port: 3000,
+                access_token: None,
+            },
+            post_host: "127.0.0.1".to_string(),
+            post_port: 8082,
+            post_suffix: "onebot/v11".to_string(),
+            secret: None,
+            quick_operation_timeout: None,
+            bot_id: None,
+            bot_nick_name: None,
+        }
+    }
+}
+
+/// A quick operation, returned as the response body of an HTTP POST event report.
+///
+/// `reply`, `auto_escape`, `at_sender`, `delete`, `kick`, `ban` and `ban_duration` apply to
+/// message events, `approve`, `remark` and `reason` apply to request events.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct QuickOperation {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reply: Option<Vec<onebot_v11::MessageSegment>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_escape: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub at_sender: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delete: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kick: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ban_duration: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub approve: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remark: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl QuickOperation {
+    pub fn reply(message: Vec<MessageSegment>) -> Self {
+        QuickOperation {
+            reply: Some(message.into_iter().map(parse_segment).collect()),
+            ..Default::default()
+        }
+    }
+
+    /// Answer the held report `matcher`'s event came in.
+    ///
+    /// Fails when the report isn't held, see [`OnebotV11HttpConfig::quick_operation_timeout`],
+    /// or it has been answered or released already.
+    pub fn answer(self, matcher: &Matcher) -> Result<()> {
+        matcher
+            .event_object
+            .as_any()
+            .downcast_ref::<EventWrapper>()
+            .and_then(|event| event.1.report.as_ref())
+            .ok_or(anyhow::anyhow!(
+                "Onebotv11: Event is not waiting for a quick operation"
+            ))?
+            .answer(self)
+    }
+}
+
+/// An event report held open for a quick operation.
+///
+/// The report is released with an empty response once the last event holding this is dropped,
+/// that is once every handler is done with it.
+pub struct HeldReport(std::sync::Mutex<Option<oneshot::Sender<QuickOperation>>>);
+
+impl HeldReport {
+    fn answer(&self, operation: QuickOperation) -> Result<()> {
+        self.0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take()
+            .ok_or(anyhow::anyhow!(
+                "Onebotv11: Event has already been answered"
+            ))?
+            .send(operation)
+            .map_err(|_| anyhow::anyhow!("Onebotv11: Quick operation deadline has passed"))
+    }
+}
+
+/// A held report, owned here until the event is handed to handlers.
+struct PendingReport {
+    event: onebot_v11::Event,
+    report: Weak<HeldReport>,
+    undispatched: Option<Arc<HeldReport>>,
+}
+
+/// Calls the HTTP API and receives events through an embedded webhook server.
+pub struct HttpWebhookConnect {
+    pub config: OnebotV11HttpConfig,
+    client: reqwest::Client,
+    event_sender: broadcast::Sender<onebot_v11::Event>,
+    tap: EventTap,
+    pending_operations: Mutex<Vec<PendingReport>>,
+}
+
+impl HttpWebhookConnect {
+    pub async fn new(config: OnebotV11HttpConfig) -> Result<Arc<Self>, ConnectError> {
+        let addr = format!("{}:{}", config.post_host, config.post_port);
+        let listener = TcpListener::bind(&addr)
+            .await
+            .map_err(|source| ConnectError::Bind { addr, source })?;
+        let self_ = Arc::new(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .map_err(|e| ConnectError::Client { source: e })?,
+            config,
+            event_sender: broadcast::channel(100).0,
+            tap: EventTap::default(),
+            pending_operations: Mutex::new(Vec::new()),
+        });
+        self_.clone().start_webhook_server(listener);
+        Ok(self_)
+    }
+
+    fn start_webhook_server(self: Arc<Self>, listener: TcpListener) {
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::warn!("Onebotv11: Failed to accept webhook connection: {}", e);
+                        continue;
+                    }
+                };
+                let self_ = self.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| self_.clone().handle_post(req));
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        tracing::warn!("Onebotv11: Webhook connection error: {}", e);
+                    }
+                });
+            }
+        });
+    }
+
+    async fn handle_post(
+        self: Arc<Self>,
+        req: Request<Incoming>,
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        if req.method() != Method::POST {
+            return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let path = req.uri().path().trim_end_matches('/');
+        if !path.ends_with(self.config.post_suffix.trim_end_matches('/')) {
+            return Ok(status_response(StatusCode::NOT_FOUND));
+        }
+        let signature = req
+            .headers()
+            .get("X-Signature")
+            .map(|v| v.to_str().unwrap_or("").to_string());
+        let body = match Limited::new(req.into_body(), MAX_REPORT_SIZE)
+            .collect()
+            .await
+        {
+            Ok(body) => body.to_bytes(),
+            Err(e) => {
+                tracing::warn!("Onebotv11: Failed to read webhook body: {}", e);
+                return Ok(status_response(
+                    if e.is::<http_body_util::LengthLimitError>() {
+                        StatusCode::PAYLOAD_TOO_LARGE
+                    } else {
+                        StatusCode::BAD_REQUEST
+                    },
+                ));
+            }
+        };
+        if let Some(secret) = &self.config.secret {
+            if let Err(status) = verify_signature(secret, signature.as_deref(), &body) {
+                tracing::warn!(
+                    "Onebotv11: Rejected webhook request, signature: {:?}",
+                    signature
+                );
+                return Ok(status_response(status));
+            }
+        }
+        let event = serde_json::from_slice::<Value>(&body).and_then(|value| {
+            self.tap.record(&value);
+            serde_json::from_value::<onebot_v11::Event>(value)
+        });
+        match event {
+            Ok(event) => {
+                let operation = match self.config.quick_operation_timeout {
+                    Some(timeout)
+                        if matches!(
+                            event,
+                            onebot_v11::Event::Message(_) | onebot_v11::Event::Request(_)
+                        ) =>
+                    {
+                        self.send_event_and_wait(event, timeout).await
+                    }
+                    _ => {
+                        if let Err(e) = self.event_sender.send(event) {
+                            tracing::warn!("Onebotv11: Error sending Event: {}", e);
+                        }
+                        None
+                    }
+                };
+                match operation.map(|op| serde_json::to_vec(&op)) {
+                    Some(Ok(body)) => {
+                        let mut resp = Response::new(Full::new(Bytes::from(body)));
+                        resp.headers_mut().insert(
+                            hyper::header::CONTENT_TYPE,
+                            hyper::header::HeaderValue::from_static("application/json"),
+                        );
+                        Ok(resp)
+                    }
+                    Some(Err(e)) => {
+                        tracing::error!("Onebotv11: Failed to serialize quick operation: {}", e);
+                        Ok(status_response(StatusCode::NO_CONTENT))
+                    }
+                    None => Ok(status_response(StatusCode::NO_CONTENT)),
+                }
+            }
+            Err(e) => {
+                tracing::warn!(
+                    "Onebotv11: Error parsing Event: {}, Raw: {}",
+                    e,
+                    String::from_utf8_lossy(&body)
+                );
+                Ok(status_response(StatusCode::BAD_REQUEST))
+            }
+        }
+    }
+}
+
+impl HttpWebhookConnect {
+    /// Dispatch `event` and wait up to `timeout` for a handler to answer it with a quick operation.
+    async fn send_event_and_wait(
+        &self,
+        event: onebot_v11::Event,
+        timeout: Duration,
+    ) -> Option<QuickOperation> {
+        let (tx, rx) = oneshot::channel();
+        let report = Arc::new(HeldReport(std::sync::Mutex::new(Some(tx))));
+        let weak = Arc::downgrade(&report);
+        self.pending_operations.lock().await.push(PendingReport {
+            event: event.clone(),
+            report: weak.clone(),
+            undispatched: Some(report),
+        });
+        let operation = match self.event_sender.send(event) {
+            // `rx` closes early once every handler has dropped the report.
+            Ok(_) => tokio::time::timeout(timeout, rx)
+                .await
+                .ok()
+                .and_then(|r| r.ok()),
+            Err(e) => {
+                tracing::warn!("Onebotv11: Error sending Event: {}", e);
+                None
+            }
+        };
+        self.pending_operations
+            .lock()
+            .await
+            .retain(|pending| !pending.report.ptr_eq(&weak));
+        operation
+    }
+
+    /// Answer a held event report with `operation`.
+    pub async fn quick_operation(
+        &self,
+        event: &onebot_v11::Event,
+        operation: QuickOperation,
+    ) -> Result<()> {
+        let report = self
+            .pending_operations
+            .lock()
+            .await
+            .iter()
+            .filter(|pending| pending.event == *event)
+            .find_map(|pending| pending.report.upgrade())
+            .ok_or(anyhow::anyhow!(
+                "Onebotv11: Event is not waiting for a quick operation"
+            ))?;
+        report.answer(operation)
+    }
+}
+
+/// Check an `X-Signature` header against the HMAC-SHA1 of `body`.
+fn verify_signature(secret: &str, signature: Option<&str>, body: &[u8]) -> Result<(), StatusCode> {
+    let signature = signature.ok_or(StatusCode::UNAUTHORIZED)?;
+    let signature = signature
+        .strip_prefix("sha1=")
+        .and_then(|hex| hex::decode(hex).ok())
+        .ok_or(StatusCode::FORBIDDEN)?;
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(secret.as_bytes()).map_err(|_| StatusCode::FORBIDDEN)?;
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .map_err(|_| StatusCode::FORBIDDEN)
+}
+
+fn status_response(status: StatusCode) -> Response<Full<Bytes>> {
+    let mut resp = Response::new(Full::new(Bytes::new()));
+    *resp.status_mut() = status;
+    resp
+}
+
+impl OnebotConnect for HttpWebhookConnect {
+    async fn call_api(self: Arc<Self>, payload: ApiPayload) -> Result<Value, OnebotError> {
+        let action = payload.endpoint();
+        let url = format!(
+            "http://{}:{}/{}",
+            self.config.api.host, self.config.api.port, action
+        );
+        let request = self.client.post(url).json(&payload);
+        let request = match &self.config.api.access_token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        };
+        let response = request.send().await.map_err(|e| {
+            if e.is_timeout() {
+                OnebotError::timeout(&payload)
+            } else {
+                OnebotError::transport(&payload, e)
+            }
+        })?;
+        if response.status() != reqwest::StatusCode::OK {
+            return Err(OnebotError::Http {
+                action,
+                payload: payload_value(&payload),
+                status: response.status().as_u16(),
+            });
+        }
+        response
+            .json::<Value>()
+            .await
+            .map_err(|e| OnebotError::transport(&payload, e))
+    }
+
+    async fn subscribe(&self) -> broadcast::Receiver<onebot_v11::Event> {
+        self.event_sender.subscribe()
+    }
+
+    fn record_events(&self, recorder: Arc<Recorder>) {
+        self.tap.set(recorder);
+    }
+
+    async fn held_report(&self, event: &onebot_v11::Event) -> Option<Arc<HeldReport>> {
+        self.pending_operations
+            .lock()
+            .await
+            .iter_mut()
+            .filter(|pending| pending.event == *event)
+            .find_map(|pending| pending.undispatched.take())
+    }
+
+    async fn bot_info(&self) -> BotInfo {
+        BotInfo {
+            id: self.config.bot_id.clone(),
+            nickname: self.config.bot_nick_name.clone(),
+        }
+    }
+}
+
+pub type OnebotV11HttpBot = OnebotV11Bot<HttpWebhookConnect>;
+
+impl OnebotV11Bot<HttpWebhookConnect> {
+    pub async fn new(config: OnebotV11HttpConfig) -> Self {
+        Self::try_new(config).await.unwrap()
+    }
+
+    /// Fails if the webhook server can't bind its address.
+    pub async fn try_new(config: OnebotV11HttpConfig) -> Result<Self, ConnectError> {
+        let connect = HttpWebhookConnect::new(config).await?;
+        Ok(Self::from_connect(connect))
+    }
+
+    /// Answer the event a handler is processing with a quick operation.
+    ///
+    /// Only works while the report is still held open, see
+    /// [`OnebotV11HttpConfig::quick_operation_timeout`]. [`QuickOperation::answer`] does the same
+    /// from the handler's [`Matcher`].
+    pub async fn quick_operation(
+        &self,
+        event: &onebot_v11::Event,
+        operation: QuickOperation,
+    ) -> Result<()> {
+        self.connect.quick_operation(event, operation).await
+    }
+}
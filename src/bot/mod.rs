@@ -1,2 +1,6 @@
+pub mod api;
+pub mod connect;
+pub mod http;
+pub mod replay;
 pub mod ws;
 pub mod ws_reverse;
@@ -0,0 +1,244 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use onebot_v11::{api::payload::ApiPayload, traits::EndPoint as _};
+use oxidebot::source::bot::BotInfo;
+use serde_json::Value;
+use tokio::sync::{broadcast, watch};
+
+use crate::{
+    error::{payload_value, ConnectError, OnebotError},
+    event::event_self_id,
+    record::{read_records, EventTap, Record, Recorder},
+};
+
+use super::{api::OnebotV11Bot, connect::OnebotConnect};
+
+/// Config for [`OnebotV11ReplayBot`].
+///
+/// By default events are replayed as fast as handlers take them, with `real_time` they keep
+/// the gaps they were recorded with, divided by `speed`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayConfig {
+    pub path: PathBuf,
+    pub real_time: bool,
+    pub speed: f64,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        ReplayConfig {
+            path: PathBuf::from("onebot_v11.jsonl"),
+            real_time: false,
+            speed: 1.0,
+        }
+    }
+}
+
+struct RecordedCall {
+    action: String,
+    params: Value,
+    response: Result<Value, String>,
+}
+
+/// A recorded event, as it was received and as onebot_v11 parses it.
+struct RecordedEvent {
+    recorded_at: i64,
+    raw: Value,
+    event: onebot_v11::Event,
+}
+
+/// Plays a recording back, see [`crate::record`].
+///
+/// Playback starts with the first subscriber, and sends each event once the bot took the one
+/// before. Recorded events onebot_v11 can't parse are skipped, as they were when received.
+/// Api calls are answered with the first recorded response not handed out yet for the same
+/// action and params, or failing that the same action, and fail when there is none.
+pub struct ReplayConnect {
+    pub config: ReplayConfig,
+    events: Arc<Vec<RecordedEvent>>,
+    calls: Mutex<Vec<Option<RecordedCall>>>,
+    event_sender: broadcast::Sender<onebot_v11::Event>,
+    tap: Arc<EventTap>,
+    started: AtomicBool,
+    /// How many events the bot took, playback waits for it to catch up.
+    taken: watch::Sender<usize>,
+    finished: Arc<watch::Sender<bool>>,
+}
+
+impl ReplayConnect {
+    #[allow(clippy::result_large_err)]
+    pub fn new(config: ReplayConfig) -> Result<Arc<Self>, ConnectError> {
+        let error = |source| ConnectError::Recording {
+            path: config.path.display().to_string(),
+            source,
+        };
+        let mut events = Vec::new();
+        let mut calls = Vec::new();
+        for record in read_records(&config.path).map_err(error)? {
+            match record {
+                Record::Event { recorded_at, event } => {
+                    match serde_json::from_value(event.clone()) {
+                        Ok(parsed) => events.push(RecordedEvent {
+                            recorded_at,
+                            raw: event,
+                            event: parsed,
+                        }),
+                        Err(e) => {
+                            tracing::warn!("Onebotv11: Error parsing Event: {}, Raw: {}", e, event)
+                        }
+                    }
+                }
+                Record::Call {
+                    action,
+                    params,
+                    response,
+                    error,
+                    ..
+                } => calls.push(Some(RecordedCall {
+                    action,
+                    params,
+                    response: response.ok_or_else(|| error.unwrap_or_default()),
+                })),
+            }
+        }
+        Ok(Arc::new(Self {
+            config,
+            events: Arc::new(events),
+            calls: Mutex::new(calls),
+            event_sender: broadcast::channel(100).0,
+            tap: Arc::default(),
+            started: AtomicBool::new(false),
+            taken: watch::channel(0).0,
+            finished: Arc::new(watch::channel(false).0),
+        }))
+    }
+
+    /// Wait until every recorded event has been taken by the subscribers.
+    pub async fn finished(&self) {
+        let mut finished = self.finished.subscribe();
+        let _ = finished.wait_for(|finished| *finished).await;
+    }
+
+    fn start_playback(&self) {
+        let events = self.events.clone();
+        let sender = self.event_sender.clone();
+        let tap = self.tap.clone();
+        let mut taken = self.taken.subscribe();
+        let finished = self.finished.clone();
+        let pacing = self.config.real_time.then_some(self.config.speed);
+        tokio::spawn(async move {
+            let mut previous: Option<i64> = None;
+            for (sent, recorded) in events.iter().enumerate() {
+                if let (Some(speed), Some(previous)) = (pacing, previous) {
+                    let gap =
+                        (recorded.recorded_at - previous).max(0) as f64 / speed.max(f64::EPSILON);
+                    tokio::time::sleep(Duration::from_secs_f64(gap / 1000.0)).await;
+                }
+                previous = Some(recorded.recorded_at);
+                drained(&sender, &mut taken, sent).await;
+                tap.record(&recorded.raw);
+                if sender.send(recorded.event.clone()).is_err() {
+                    tracing::warn!("Onebotv11: Replay has no subscribers left, stopping");
+                    break;
+                }
+            }
+            drained(&sender, &mut taken, events.len()).await;
+            finished.send_replace(true);
+        });
+    }
+}
+
+/// Handlers get events no faster than the bot takes them, instead of lagging. Waits for `sent`
+/// events to be taken, unless nobody is subscribed or the connect is gone.
+async fn drained(
+    sender: &broadcast::Sender<onebot_v11::Event>,
+    taken: &mut watch::Receiver<usize>,
+    sent: usize,
+) {
+    if sender.receiver_count() > 0 {
+        let _ = taken.wait_for(|taken| *taken >= sent).await;
+    }
+}
+
+impl OnebotConnect for ReplayConnect {
+    async fn call_api(self: Arc<Self>, payload: ApiPayload) -> Result<Value, OnebotError> {
+        let action = payload.endpoint();
+        let params = payload_value(&payload);
+        let recorded = {
+            let mut calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+            let matches = |call: &RecordedCall, exact: bool| {
+                call.action == action && (!exact || call.params == params)
+            };
+            let index = [true, false].into_iter().find_map(|exact| {
+                calls
+                    .iter()
+                    .position(|call| call.as_ref().is_some_and(|call| matches(call, exact)))
+            });
+            index.and_then(|index| calls[index].take())
+        };
+        match recorded {
+            Some(call) => call
+                .response
+                .map_err(|e| OnebotError::transport(&payload, anyhow!(e))),
+            None => Err(OnebotError::transport(
+                &payload,
+                anyhow!("no recorded response left for {}", action),
+            )),
+        }
+    }
+
+    fn event_taken(&self, _event: &onebot_v11::Event) {
+        self.taken.send_modify(|taken| *taken += 1);
+    }
+
+    fn record_events(&self, recorder: Arc<Recorder>) {
+        self.tap.set(recorder);
+    }
+
+    async fn subscribe(&self) -> broadcast::Receiver<onebot_v11::Event> {
+        let receiver = self.event_sender.subscribe();
+        if !self.started.swap(true, Ordering::SeqCst) {
+            self.start_playback();
+        }
+        receiver
+    }
+
+    async fn bot_info(&self) -> BotInfo {
+        BotInfo {
+            id: self
+                .events
+                .iter()
+                .find_map(|recorded| event_self_id(&recorded.event))
+                .map(|id| id.to_string()),
+            nickname: None,
+        }
+    }
+}
+
+/// A bot replaying a recording, for reproducing what handlers did offline.
+pub type OnebotV11ReplayBot = OnebotV11Bot<ReplayConnect>;
+
+impl OnebotV11Bot<ReplayConnect> {
+    pub fn new(config: ReplayConfig) -> Self {
+        Self::try_new(config).unwrap()
+    }
+
+    /// Fails if the recording can't be read.
+    #[allow(clippy::result_large_err)]
+    pub fn try_new(config: ReplayConfig) -> Result<Self, ConnectError> {
+        Ok(Self::from_connect(ReplayConnect::new(config)?))
+    }
+
+    /// Wait until every recorded event has been handed to handlers.
+    pub async fn finished(&self) {
+        self.connect.finished().await
+    }
+}
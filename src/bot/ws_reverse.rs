@@ -1,1432 +1,414 @@
-use anyhow::Result;
-
-use chrono::DateTime;
-use onebot_v11::api::{
-    payload::{
-        ApiPayload, DeleteMsg, GetFile, GetGroupFileCount, GetGroupFileList, GetGroupMemberList,
-        GetStrangerInfo, SetFriendAddRequest, SetGroupAddRequest, SetGroupAdmin, SetGroupBan,
-        SetGroupCard, SetGroupFileFolder, SetGroupKick, SetGroupName, SetGroupWholeBan,
-        SetMsgEmojiLike, SetQQAvatar,
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Weak,
     },
-    resp::{SendGroupMsgResponse, SendPrivateMsgResponse},
 };
-use onebot_v11::connect::ws_reverse::{ReverseWsConfig, ReverseWsConnect};
-use oxidebot::{
-    api::{
-        payload::{GroupAdminChangeType, GroupMuteType, RequestResponse, SendMessageTarget},
-        BotGetFriendListResponse, BotGetGroupListResponse, BotGetProfileResponse, CallApiTrait,
-        GetMessageDetailResponse, GroupGetFileCountResponse, GroupGetFsListResponse,
-        GroupGetProfileResponse, GroupMemberListResponse, SendMessageResponse,
-        UserGetProfileResponse,
-    },
-    bot::BotObject,
-    matcher::Matcher,
-    source::{
-        bot::BotInfo,
-        group::GroupProfile,
-        message::{File, Folder, FsNode, MessageSegment},
-        user::{Sex, User, UserGroupInfo, UserProfile},
+
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    StreamExt as _,
+};
+use onebot_v11::{
+    api::payload::ApiPayload, connect::ws_reverse::ReverseWsConfig, traits::EndPoint as _,
+};
+use oxidebot::{bot::BotObject, source::bot::BotInfo};
+use serde_json::Value;
+use subtle::ConstantTimeEq as _;
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, watch, Mutex, RwLock},
+    task::{AbortHandle, JoinSet},
+};
+use tokio_tungstenite::{
+    accept_hdr_async,
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::{header::AUTHORIZATION, StatusCode},
+        Message,
     },
-    BotTrait,
+    WebSocketStream,
 };
-use std::{any::Any, sync::Arc};
-use tokio::sync::broadcast;
-use tracing::warn;
-
-use std::time::Duration;
 
 use crate::{
-    event::EventWrapper,
-    segment::{cast_segment, parse_segment},
-    PLATFORM,
+    error::{ConnectError, OnebotError},
+    event::event_self_id,
+    record::{EventTap, Recorder},
 };
 
-#[derive(Clone)]
-pub struct OnebotV11ReverseWsBot {
-    connect: Arc<ReverseWsConnect>,
+use super::{
+    api::OnebotV11Bot,
+    connect::{OnebotConnect, WsChannel},
+};
+
+type WsWrite = SplitSink<WebSocketStream<TcpStream>, Message>;
+type WsRead = SplitStream<WebSocketStream<TcpStream>>;
+
+/// A websocket server the OneBot implementations connect to.
+///
+/// The listener stays bound for the lifetime of the bot, so implementations can
+/// reconnect at any time, dropping the last handle unbinds it and closes the connections.
+/// Connections are told apart by their `X-Self-ID` header, each account gets a
+/// [`ReverseWsAccount`] and a new connection of the same account replaces the previous one.
+/// With `access_token` configured, connections without it are refused with 401 during the
+/// handshake.
+pub struct ReverseWsListenConnect {
+    pub config: ReverseWsConfig,
+    accounts: RwLock<HashMap<String, Arc<ReverseWsAccount>>>,
+    event_sender: broadcast::Sender<onebot_v11::Event>,
+    tap: Arc<EventTap>,
+    connected: watch::Sender<bool>,
+    listener: AbortHandle,
 }
 
-impl BotTrait for OnebotV11ReverseWsBot {
-    fn bot_info<'life0, 'async_trait>(
-        &'life0 self,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = BotInfo> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        Box::pin(async move {
-            let bot_id = self.connect.bot_id.read().await;
-            let bot_id = bot_id.as_ref().and_then(|s| Some(s.to_string()));
-            BotInfo {
-                id: bot_id,
-                nickname: None,
-            }
-        })
-    }
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn start_sending_events<'life0, 'async_trait>(
-        &'life0 self,
-        sender: broadcast::Sender<Matcher>,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = ()> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        Box::pin(async move {
-            let mut subscriber = self.connect.subscribe().await;
-            while let Ok(event) = subscriber.recv().await {
-                for matcher in Matcher::new(
-                    Box::new(EventWrapper(Arc::new(event))),
-                    <Self as BotTrait>::clone_box(self),
-                ) {
-                    match sender.send(matcher) {
-                        Ok(_) => {}
-                        Err(e) => {
-                            tracing::error!("Onebotv11: Failed to send event: {:?}", e);
-                        }
-                    }
-                }
-            }
-        })
-    }
+/// One account connected to a [`ReverseWsListenConnect`].
+///
+/// It outlives its connections, so bots built on it keep working after a reconnect.
+/// Its events go to the listener's stream, shared by every account, which its own subscribers
+/// get filtered by `self_id`.
+pub struct ReverseWsAccount {
+    pub self_id: String,
+    channel: WsChannel<WsWrite>,
+    /// The id of the current connection, `None` while disconnected.
+    connection: Mutex<Option<u64>>,
+    next_connection: AtomicU64,
+}
 
-    fn clone_box(&self) -> BotObject {
-        Box::new(self.clone())
+impl ReverseWsAccount {
+    pub async fn is_connected(&self) -> bool {
+        self.connection.lock().await.is_some()
     }
 
-    fn server(&self) -> &'static str {
-        PLATFORM
+    async fn attach(&self, write: WsWrite) -> u64 {
+        let mut connection = self.connection.lock().await;
+        let id = self.next_connection.fetch_add(1, Ordering::Relaxed);
+        self.channel.set_write(Some(write)).await;
+        *connection = Some(id);
+        id
     }
 
-    fn as_any(&self) -> &dyn Any {
-        self
+    /// Drop connection `id`, unless a newer connection replaced it already.
+    async fn detach(&self, id: u64) -> bool {
+        let mut connection = self.connection.lock().await;
+        if *connection != Some(id) {
+            return false;
+        }
+        self.channel.set_write(None).await;
+        *connection = None;
+        true
     }
 }
 
-impl OnebotV11ReverseWsBot {
-    pub async fn new(config: ReverseWsConfig) -> BotObject {
-        let connect = ReverseWsConnect::new(config).await.unwrap();
-        Box::new(Self { connect })
-    }
-    pub async fn call_api(
-        &self,
-        payload: onebot_v11::api::payload::ApiPayload,
-    ) -> Result<onebot_v11::api::resp::ApiResp> {
-        self.connect.clone().call_api(payload).await
+impl ReverseWsListenConnect {
+    /// Bind the listener, and unless `start_disconnected` is set, wait for the first connection.
+    pub async fn try_new(
+        config: ReverseWsConfig,
+        start_disconnected: bool,
+    ) -> Result<Arc<Self>, ConnectError> {
+        let addr = format!("{}:{}", config.host, config.port);
+        let listener = TcpListener::bind(&addr)
+            .await
+            .map_err(|source| ConnectError::Bind { addr, source })?;
+        let self_ = Arc::new_cyclic(|weak| Self {
+            config,
+            accounts: RwLock::new(HashMap::new()),
+            event_sender: broadcast::channel(100).0,
+            tap: Arc::default(),
+            connected: watch::channel(false).0,
+            listener: tokio::spawn(Self::listen(weak.clone(), listener)).abort_handle(),
+        });
+        let mut connected = self_.connected.subscribe();
+        if !start_disconnected {
+            // The sender lives in `self_`, so this can't fail.
+            let _ = connected.wait_for(|connected| *connected).await;
+        }
+        Ok(self_)
+    }
+
+    /// Accept connections until aborted on drop, which aborts the connections with it. Neither
+    /// keeps the connect alive.
+    async fn listen(this: Weak<Self>, listener: TcpListener) {
+        let mut connections = JoinSet::new();
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("Onebotv11: Failed to accept connection: {}", e);
+                    continue;
+                }
+            };
+            while connections.try_join_next().is_some() {}
+            connections.spawn(Self::handle_connection(this.clone(), stream));
+        }
     }
-}
 
-impl CallApiTrait for OnebotV11ReverseWsBot {
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn send_message<'life0, 'async_trait>(
-        &'life0 self,
-        message: Vec<MessageSegment>,
-        target: SendMessageTarget,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<Vec<SendMessageResponse>>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload: Result<ApiPayload> = match target {
-            oxidebot::api::payload::SendMessageTarget::Group(id) => {
-                Ok(onebot_v11::api::payload::ApiPayload::SendGroupMsg(
-                    onebot_v11::api::payload::SendGroupMsg {
-                        group_id: id.parse().unwrap_or_else(|e| {
-                            tracing::error!(
-                                "Onebotv11: Failed to parse group id: {}, error: {}",
-                                id,
-                                e
-                            );
-                            Default::default()
-                        }),
-                        message: message.into_iter().map(parse_segment).collect(),
-                        auto_escape: true,
-                    },
-                ))
-            }
-            oxidebot::api::payload::SendMessageTarget::Private(id) => {
-                Ok(onebot_v11::api::payload::ApiPayload::SendPrivateMsg(
-                    onebot_v11::api::payload::SendPrivateMsg {
-                        user_id: id.parse().unwrap_or_else(|e| {
-                            tracing::error!(
-                                "Onebotv11: Failed to parse user id: {}, error: {}",
-                                id,
-                                e
-                            );
-                            Default::default()
-                        }),
-                        message: message.into_iter().map(parse_segment).collect(),
-                        auto_escape: true,
-                    },
-                ))
-            }
+    async fn handle_connection(this: Weak<Self>, stream: TcpStream) {
+        let Some((account, connection, mut read)) = (match this.upgrade() {
+            Some(this) => this.accept(stream).await,
+            None => None,
+        }) else {
+            return;
         };
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload?).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::SendPrivateMsgResponse(
-                        SendPrivateMsgResponse { message_id },
-                    )
-                    | onebot_v11::api::resp::ApiRespData::SendGroupMsgResponse(
-                        SendGroupMsgResponse { message_id },
-                    ) => Ok(vec![SendMessageResponse {
-                        sent_message_id: message_id.to_string(),
-                    }]),
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to send message, resp: {:?}",
-                    resp
-                ))
-            }
-        })
-    }
+        let self_id = account.self_id.clone();
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn delete_message<'life0, 'async_trait>(
-        &'life0 self,
-        message_id: String,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::DeleteMsg(DeleteMsg {
-            message_id: message_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse message id: {}, error: {}",
-                    message_id,
-                    e
-                );
-                Default::default()
-            }),
-        });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to delete message, resp: {:?}",
-                    resp
-                ))
-            }
-        })
-    }
+        account.channel.read_messages(&mut read).await;
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_message_detail<'life0, 'async_trait>(
-        &'life0 self,
-        message_id: String,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<GetMessageDetailResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload =
-            onebot_v11::api::payload::ApiPayload::GetMsg(onebot_v11::api::payload::GetMsg {
-                message_id: message_id.parse().unwrap_or_else(|e| {
-                    tracing::error!(
-                        "Onebotv11: Failed to parse message id: {}, error: {}",
-                        message_id,
-                        e
-                    );
-                    Default::default()
-                }),
-            });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetMsgResponse(resp) => {
-                        Ok(GetMessageDetailResponse {
-                            message: resp.message.into_iter().map(cast_segment).collect(),
-                            sender: Some(User {
-                                id: resp.sender.user_id.unwrap_or_default().to_string(),
-                                profile: Some(UserProfile {
-                                    nickname: resp.sender.nickname,
-                                    sex: Some(Sex::from(
-                                        resp.sender.sex.unwrap_or_default().as_str(),
-                                    )),
-                                    age: resp.sender.age.and_then(|a| Some(a as u64)),
-                                    avatar: None,
-                                    email: None,
-                                    phone: None,
-                                    signature: None,
-                                    level: None,
-                                }),
-                                group_info: None,
-                            }),
-                            time: DateTime::from_timestamp(resp.time, 0),
-                        })
-                    }
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get message detail, resp: {:?}",
-                    resp
-                ))
+        if account.detach(connection).await {
+            tracing::warn!("Onebotv11: Connection closed, bot_id: {:?}", self_id);
+            if let Some(this) = this.upgrade() {
+                this.connected
+                    .send_replace(!this.connected_accounts().await.is_empty());
             }
-        })
+            account.channel.send_lifecycle(Some(&self_id), "disable");
+        } else {
+            tracing::info!("Onebotv11: Connection replaced, bot_id: {:?}", self_id);
+        }
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn set_message_reaction<'life0, 'async_trait>(
-        &'life0 self,
-        message_id: String,
-        reaction_id: String,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::SetMsgEmojiLike(SetMsgEmojiLike {
-            message_id: message_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse message id: {}, error: {}",
-                    message_id,
-                    e
+    /// Complete the handshake of `stream` and attach it to its account.
+    // The handshake callback's error type is fixed by tungstenite.
+    #[allow(clippy::result_large_err)]
+    async fn accept(&self, stream: TcpStream) -> Option<(Arc<ReverseWsAccount>, u64, WsRead)> {
+        let mut bot_id = None;
+        let ws_stream = match accept_hdr_async(stream, |req: &Request, resp: Response| {
+            let path = req.uri().path().trim_end_matches('/');
+            if !path.ends_with(self.config.suffix.trim_end_matches('/')) {
+                return Err(error_response(StatusCode::NOT_FOUND));
+            }
+            if !self.authorized(req) {
+                tracing::warn!(
+                    "Onebotv11: Connection refused: Unauthorized, missing or wrong access token"
                 );
-                Default::default()
-            }),
-            emoji_id: reaction_id,
-        });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to set message reaction, resp: {:?}",
-                    resp
-                ))
+                return Err(error_response(StatusCode::UNAUTHORIZED));
             }
+            bot_id = req
+                .headers()
+                .get("X-Self-ID")
+                .map(|v| v.to_str().unwrap_or("").to_string());
+            Ok(resp)
         })
-    }
-
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_group_member_list<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<GroupMemberListResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload =
-            onebot_v11::api::payload::ApiPayload::GetGroupMemberList(GetGroupMemberList {
-                group_id: group_id.parse().unwrap_or_else(|e| {
-                    tracing::error!(
-                        "Onebotv11: Failed to parse group id: {}, error: {}",
-                        group_id,
-                        e
-                    );
-                    Default::default()
-                }),
-            });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetGroupMemberListResponse(resp) => {
-                        Ok(GroupMemberListResponse {
-                            members: resp
-                                .into_iter()
-                                .map(|m| User {
-                                    id: m.user_id.to_string(),
-                                    profile: Some(UserProfile {
-                                        nickname: Some(m.nickname),
-                                        sex: Some(Sex::from(m.sex.as_str())),
-                                        age: Some(m.age as u64),
-                                        avatar: None,
-                                        email: None,
-                                        phone: None,
-                                        signature: None,
-                                        level: None,
-                                    }),
-                                    group_info: Some(UserGroupInfo {
-                                        role: Some(match m.role.to_lowercase().as_str() {
-                                            "owner" => oxidebot::source::user::Role::Owner,
-                                            "admin" => oxidebot::source::user::Role::Admin,
-                                            "membet" => oxidebot::source::user::Role::Member,
-                                            _ => oxidebot::source::user::Role::Unknown,
-                                        }),
-                                        join_time: DateTime::from_timestamp(m.join_time, 0),
-                                        last_active_time: DateTime::from_timestamp(
-                                            m.last_sent_time,
-                                            0,
-                                        ),
-                                        level: Some(m.level),
-                                        alias: Some(m.title),
-                                    }),
-                                })
-                                .collect(),
-                        })
-                    }
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get group member list, resp: {:?}",
-                    resp
-                ))
+        .await
+        {
+            Ok(ws_stream) => ws_stream,
+            Err(e) => {
+                tracing::warn!("Onebotv11: Connection failed: {}", e);
+                return None;
             }
-        })
-    }
-
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn kick_group_member<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        user_id: String,
-        reject_add_request: Option<bool>,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = SetGroupKick {
-            group_id: group_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse group id: {}, error: {}",
-                    group_id,
-                    e
-                );
-                Default::default()
-            }),
-            user_id: user_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse user id: {}, error: {}",
-                    user_id,
-                    e
-                );
-                Default::default()
-            }),
-            reject_add_request: reject_add_request.unwrap_or(false),
         };
-        Box::pin(async move {
-            let resp = self
-                .connect
-                .clone()
-                .call_api(onebot_v11::api::payload::ApiPayload::SetGroupKick(payload))
-                .await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to kick group member, resp: {:?}",
-                    resp
-                ))
+        tracing::info!("Onebotv11: Connection succeed, bot_id: {:?}", bot_id);
+        let self_id = bot_id.unwrap_or_default();
+        let (write, read) = ws_stream.split();
+        let account = self.account_entry(&self_id).await;
+        let connection = account.attach(write).await;
+        self.connected.send_replace(true);
+        account.channel.send_lifecycle(Some(&self_id), "connect");
+        Some((account, connection, read))
+    }
+
+    async fn account_entry(&self, self_id: &str) -> Arc<ReverseWsAccount> {
+        self.accounts
+            .write()
+            .await
+            .entry(self_id.to_string())
+            .or_insert_with(|| {
+                Arc::new(ReverseWsAccount {
+                    self_id: self_id.to_string(),
+                    channel: WsChannel::with_event_sender(
+                        self.event_sender.clone(),
+                        self.tap.clone(),
+                    ),
+                    connection: Mutex::new(None),
+                    next_connection: AtomicU64::new(0),
+                })
+            })
+            .clone()
+    }
+
+    /// Every account that connected so far, connected or not.
+    pub async fn accounts(&self) -> Vec<Arc<ReverseWsAccount>> {
+        self.accounts.read().await.values().cloned().collect()
+    }
+
+    pub async fn account(&self, self_id: &str) -> Option<Arc<ReverseWsAccount>> {
+        self.accounts.read().await.get(self_id).cloned()
+    }
+
+    async fn connected_accounts(&self) -> Vec<Arc<ReverseWsAccount>> {
+        let mut connected = Vec::new();
+        for account in self.accounts().await {
+            if account.is_connected().await {
+                connected.push(account);
             }
-        })
-    }
-
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn mute_group<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        duration: Option<Duration>,
-        r#type: GroupMuteType,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        if duration.is_some() {
-            warn!("Onebotv11: Mute Group duration is not supported");
         }
-        let payload = SetGroupWholeBan {
-            group_id: group_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse group id: {}, error: {}",
-                    group_id,
-                    e
-                );
-                Default::default()
-            }),
-            enable: match r#type {
-                oxidebot::api::payload::GroupMuteType::Mute => true,
-                oxidebot::api::payload::GroupMuteType::Unmute => false,
-            },
-        };
-        Box::pin(async move {
-            let resp = self
-                .connect
-                .clone()
-                .call_api(onebot_v11::api::payload::ApiPayload::SetGroupWholeBan(
-                    payload,
-                ))
-                .await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to mute group, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+        connected
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn mute_group_member<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        user_id: String,
-        r#type: GroupMuteType,
-        duration: Option<Duration>,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = SetGroupBan {
-            group_id: group_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse group id: {}, error: {}",
-                    group_id,
-                    e
-                );
-                Default::default()
-            }),
-            user_id: user_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse user id: {}, error: {}",
-                    user_id,
-                    e
-                );
-                Default::default()
-            }),
-            duration: match r#type {
-                GroupMuteType::Mute => duration.unwrap_or(Duration::from_secs(60)).as_secs() as i64,
-                GroupMuteType::Unmute => 0,
-            },
-        };
-        Box::pin(async move {
-            let resp = self
-                .connect
-                .clone()
-                .call_api(onebot_v11::api::payload::ApiPayload::SetGroupBan(payload))
-                .await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to mute group member, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    /// Whether `req` carries the configured access token, see [`carries_token`]. Without a
+    /// configured token everyone is let in.
+    fn authorized(&self, req: &Request) -> bool {
+        match &self.config.access_token {
+            Some(expected) => carries_token(req, expected),
+            None => true,
+        }
     }
+}
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn change_group_admin<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        user_id: String,
-        r#type: GroupAdminChangeType,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = SetGroupAdmin {
-            group_id: group_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse group id: {}, error: {}",
-                    group_id,
-                    e
-                );
-                Default::default()
-            }),
-            user_id: user_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse user id: {}, error: {}",
-                    user_id,
-                    e
-                );
-                Default::default()
-            }),
-            enable: match r#type {
-                oxidebot::api::payload::GroupAdminChangeType::Set => true,
-                oxidebot::api::payload::GroupAdminChangeType::Unset => false,
-            },
-        };
-        Box::pin(async move {
-            let resp = self
-                .connect
-                .clone()
-                .call_api(onebot_v11::api::payload::ApiPayload::SetGroupAdmin(payload))
-                .await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to change group admin, resp: {:?}",
-                    resp
-                ))
-            }
-        })
-    }
+/// Whether `req` carries `expected` as an `Authorization: Bearer` header or an `access_token`
+/// query parameter, compared in constant time.
+pub(crate) fn carries_token(req: &Request, expected: &str) -> bool {
+    let matches = |token: &str| bool::from(token.as_bytes().ct_eq(expected.as_bytes()));
+    let header = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if header.is_some_and(matches) {
+        return true;
+    }
+    req.uri().query().is_some_and(|query| {
+        form_urlencoded::parse(query.as_bytes())
+            .any(|(key, value)| key == "access_token" && matches(&value))
+    })
+}
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn set_group_member_alias<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        user_id: String,
-        new_alias: String,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::SetGroupCard(SetGroupCard {
-            group_id: group_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse group id: {}, error: {}",
-                    group_id,
-                    e
-                );
-                Default::default()
-            }),
-            user_id: user_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse user id: {}, error: {}",
-                    user_id,
-                    e
-                );
-                Default::default()
-            }),
-            card: new_alias,
-        });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to set group member alias, resp: {:?}",
-                    resp
-                ))
-            }
-        })
-    }
+fn error_response(status: StatusCode) -> ErrorResponse {
+    let mut resp = ErrorResponse::new(None);
+    *resp.status_mut() = status;
+    resp
+}
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_group_profile<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<GroupGetProfileResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::GetGroupInfo(
-            onebot_v11::api::payload::GetGroupInfo {
-                group_id: group_id.parse().unwrap_or_else(|e| {
-                    tracing::error!(
-                        "Onebotv11: Failed to parse group id: {}, error: {}",
-                        group_id,
-                        e
-                    );
-                    Default::default()
-                }),
-                no_cache: true,
-            },
-        );
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetGroupInfoResponse(resp) => {
-                        Ok(GroupGetProfileResponse {
-                            profile: oxidebot::source::group::GroupProfile {
-                                name: Some(resp.group_name),
-                                avatar: None,
-                                member_count: {
-                                    if resp.member_count > u64::MAX as i64 {
-                                        Some(u64::MAX)
-                                    } else if resp.member_count < u64::MIN as i64 {
-                                        Some(u64::MIN)
-                                    } else {
-                                        Some(resp.member_count as u64)
-                                    }
-                                },
-                            },
-                        })
-                    }
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get group profile, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+/// Calls made on the listener itself go to the only connected account, with several
+/// accounts connected they have to be made through the account's bot.
+impl Drop for ReverseWsListenConnect {
+    fn drop(&mut self) {
+        self.listener.abort();
     }
+}
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn set_group_profile<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        new_profile: GroupProfile,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        if new_profile.avatar.is_some() {
-            tracing::warn!("Onebotv11: Set Group avatar is not supported");
+impl OnebotConnect for ReverseWsListenConnect {
+    async fn call_api(self: Arc<Self>, payload: ApiPayload) -> Result<Value, OnebotError> {
+        let mut connected = self.connected_accounts().await;
+        match connected.pop() {
+            Some(account) if connected.is_empty() => account.channel.call_api(payload).await,
+            Some(_) => Err(OnebotError::invalid_argument(
+                &payload.endpoint(),
+                "several accounts are connected, call through the account's bot",
+            )),
+            None => Err(OnebotError::disconnected(&payload)),
         }
-        let payload = {
-            if let Some(new_name) = new_profile.name {
-                Some(onebot_v11::api::payload::ApiPayload::SetGroupName(
-                    SetGroupName {
-                        group_id: group_id.parse().unwrap_or_else(|e| {
-                            tracing::error!(
-                                "Onebotv11: Failed to parse group id: {}, error: {}",
-                                group_id,
-                                e
-                            );
-                            Default::default()
-                        }),
-                        group_name: new_name,
-                    },
-                ))
-            } else {
-                None
-            }
-        };
-        Box::pin(async move {
-            let payload = payload.ok_or(anyhow::anyhow!("Onebotv11: No new name to set"))?;
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to set group profile, resp: {:?}",
-                    resp
-                ))
-            }
-        })
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_group_file_count<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        parent_folder_id: Option<String>,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<GroupGetFileCountResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        if parent_folder_id.is_some() {
-            tracing::error!("Onebotv11: Get Group File Parent Fold ID is not supported");
-        }
-        let payload = onebot_v11::api::payload::ApiPayload::GetGroupFileCount(GetGroupFileCount {
-            group_id: group_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse group id: {}, error: {}",
-                    group_id,
-                    e
-                );
-                Default::default()
-            }),
-        });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetGroupFileCountResponse(resp) => {
-                        Ok(GroupGetFileCountResponse { count: resp.count })
-                    }
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get group file count, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    async fn subscribe(&self) -> broadcast::Receiver<onebot_v11::Event> {
+        self.event_sender.subscribe()
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_group_fs_list<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        start_index: u64,
-        count: u64,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<GroupGetFsListResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::GetGroupFileList(GetGroupFileList {
-            group_id: group_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse group id: {}, error: {}",
-                    group_id,
-                    e
-                );
-                Default::default()
-            }),
-            start_index: {
-                if start_index > i64::MAX as u64 {
-                    tracing::error!("Onebotv11: Start index is too large: {}", start_index);
-                    i64::MAX
-                } else {
-                    start_index as i64
-                }
-            },
-            file_count: {
-                if count > i64::MAX as u64 {
-                    tracing::error!("Onebotv11: Count is too large: {}", count);
-                    i64::MAX
-                } else {
-                    count as i64
-                }
+    async fn bot_info(&self) -> BotInfo {
+        let mut connected = self.connected_accounts().await;
+        match connected.pop() {
+            Some(account) if connected.is_empty() => account.bot_info().await,
+            _ => BotInfo {
+                id: None,
+                nickname: None,
             },
-        });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data{
-                    onebot_v11::api::resp::ApiRespData::GetGroupFileListResponse(resp)=>{
-                        Ok(GroupGetFsListResponse {
-                            fs_tree: resp
-                                .file_list
-                                .into_iter()
-                                .map(|f| {
-                                    if let Some(folder_info) = f.folder_info {
-                                        FsNode::Folder(Folder {
-                                            id: folder_info.folder_id,
-                                            name: folder_info.folder_name,
-                                            file_amount: {
-                                                if folder_info.total_file_count > u64::MAX as i64 {
-                                                    u64::MAX
-                                                } else if folder_info.total_file_count < u64::MIN as i64 {
-                                                    u64::MIN
-                                                } else {
-                                                    folder_info.total_file_count as u64
-                                                }
-                                            },
-                                            children: Vec::with_capacity(0),
-                                        })
-                                    } else if let Some(file_info) = f.file_info {
-                                        FsNode::File(File {
-                                            id: Some(file_info.file_id),
-                                            name: file_info.file_name,
-                                            uri: None,
-                                            base64: None,
-                                            r#mime: None,
-                                            size: {
-                                                match file_info.file_size.parse::<u64>() {
-                                                    Ok(size) => Some(size),
-                                                    Err(e) =>{
-                                                        tracing::error!("Onebotv11: Failed to parse file size: {}, error: {}", file_info.file_size, e);
-                                                        None
-                                                    },
-                                                }
-                                            },
-                                        })
-                                    } else {
-                                        FsNode::Unknown
-                                    }
-                                })
-                                .collect(),
-                        })
-                    }
-                    _=> Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get group file list, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+        }
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn delete_group_file<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        file_id: String,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::DelGroupFile(
-            onebot_v11::api::payload::DelGroupFile {
-                group_id: group_id.parse().unwrap_or_else(|e| {
-                    tracing::error!(
-                        "Onebotv11: Failed to parse group id: {}, error: {}",
-                        group_id,
-                        e
-                    );
-                    Default::default()
-                }),
-                file_id: file_id,
-            },
-        );
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to delete group file, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    async fn account_key(&self) -> Option<String> {
+        self.bot_info().await.id
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn delete_group_folder<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        folder_id: String,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::DelGroupFileFolder(
-            onebot_v11::api::payload::DelGroupFileFolder {
-                group_id: group_id.parse().unwrap_or_else(|e| {
-                    tracing::error!(
-                        "Onebotv11: Failed to parse group id: {}, error: {}",
-                        group_id,
-                        e
-                    );
-                    Default::default()
-                }),
-                folder_id: folder_id,
-            },
-        );
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to delete group folder, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    fn record_events(&self, recorder: Arc<Recorder>) {
+        self.tap.set(recorder);
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn create_group_folder<'life0, 'async_trait>(
-        &'life0 self,
-        group_id: String,
-        folder_name: String,
-        parent_folder_id: Option<String>,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        if parent_folder_id.is_some() {
-            tracing::error!("Onebotv11: Create Group Folder Parent Fold ID is not supported");
-        }
-        let payload =
-            onebot_v11::api::payload::ApiPayload::SetGroupFileFolder(SetGroupFileFolder {
-                group_id: group_id.parse().unwrap_or_else(|e| {
-                    tracing::error!(
-                        "Onebotv11: Failed to parse group id: {}, error: {}",
-                        group_id,
-                        e
-                    );
-                    Default::default()
-                }),
-                folder_name: folder_name,
-            });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to create group folder, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    async fn event_account(&self, event: &onebot_v11::Event) -> Option<Arc<impl OnebotConnect>> {
+        self.account(&event_self_id(event)?.to_string()).await
     }
+}
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_user_profile<'life0, 'async_trait>(
-        &'life0 self,
-        user_id: String,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<UserGetProfileResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::GetStrangerInfo(GetStrangerInfo {
-            user_id: user_id.parse().unwrap_or_else(|e| {
-                tracing::error!(
-                    "Onebotv11: Failed to parse user id: {}, error: {}",
-                    user_id,
-                    e
-                );
-                Default::default()
-            }),
-            no_cache: true,
-        });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetStrangerInfoResponse(resp) => {
-                        Ok(UserGetProfileResponse {
-                            profile: UserProfile {
-                                nickname: Some(resp.nickname),
-                                sex: Some(Sex::from(resp.sex.as_str())),
-                                age: Some(resp.age as u64),
-                                ..Default::default()
-                            },
-                        })
+impl OnebotConnect for ReverseWsAccount {
+    async fn call_api(self: Arc<Self>, payload: ApiPayload) -> Result<Value, OnebotError> {
+        self.channel.call_api(payload).await
+    }
+
+    async fn subscribe(&self) -> broadcast::Receiver<onebot_v11::Event> {
+        let mut events = self.channel.subscribe();
+        let (sender, receiver) = broadcast::channel(100);
+        let self_id = self.self_id.clone();
+        tokio::spawn(async move {
+            // Until every receiver is gone, noticed at the next event of any account.
+            while sender.receiver_count() > 0 {
+                match events.recv().await {
+                    Ok(event) => {
+                        if event_self_id(&event).is_some_and(|id| id.to_string() == self_id) {
+                            let _ = sender.send(event);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!(
+                            "Onebotv11: Account {} lagged, {} events skipped",
+                            self_id,
+                            skipped
+                        );
                     }
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
+                    Err(broadcast::error::RecvError::Closed) => break,
                 }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get user profile, resp: {:?}",
-                    resp
-                ))
             }
-        })
+        });
+        receiver
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn set_bot_profile<'life0, 'async_trait>(
-        &'life0 self,
-        new_profile: UserProfile,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = if let Some(avatar) = new_profile.avatar {
-            Ok(onebot_v11::api::payload::ApiPayload::SetQQAvatar(
-                SetQQAvatar {
-                    file: avatar.to_string(),
-                },
-            ))
-        } else {
-            Err(anyhow::anyhow!("Onebotv11: New Bot avatar is None"))
-        };
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload?).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to set bot profile, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    async fn bot_info(&self) -> BotInfo {
+        BotInfo {
+            id: Some(self.self_id.clone()).filter(|id| !id.is_empty()),
+            nickname: None,
+        }
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_bot_profile<'life0, 'async_trait>(
-        &'life0 self,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<BotGetProfileResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::GetLoginInfo(
-            onebot_v11::api::payload::GetLoginInfo {},
-        );
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetLoginInfoResponse(resp) => {
-                        Ok(BotGetProfileResponse {
-                            profile: UserProfile {
-                                nickname: Some(resp.nickname),
-                                ..Default::default()
-                            },
-                        })
-                    }
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get bot profile, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    fn record_events(&self, recorder: Arc<Recorder>) {
+        self.channel.record_events(recorder);
     }
+}
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_bot_friend_list<'life0, 'async_trait>(
-        &'life0 self,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<BotGetFriendListResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::GetFriendList(
-            onebot_v11::api::payload::GetFriendList {},
-        );
+pub type OnebotV11ReverseWsBot = OnebotV11Bot<ReverseWsListenConnect>;
 
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetFriendListResponse(resp) => {
-                        Ok(BotGetFriendListResponse {
-                            friends: resp
-                                .into_iter()
-                                .map(|f| User {
-                                    id: f.user_id.to_string(),
-                                    profile: Some(UserProfile {
-                                        nickname: Some(f.nickname),
-                                        ..Default::default()
-                                    }),
-                                    group_info: None,
-                                })
-                                .collect(),
-                        })
-                    }
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get bot friend list, resp: {:?}",
-                    resp
-                ))
-            }
-        })
-    }
+/// The bot of a single account behind an [`OnebotV11ReverseWsBot`].
+pub type OnebotV11ReverseWsAccountBot = OnebotV11Bot<ReverseWsAccount>;
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_bot_group_list<'life0, 'async_trait>(
-        &'life0 self,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<BotGetGroupListResponse>>
-                + ::core::marker::Send
-                + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::GetGroupList(
-            onebot_v11::api::payload::GetGroupList {},
-        );
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetGroupListResponse(resp) => {
-                        Ok(BotGetGroupListResponse {
-                            groups: resp
-                                .into_iter()
-                                .map(|g| oxidebot::source::group::Group {
-                                    id: g.group_id.to_string(),
-                                    profile: Some(oxidebot::source::group::GroupProfile {
-                                        name: Some(g.group_name),
-                                        avatar: None,
-                                        member_count: {
-                                            if g.member_count > u64::MAX as i64 {
-                                                Some(u64::MAX)
-                                            } else if g.member_count < u64::MIN as i64 {
-                                                Some(u64::MIN)
-                                            } else {
-                                                Some(g.member_count as u64)
-                                            }
-                                        },
-                                    }),
-                                })
-                                .collect(),
-                        })
-                    }
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get bot group list, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+impl OnebotV11Bot<ReverseWsListenConnect> {
+    #[allow(clippy::new_ret_no_self)]
+    pub async fn new(config: ReverseWsConfig) -> BotObject {
+        Box::new(Self::try_new(config).await.unwrap())
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn handle_add_friend_request<'life0, 'async_trait>(
-        &'life0 self,
-        id: String,
-        response: RequestResponse,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload =
-            onebot_v11::api::payload::ApiPayload::SetFriendAddRequest(SetFriendAddRequest {
-                flag: id.parse().unwrap_or_else(|e| {
-                    tracing::error!("Onebotv11: Failed to parse user id: {}, error: {}", id, e);
-                    Default::default()
-                }),
-                approve: match response {
-                    oxidebot::api::payload::RequestResponse::Approve => true,
-                    oxidebot::api::payload::RequestResponse::Reject => false,
-                },
-                remark: None,
-            });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to handle add friend request, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    /// Bind the listener and wait for the implementation to connect.
+    pub async fn try_new(config: ReverseWsConfig) -> Result<Self, ConnectError> {
+        let connect = ReverseWsListenConnect::try_new(config, false).await?;
+        Ok(Self::from_connect(connect))
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn handle_add_group_request<'life0, 'async_trait>(
-        &'life0 self,
-        id: String,
-        response: RequestResponse,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload =
-            onebot_v11::api::payload::ApiPayload::SetGroupAddRequest(SetGroupAddRequest {
-                flag: id.parse().unwrap_or_else(|e| {
-                    tracing::error!("Onebotv11: Failed to parse user id: {}, error: {}", id, e);
-                    Default::default()
-                }),
-                sub_type: "add".to_string(),
-                reason: None,
-                approve: match response {
-                    oxidebot::api::payload::RequestResponse::Approve => true,
-                    oxidebot::api::payload::RequestResponse::Reject => false,
-                },
-            });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to handle add group request, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    /// Bind the listener and return at once, the implementation may connect later.
+    pub async fn try_new_disconnected(config: ReverseWsConfig) -> Result<Self, ConnectError> {
+        let connect = ReverseWsListenConnect::try_new(config, true).await?;
+        Ok(Self::from_connect(connect))
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn handle_invite_group_request<'life0, 'async_trait>(
-        &'life0 self,
-        id: String,
-        response: RequestResponse,
-    ) -> ::core::pin::Pin<
-        Box<dyn ::core::future::Future<Output = Result<()>> + ::core::marker::Send + 'async_trait>,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload =
-            onebot_v11::api::payload::ApiPayload::SetGroupAddRequest(SetGroupAddRequest {
-                flag: id.parse().unwrap_or_else(|e| {
-                    tracing::error!("Onebotv11: Failed to parse user id: {}, error: {}", id, e);
-                    Default::default()
-                }),
-                sub_type: "invite".to_string(),
-                reason: None,
-                approve: match response {
-                    oxidebot::api::payload::RequestResponse::Approve => true,
-                    oxidebot::api::payload::RequestResponse::Reject => false,
-                },
-            });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                Ok(())
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to handle invite group request, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    /// The bots of every account that connected so far.
+    pub async fn accounts(&self) -> Vec<OnebotV11ReverseWsAccountBot> {
+        self.connect
+            .accounts()
+            .await
+            .into_iter()
+            .map(|account| self.with_connect(account))
+            .collect()
     }
 
-    #[must_use]
-    #[allow(clippy::type_complexity, clippy::type_repetition_in_bounds)]
-    fn get_file_info<'life0, 'async_trait>(
-        &'life0 self,
-        file_id: String,
-    ) -> ::core::pin::Pin<
-        Box<
-            dyn ::core::future::Future<Output = Result<File>> + ::core::marker::Send + 'async_trait,
-        >,
-    >
-    where
-        'life0: 'async_trait,
-        Self: 'async_trait,
-    {
-        let payload = onebot_v11::api::payload::ApiPayload::GetFile(GetFile { file_id: file_id });
-        Box::pin(async move {
-            let resp = self.connect.clone().call_api(payload).await?;
-            if resp.status == "ok" {
-                match resp.data {
-                    onebot_v11::api::resp::ApiRespData::GetFileResponse(resp) => Ok(File {
-                        name: resp.file_name,
-                        uri: None,
-                        mime: None,
-                        size: Some(resp.file_size),
-                        base64: Some(resp.base64),
-                        id: None,
-                    }),
-                    _ => Err(anyhow::anyhow!("Onebotv11: Unexpected response")),
-                }
-            } else {
-                Err(anyhow::anyhow!(
-                    "Onebotv11: Failed to get file info, resp: {:?}",
-                    resp
-                ))
-            }
-        })
+    /// The bot of the account with `self_id`, if it connected so far.
+    pub async fn account(&self, self_id: &str) -> Option<OnebotV11ReverseWsAccountBot> {
+        self.connect
+            .account(self_id)
+            .await
+            .map(|account| self.with_connect(account))
     }
 }
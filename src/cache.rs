@@ -0,0 +1,297 @@
+//! An opt-in cache of group member and friend lists.
+//!
+//! Fed by `get_group_member_list` and `get_bot_friend_list`, kept up to date by member
+//! increase/decrease, admin change, card change and friend add notices. Notices only carry ids,
+//! the cache fills in the profile and group info of the users they mention, and the lists are
+//! answered from it while fresh. Enable it with
+//! [`OnebotV11Bot::with_member_cache`](crate::bot::api::OnebotV11Bot::with_member_cache).
+
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use oxidebot::{
+    event::{
+        notice::{GroupAdminChangeType, GroupMemberDecreaseReason, GroupMemberIncreseReason},
+        Event, NoticeEvent, RequestEvent,
+    },
+    source::user::{Role, User},
+};
+
+use crate::event::merge_profile;
+
+/// Config for [`MemberCache`].
+///
+/// Lists older than `ttl` are fetched again, notices keep updating them in between. A member
+/// joining makes its group's list stale right away, its profile is only known after a fetch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MemberCacheConfig {
+    pub ttl: Duration,
+}
+
+impl Default for MemberCacheConfig {
+    fn default() -> Self {
+        MemberCacheConfig {
+            ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+struct Cached {
+    fetched_at: Instant,
+    stale: bool,
+    users: Vec<User>,
+}
+
+impl Cached {
+    fn new(users: Vec<User>) -> Self {
+        Cached {
+            fetched_at: Instant::now(),
+            stale: false,
+            users,
+        }
+    }
+
+    fn fresh(&self, ttl: Duration) -> Option<Vec<User>> {
+        (!self.stale && self.fetched_at.elapsed() < ttl).then(|| self.users.clone())
+    }
+
+    fn user(&self, user_id: &str) -> Option<&User> {
+        self.users.iter().find(|user| user.id == user_id)
+    }
+
+    fn user_mut(&mut self, user_id: &str) -> Option<&mut User> {
+        self.users.iter_mut().find(|user| user.id == user_id)
+    }
+}
+
+/// Group member lists by group id, friend lists by bot id.
+///
+/// Shared by every account of a bot, so profiles found in one account's lists fill the events
+/// of the others.
+pub struct MemberCache {
+    pub config: MemberCacheConfig,
+    groups: RwLock<HashMap<String, Cached>>,
+    friends: RwLock<HashMap<String, Cached>>,
+}
+
+impl MemberCache {
+    pub fn new(config: MemberCacheConfig) -> Self {
+        MemberCache {
+            config,
+            groups: RwLock::new(HashMap::new()),
+            friends: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The members of `group_id`, unless the cached list is missing or stale.
+    pub fn group_members(&self, group_id: &str) -> Option<Vec<User>> {
+        read(&self.groups).get(group_id)?.fresh(self.config.ttl)
+    }
+
+    /// A cached member of `group_id`, however old.
+    pub fn group_member(&self, group_id: &str, user_id: &str) -> Option<User> {
+        read(&self.groups).get(group_id)?.user(user_id).cloned()
+    }
+
+    /// The friends of the bot `bot_id`, unless the cached list is missing or stale.
+    pub fn friends(&self, bot_id: &str) -> Option<Vec<User>> {
+        read(&self.friends).get(bot_id)?.fresh(self.config.ttl)
+    }
+
+    /// A cached friend of any bot, however old.
+    pub fn friend(&self, user_id: &str) -> Option<User> {
+        read(&self.friends)
+            .values()
+            .find_map(|friends| friends.user(user_id).cloned())
+    }
+
+    /// Fetch `group_id`'s members again on the next `get_group_member_list`.
+    pub fn invalidate_group(&self, group_id: &str) {
+        if let Some(group) = write(&self.groups).get_mut(group_id) {
+            group.stale = true;
+        }
+    }
+
+    /// Fetch every friend list again on the next `get_bot_friend_list`.
+    pub fn invalidate_friends(&self) {
+        for friends in write(&self.friends).values_mut() {
+            friends.stale = true;
+        }
+    }
+
+    pub fn clear(&self) {
+        write(&self.groups).clear();
+        write(&self.friends).clear();
+    }
+
+    pub(crate) fn set_group_members(&self, group_id: String, members: Vec<User>) {
+        write(&self.groups).insert(group_id, Cached::new(members));
+    }
+
+    pub(crate) fn set_friends(&self, bot_id: String, friends: Vec<User>) {
+        write(&self.friends).insert(bot_id, Cached::new(friends));
+    }
+
+    /// Apply what a received event tells about members and friends.
+    pub(crate) fn observe(&self, event: &onebot_v11::Event) {
+        use onebot_v11::event::notice::Notice;
+        let onebot_v11::Event::Notice(notice) = event else {
+            return;
+        };
+        match notice {
+            Notice::GroupMemberIncrease(event) => {
+                self.invalidate_group(&event.group_id.to_string());
+            }
+            Notice::GroupMemberDecrease(event) => {
+                let group_id = event.group_id.to_string();
+                if event.sub_type == "kick_me" || event.user_id == event.self_id {
+                    write(&self.groups).remove(&group_id);
+                } else if let Some(group) = write(&self.groups).get_mut(&group_id) {
+                    group
+                        .users
+                        .retain(|user| user.id != event.user_id.to_string());
+                }
+            }
+            Notice::GroupAdminChange(event) => {
+                let role = match event.sub_type.as_str() {
+                    "set" => Role::Admin,
+                    "unset" => Role::Member,
+                    _ => return,
+                };
+                self.update_member(event.group_id, event.user_id, |user| {
+                    user.group_info.get_or_insert_with(Default::default).role = Some(role)
+                });
+            }
+            Notice::GroupCardChange(event) => {
+                self.update_member(event.group_id, event.user_id, |user| {
+                    user.group_info.get_or_insert_with(Default::default).alias =
+                        Some(event.card_new.clone())
+                });
+            }
+            Notice::FriendAdd(_) => self.invalidate_friends(),
+            _ => {}
+        }
+    }
+
+    fn update_member(&self, group_id: i64, user_id: i64, update: impl FnOnce(&mut User)) {
+        if let Some(user) = write(&self.groups)
+            .get_mut(&group_id.to_string())
+            .and_then(|group| group.user_mut(&user_id.to_string()))
+        {
+            update(user);
+        }
+    }
+
+    /// Fill the profile and group info of the users `event` mentions by id only.
+    pub(crate) fn fill(&self, event: &mut Event) {
+        let (group_id, users) = mentioned_users(event);
+        for user in users {
+            let cached = match &group_id {
+                Some(group_id) => self.group_member(group_id, &user.id),
+                None => self.friend(&user.id),
+            };
+            let Some(cached) = cached else {
+                continue;
+            };
+            merge_profile(&mut user.profile, cached.profile);
+            if user.group_info.is_none() && group_id.is_some() {
+                user.group_info = cached.group_info;
+            }
+        }
+        apply_change(event);
+    }
+}
+
+/// The group an event happened in and the users it mentions that may lack a profile.
+fn mentioned_users(event: &mut Event) -> (Option<String>, Vec<&mut User>) {
+    match event {
+        Event::NoticeEvent(notice) => match notice {
+            NoticeEvent::GroupMemberIncreseEvent(event) => {
+                let mut users = vec![&mut event.user];
+                match &mut event.reason {
+                    GroupMemberIncreseReason::Approve { operator } => users.extend(operator),
+                    GroupMemberIncreseReason::Invite { inviter, operator } => {
+                        users.extend(inviter);
+                        users.extend(operator);
+                    }
+                    _ => {}
+                }
+                (Some(event.group.id.clone()), users)
+            }
+            NoticeEvent::GroupMemberDecreaseEvent(event) => {
+                let mut users = vec![&mut event.user];
+                match &mut event.reason {
+                    GroupMemberDecreaseReason::Kick { operator }
+                    | GroupMemberDecreaseReason::KickMe { operator } => users.extend(operator),
+                    _ => {}
+                }
+                (Some(event.group.id.clone()), users)
+            }
+            NoticeEvent::GroupAdminChangeEvent(event) => {
+                (Some(event.group.id.clone()), vec![&mut event.user])
+            }
+            NoticeEvent::GroupMuteChangeEvent(event) => (
+                Some(event.group.id.clone()),
+                event.operator.iter_mut().collect(),
+            ),
+            NoticeEvent::GroupMemberMuteChangeEvent(event) => {
+                let mut users = vec![&mut event.user];
+                users.extend(&mut event.operator);
+                (Some(event.group.id.clone()), users)
+            }
+            NoticeEvent::GroupHightLightChangeEvent(event) => {
+                let mut users: Vec<_> = event.sender.iter_mut().collect();
+                users.extend(&mut event.operator);
+                (Some(event.group.id.clone()), users)
+            }
+            NoticeEvent::GroupMemberAliasChangeEvent(event) => {
+                let mut users = vec![&mut event.user];
+                users.extend(&mut event.operator);
+                (Some(event.group.id.clone()), users)
+            }
+            NoticeEvent::MessageDeletedEvent(event) => {
+                let mut users: Vec<_> = event.user.iter_mut().collect();
+                users.extend(&mut event.operator);
+                (event.group.as_ref().map(|group| group.id.clone()), users)
+            }
+            _ => (None, Vec::new()),
+        },
+        Event::RequestEvent(RequestEvent::FriendAddEvent(event)) => (None, vec![&mut event.user]),
+        _ => (None, Vec::new()),
+    }
+}
+
+/// Apply what the event itself says over the cached group info, which predates it.
+fn apply_change(event: &mut Event) {
+    if let Event::NoticeEvent(notice) = event {
+        match notice {
+            NoticeEvent::GroupAdminChangeEvent(event) => {
+                let role = match event.r#type {
+                    GroupAdminChangeType::Set => Role::Admin,
+                    GroupAdminChangeType::Unset => Role::Member,
+                    _ => return,
+                };
+                if let Some(group_info) = &mut event.user.group_info {
+                    group_info.role = Some(role);
+                }
+            }
+            NoticeEvent::GroupMemberAliasChangeEvent(event) => {
+                if let Some(group_info) = &mut event.user.group_info {
+                    group_info.alias = event.new_alias.clone();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> std::sync::RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn write<T>(lock: &RwLock<T>) -> std::sync::RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,295 @@
+use std::fmt::{self, Display};
+
+use onebot_v11::{
+    api::{payload::ApiPayload, resp::ApiRespData},
+    traits::EndPoint as _,
+};
+use serde_json::Value;
+use tokio_tungstenite::tungstenite;
+
+use crate::id::ParseIdError;
+
+/// Why a bot could not be constructed.
+#[derive(Debug)]
+pub enum ConnectError {
+    /// Failed to bind the local listening address.
+    Bind {
+        addr: String,
+        source: std::io::Error,
+    },
+    /// Failed to reach the OneBot implementation.
+    Connect { url: String, source: std::io::Error },
+    /// Reached the implementation, but the websocket handshake failed.
+    Handshake {
+        url: String,
+        source: tungstenite::Error,
+    },
+    /// The implementation refused our access token.
+    Auth { url: String, status: u16 },
+    /// Failed to set up the HTTP client.
+    Client { source: reqwest::Error },
+    /// Failed to read a recording to replay.
+    Recording {
+        path: String,
+        source: std::io::Error,
+    },
+}
+
+impl ConnectError {
+    pub(crate) fn from_ws(url: &str, error: tungstenite::Error) -> Self {
+        match error {
+            tungstenite::Error::Io(source) => ConnectError::Connect {
+                url: url.to_string(),
+                source,
+            },
+            tungstenite::Error::Http(resp)
+                if resp.status() == tungstenite::http::StatusCode::UNAUTHORIZED
+                    || resp.status() == tungstenite::http::StatusCode::FORBIDDEN =>
+            {
+                ConnectError::Auth {
+                    url: url.to_string(),
+                    status: resp.status().as_u16(),
+                }
+            }
+            source => ConnectError::Handshake {
+                url: url.to_string(),
+                source,
+            },
+        }
+    }
+}
+
+impl Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectError::Bind { addr, source } => {
+                write!(f, "Onebotv11: Failed to bind {}: {}", addr, source)
+            }
+            ConnectError::Connect { url, source } => {
+                write!(f, "Onebotv11: Failed to connect to {}: {}", url, source)
+            }
+            ConnectError::Handshake { url, source } => {
+                write!(
+                    f,
+                    "Onebotv11: Websocket handshake with {} failed: {}",
+                    url, source
+                )
+            }
+            ConnectError::Auth { url, status } => {
+                write!(
+                    f,
+                    "Onebotv11: {} rejected the access token with status {}",
+                    url, status
+                )
+            }
+            ConnectError::Client { source } => {
+                write!(f, "Onebotv11: Failed to build http client: {}", source)
+            }
+            ConnectError::Recording { path, source } => {
+                write!(
+                    f,
+                    "Onebotv11: Failed to read recording {}: {}",
+                    path, source
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConnectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConnectError::Bind { source, .. }
+            | ConnectError::Connect { source, .. }
+            | ConnectError::Recording { source, .. } => Some(source),
+            ConnectError::Handshake { source, .. } => Some(source),
+            ConnectError::Client { source } => Some(source),
+            ConnectError::Auth { .. } => None,
+        }
+    }
+}
+
+/// Why an api call failed.
+///
+/// Converts into `anyhow::Error` like any other error, plugins that care can
+/// `downcast_ref::<OnebotError>()` it back, e.g. to decide whether to retry.
+#[derive(Debug)]
+pub enum OnebotError {
+    /// The implementation handled the call and reported a failure.
+    Api {
+        action: String,
+        payload: Value,
+        status: String,
+        retcode: i64,
+        message: Option<String>,
+        wording: Option<String>,
+    },
+    /// The HTTP API answered with a non-200 status.
+    Http {
+        action: String,
+        payload: Value,
+        status: u16,
+    },
+    /// The response doesn't fit the action.
+    UnexpectedResponse {
+        action: String,
+        payload: Value,
+        response: Value,
+    },
+    /// No response arrived in time.
+    Timeout { action: String, payload: Value },
+    /// The connection to the implementation is down.
+    Disconnected { action: String, payload: Value },
+    /// The call can't be expressed in OneBot v11.
+    InvalidArgument { action: String, reason: String },
+    /// An id argument isn't a valid number, the call was not sent.
+    InvalidId {
+        action: String,
+        source: ParseIdError,
+    },
+    /// Any other failure while talking to the implementation.
+    Transport {
+        action: String,
+        payload: Value,
+        source: anyhow::Error,
+    },
+}
+
+impl OnebotError {
+    /// The OneBot action, e.g. `send_group_msg`.
+    pub fn action(&self) -> &str {
+        match self {
+            OnebotError::Api { action, .. }
+            | OnebotError::Http { action, .. }
+            | OnebotError::UnexpectedResponse { action, .. }
+            | OnebotError::Timeout { action, .. }
+            | OnebotError::Disconnected { action, .. }
+            | OnebotError::InvalidArgument { action, .. }
+            | OnebotError::InvalidId { action, .. }
+            | OnebotError::Transport { action, .. } => action,
+        }
+    }
+
+    /// The `retcode` reported by the implementation, if it answered at all.
+    pub fn retcode(&self) -> Option<i64> {
+        match self {
+            OnebotError::Api { retcode, .. } => Some(*retcode),
+            _ => None,
+        }
+    }
+
+    /// Whether the same call may succeed if tried again later.
+    ///
+    /// HTTP answers count when the server failed or asked to slow down, 5xx and 429.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            OnebotError::Timeout { .. }
+            | OnebotError::Disconnected { .. }
+            | OnebotError::Transport { .. } => true,
+            OnebotError::Http { status, .. } => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
+
+    pub(crate) fn timeout(payload: &ApiPayload) -> Self {
+        OnebotError::Timeout {
+            action: payload.endpoint(),
+            payload: payload_value(payload),
+        }
+    }
+
+    pub(crate) fn disconnected(payload: &ApiPayload) -> Self {
+        OnebotError::Disconnected {
+            action: payload.endpoint(),
+            payload: payload_value(payload),
+        }
+    }
+
+    pub(crate) fn transport(payload: &ApiPayload, source: impl Into<anyhow::Error>) -> Self {
+        OnebotError::Transport {
+            action: payload.endpoint(),
+            payload: payload_value(payload),
+            source: source.into(),
+        }
+    }
+
+    pub(crate) fn unexpected_response(payload: &ApiPayload, data: &ApiRespData) -> Self {
+        OnebotError::UnexpectedResponse {
+            action: payload.endpoint(),
+            payload: payload_value(payload),
+            response: serde_json::to_value(data).unwrap_or_default(),
+        }
+    }
+
+    pub(crate) fn invalid_argument(action: &str, reason: impl Into<String>) -> Self {
+        OnebotError::InvalidArgument {
+            action: action.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+pub(crate) fn payload_value(payload: &ApiPayload) -> Value {
+    serde_json::to_value(payload).unwrap_or_default()
+}
+
+impl Display for OnebotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnebotError::Api {
+                action,
+                status,
+                retcode,
+                message,
+                wording,
+                ..
+            } => write!(
+                f,
+                "Onebotv11: {} failed, status: {}, retcode: {}, message: {}",
+                action,
+                status,
+                retcode,
+                wording.as_ref().or(message.as_ref()).map_or("", |m| m)
+            ),
+            OnebotError::Http { action, status, .. } => {
+                write!(
+                    f,
+                    "Onebotv11: {} failed with http status {}",
+                    action, status
+                )
+            }
+            OnebotError::UnexpectedResponse {
+                action, response, ..
+            } => write!(
+                f,
+                "Onebotv11: Unexpected response to {}: {}",
+                action, response
+            ),
+            OnebotError::Timeout { action, .. } => {
+                write!(f, "Onebotv11: {} timed out", action)
+            }
+            OnebotError::Disconnected { action, .. } => {
+                write!(f, "Onebotv11: Can't call {}, disconnected", action)
+            }
+            OnebotError::InvalidArgument { action, reason } => {
+                write!(f, "Onebotv11: Invalid argument for {}: {}", action, reason)
+            }
+            OnebotError::InvalidId { action, source } => {
+                write!(f, "Onebotv11: Can't call {}, {}", action, source)
+            }
+            OnebotError::Transport { action, source, .. } => {
+                write!(f, "Onebotv11: Failed to call {}: {}", action, source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for OnebotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            OnebotError::InvalidId { source, .. } => Some(source),
+            OnebotError::Transport { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
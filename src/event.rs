@@ -1,4 +1,4 @@
-use std::{ops::Deref, sync::Arc, time::Duration};
+use std::{collections::HashMap, ops::Deref, sync::Arc, time::Duration};
 
 use anyhow::Result;
 use chrono::DateTime;
@@ -16,17 +16,54 @@ use oxidebot::{
     },
     source::{
         group::Group,
-        message::Message,
+        message::{Message, MessageSegment},
         user::{Role, Sex, User, UserGroupInfo, UserProfile},
     },
     EventTrait,
 };
 
-use crate::{segment::cast_segment, PLATFORM};
+use crate::{
+    avatar::AvatarConfig, bot::http::HeldReport, cache::MemberCache, forward, reply::QuotedMessage,
+    segment::cast_segment, store, PLATFORM,
+};
+
+/// A received event, parsed with what the bot that received it knows beyond it.
+///
+/// Breaking change: this used to be `EventWrapper(pub Arc<Event>)`. Patterns now need the
+/// second field, `EventWrapper(event, _)`, and `EventWrapper::from(event)` builds one with an
+/// empty [`EventContext`]. `.0` is still the event.
+pub struct EventWrapper(pub Arc<onebot_v11::Event>, pub EventContext);
+
+impl From<Arc<onebot_v11::Event>> for EventWrapper {
+    fn from(value: Arc<onebot_v11::Event>) -> Self {
+        EventWrapper(value, EventContext::default())
+    }
+}
 
-pub struct EventWrapper(pub Arc<onebot_v11::Event>);
+/// What a bot adds to the events it receives.
+#[derive(Clone, Default)]
+pub struct EventContext {
+    pub avatars: AvatarConfig,
+    pub members: Option<Arc<MemberCache>>,
+    /// The message a recall or essence notice is about.
+    pub message: Option<MessageEvent>,
+    /// The message a received message replies to.
+    pub quoted: Option<QuotedMessage>,
+    /// The nodes of the forwards in a received message, by forward id.
+    pub forwards: Arc<HashMap<String, Vec<MessageSegment>>>,
+    /// The HTTP report the event came in, while it's held open for a quick operation.
+    pub report: Option<Arc<HeldReport>>,
+}
 
+/// [`parse_event_with_avatars`] with the default [`AvatarConfig`].
 pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
+    parse_event_with_avatars(event, AvatarConfig::default())
+}
+
+pub fn parse_event_with_avatars(
+    event: onebot_v11::Event,
+    avatars: AvatarConfig,
+) -> Result<oxidebot::event::Event> {
     match event {
         onebot_v11::Event::Message(event) => match event {
             onebot_v11::event::message::Message::PrivateMessage(event) => {
@@ -43,25 +80,13 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                                     .clone()
                                     .unwrap_or(String::with_capacity(0)),
                             ),
-                            sex: {
-                                if let Some(sex) = &event.sender.sex {
-                                    Some(Sex::from(sex.as_str()))
-                                } else {
-                                    None
-                                }
-                            },
-                            avatar: None,
+                            sex: event.sender.sex.as_ref().map(|sex| Sex::from(sex.as_str())),
+                            avatar: avatars.user(event.user_id),
                             email: None,
                             phone: None,
                             signature: None,
                             level: None,
-                            age: {
-                                if let Some(age) = &event.sender.age {
-                                    Some(*age as u64)
-                                } else {
-                                    None
-                                }
-                            },
+                            age: event.sender.age.as_ref().map(|age| *age as u64),
                         }),
                         group_info: None,
                     },
@@ -72,7 +97,7 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                             .message
                             .clone()
                             .into_iter()
-                            .map(|segment| cast_segment(segment))
+                            .map(cast_segment)
                             .collect(),
                     },
                 }))
@@ -91,31 +116,13 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                                     .clone()
                                     .unwrap_or(String::with_capacity(0)),
                             ),
-                            sex: {
-                                if let Some(sex) = &event.sender.sex {
-                                    Some(Sex::from(sex.as_str()))
-                                } else {
-                                    None
-                                }
-                            },
-                            avatar: None,
+                            sex: event.sender.sex.as_ref().map(|sex| Sex::from(sex.as_str())),
+                            avatar: avatars.user(event.user_id),
                             email: None,
                             phone: None,
                             signature: None,
-                            level: {
-                                if let Some(level) = &event.sender.level {
-                                    Some(level.to_string())
-                                } else {
-                                    None
-                                }
-                            },
-                            age: {
-                                if let Some(age) = &event.sender.age {
-                                    Some(*age as u64)
-                                } else {
-                                    None
-                                }
-                            },
+                            level: event.sender.level.as_ref().map(|level| level.to_string()),
+                            age: event.sender.age.as_ref().map(|age| *age as u64),
                         }),
                         group_info: Some(UserGroupInfo {
                             alias: event.sender.card.clone(),
@@ -148,7 +155,7 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                             .message
                             .clone()
                             .into_iter()
-                            .map(|segment| cast_segment(segment))
+                            .map(cast_segment)
                             .collect(),
                     },
                 }))
@@ -186,11 +193,7 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                         id: event.group_id.to_string(),
                         profile: None,
                     },
-                    user: User {
-                        id: event.user_id.to_string(),
-                        profile: None,
-                        group_info: None,
-                    },
+                    user: user(event.user_id, avatars),
                     r#type: {
                         if event.sub_type == "set" {
                             GroupAdminChangeType::Set
@@ -209,30 +212,18 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                             id: event.group_id.to_string(),
                             profile: None,
                         },
-                        user: User {
-                            id: event.user_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        },
+                        user: user(event.user_id, avatars),
 
                         reason: {
                             if event.sub_type == "leave" {
                                 oxidebot::event::notice::GroupMemberDecreaseReason::Leave
                             } else if event.sub_type == "kick" {
                                 oxidebot::event::notice::GroupMemberDecreaseReason::Kick {
-                                    operator: Some(User {
-                                        id: event.operator_id.to_string(),
-                                        profile: None,
-                                        group_info: None,
-                                    }),
+                                    operator: Some(user(event.operator_id, avatars)),
                                 }
                             } else if event.sub_type == "kick_me" {
                                 oxidebot::event::notice::GroupMemberDecreaseReason::KickMe {
-                                    operator: Some(User {
-                                        id: event.operator_id.to_string(),
-                                        profile: None,
-                                        group_info: None,
-                                    }),
+                                    operator: Some(user(event.operator_id, avatars)),
                                 }
                             } else {
                                 oxidebot::event::notice::GroupMemberDecreaseReason::Unknown
@@ -248,28 +239,16 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                             id: event.group_id.to_string(),
                             profile: None,
                         },
-                        user: User {
-                            id: event.user_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        },
+                        user: user(event.user_id, avatars),
                         reason: {
                             if event.sub_type == "approve" {
                                 oxidebot::event::notice::GroupMemberIncreseReason::Approve {
-                                    operator: Some(User {
-                                        id: event.operator_id.to_string(),
-                                        profile: None,
-                                        group_info: None,
-                                    }),
+                                    operator: Some(user(event.operator_id, avatars)),
                                 }
                             } else if event.sub_type == "invite" {
                                 oxidebot::event::notice::GroupMemberIncreseReason::Invite {
                                     inviter: None,
-                                    operator: Some(User {
-                                        id: event.operator_id.to_string(),
-                                        profile: None,
-                                        group_info: None,
-                                    }),
+                                    operator: Some(user(event.operator_id, avatars)),
                                 }
                             } else {
                                 oxidebot::event::notice::GroupMemberIncreseReason::Unknown
@@ -285,16 +264,8 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                             id: event.group_id.to_string(),
                             profile: None,
                         },
-                        user: User {
-                            id: event.user_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        },
-                        operator: Some(User {
-                            id: event.operator_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        }),
+                        user: user(event.user_id, avatars),
+                        operator: Some(user(event.operator_id, avatars)),
                         r#type: {
                             if event.sub_type == "ban" {
                                 oxidebot::event::notice::MuteType::Mute {
@@ -311,31 +282,19 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
             )),
             onebot_v11::event::notice::Notice::FriendAdd(event) => Ok(Event::RequestEvent(
                 oxidebot::event::RequestEvent::FriendAddEvent(FriendAddEvent {
-                    user: User {
-                        id: event.user_id.to_string(),
-                        profile: None,
-                        group_info: None,
-                    },
+                    user: user(event.user_id, avatars),
                     id: event.user_id.to_string(),
                     message: None,
                 }),
             )),
             onebot_v11::event::notice::Notice::GroupMessageRecall(event) => Ok(Event::NoticeEvent(
                 oxidebot::event::NoticeEvent::MessageDeletedEvent(MessageDeletedEvent {
-                    user: Some(User {
-                        id: event.user_id.to_string(),
-                        profile: None,
-                        group_info: None,
-                    }),
+                    user: Some(user(event.user_id, avatars)),
                     group: Some(Group {
                         id: event.group_id.to_string(),
                         profile: None,
                     }),
-                    operator: Some(User {
-                        id: event.operator_id.to_string(),
-                        profile: None,
-                        group_info: None,
-                    }),
+                    operator: Some(user(event.operator_id, avatars)),
                     message: Some(Message {
                         id: event.message_id.to_string(),
                         segments: Vec::with_capacity(0),
@@ -345,17 +304,9 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
             onebot_v11::event::notice::Notice::FriendMessageRecall(event) => {
                 Ok(Event::NoticeEvent(
                     oxidebot::event::NoticeEvent::MessageDeletedEvent(MessageDeletedEvent {
-                        user: Some(User {
-                            id: event.user_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        }),
+                        user: Some(user(event.user_id, avatars)),
                         group: None,
-                        operator: Some(User {
-                            id: event.user_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        }),
+                        operator: Some(user(event.user_id, avatars)),
                         message: Some(Message {
                             id: event.message_id.to_string(),
                             segments: Vec::with_capacity(0),
@@ -410,16 +361,8 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                             id: event.message_id.to_string(),
                             segments: Vec::with_capacity(0),
                         },
-                        sender: Some(User {
-                            id: event.sender_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        }),
-                        operator: Some(User {
-                            id: event.user_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        }),
+                        sender: Some(user(event.sender_id, avatars)),
+                        operator: Some(user(event.user_id, avatars)),
                     },
                 )),
             ),
@@ -430,11 +373,7 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                             id: event.group_id.to_string(),
                             profile: None,
                         },
-                        user: User {
-                            id: event.user_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        },
+                        user: user(event.user_id, avatars),
                         operator: None,
                         old_alias: Some(event.card_old.clone()),
                         new_alias: Some(event.card_new.clone()),
@@ -447,11 +386,7 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                 Ok(Event::RequestEvent(
                     oxidebot::event::RequestEvent::FriendAddEvent(FriendAddEvent {
                         id: event.flag.to_string(),
-                        user: User {
-                            id: event.user_id.to_string(),
-                            profile: None,
-                            group_info: None,
-                        },
+                        user: user(event.user_id, avatars),
                         message: Some(event.comment.clone()),
                     }),
                 ))
@@ -461,11 +396,7 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                     Ok(Event::RequestEvent(
                         oxidebot::event::RequestEvent::GroupAddEvent(GroupAddEvent {
                             id: event.flag.clone(),
-                            user: User {
-                                id: event.user_id.to_string(),
-                                profile: None,
-                                group_info: None,
-                            },
+                            user: user(event.user_id, avatars),
                             group: Group {
                                 id: event.group_id.to_string(),
                                 profile: None,
@@ -477,11 +408,7 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
                     Ok(Event::RequestEvent(
                         oxidebot::event::RequestEvent::GroupInviteEvent(GroupInviteEvent {
                             id: event.flag.clone(),
-                            user: User {
-                                id: event.user_id.to_string(),
-                                profile: None,
-                                group_info: None,
-                            },
+                            user: user(event.user_id, avatars),
                             group_id: event.group_id.to_string(),
                             message: Some(event.comment.clone()),
                         }),
@@ -497,9 +424,82 @@ pub fn parse_event(event: onebot_v11::Event) -> Result<oxidebot::event::Event> {
     }
 }
 
+/// A user known only by id, with the avatar `avatars` gives it.
+fn user(id: i64, avatars: AvatarConfig) -> User {
+    User {
+        id: id.to_string(),
+        profile: avatars.user(id).map(|avatar| UserProfile {
+            avatar: Some(avatar),
+            ..Default::default()
+        }),
+        group_info: None,
+    }
+}
+
+/// Fill the fields `profile` lacks from `from`.
+pub(crate) fn merge_profile(profile: &mut Option<UserProfile>, from: Option<UserProfile>) {
+    let Some(from) = from else {
+        return;
+    };
+    let profile = profile.get_or_insert_with(UserProfile::default);
+    profile.nickname = profile.nickname.take().or(from.nickname);
+    profile.sex = profile.sex.take().or(from.sex);
+    profile.avatar = profile.avatar.take().or(from.avatar);
+    profile.email = profile.email.take().or(from.email);
+    profile.phone = profile.phone.take().or(from.phone);
+    profile.signature = profile.signature.take().or(from.signature);
+    profile.level = profile.level.take().or(from.level);
+    profile.age = profile.age.take().or(from.age);
+}
+
+/// The account that received `event`, `None` for api responses.
+pub fn event_self_id(event: &onebot_v11::Event) -> Option<i64> {
+    use onebot_v11::event::{message::Message, meta::Meta, notice::Notice, request::Request};
+    Some(match event {
+        onebot_v11::Event::Message(event) => match event {
+            Message::PrivateMessage(event) => event.self_id,
+            Message::GroupMessage(event) => event.self_id,
+        },
+        onebot_v11::Event::Meta(event) => match event {
+            Meta::Lifecycle(event) => event.self_id,
+            Meta::Heartbeat(event) => event.self_id,
+        },
+        onebot_v11::Event::Notice(event) => match event {
+            Notice::GroupFileUpload(event) => event.self_id,
+            Notice::GroupAdminChange(event) => event.self_id,
+            Notice::GroupMemberDecrease(event) => event.self_id,
+            Notice::GroupMemberIncrease(event) => event.self_id,
+            Notice::GroupBan(event) => event.self_id,
+            Notice::FriendAdd(event) => event.self_id,
+            Notice::GroupMessageRecall(event) => event.self_id,
+            Notice::FriendMessageRecall(event) => event.self_id,
+            Notice::GroupPoke(event) => event.self_id,
+            Notice::GroupLuckyKing(event) => event.self_id,
+            Notice::GroupMemberHonorChange(event) => event.self_id,
+            Notice::FriendInputStatusChange(event) => event.self_id,
+            Notice::GroupEssenceMessageChange(event) => event.self_id,
+            Notice::GroupCardChange(event) => event.self_id,
+        },
+        onebot_v11::Event::Request(event) => match event {
+            Request::FriendRequestEvent(event) => event.self_id,
+            Request::GroupRequestEvent(event) => event.self_id,
+        },
+        onebot_v11::Event::ApiRespBuilder(_) => return None,
+    })
+}
+
 impl EventTrait for EventWrapper {
     fn get_events(&self) -> Vec<Event> {
-        if let Ok(event) = parse_event(self.0.as_ref().clone()) {
+        if let Ok(mut event) = parse_event_with_avatars(self.0.as_ref().clone(), self.1.avatars) {
+            if let Some(message) = &self.1.message {
+                store::fill(&mut event, message);
+            }
+            if let Some(members) = &self.1.members {
+                members.fill(&mut event);
+            }
+            if !self.1.forwards.is_empty() {
+                forward::fill(&mut event, &self.1.forwards);
+            }
             vec![event]
         } else {
             vec![]
@@ -507,7 +507,7 @@ impl EventTrait for EventWrapper {
     }
 
     fn clone_box(&self) -> oxidebot::event::EventObject {
-        Box::new(EventWrapper(Arc::clone(&self.0)))
+        Box::new(EventWrapper(Arc::clone(&self.0), self.1.clone()))
     }
 
     fn server(&self) -> &'static str {
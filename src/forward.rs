@@ -0,0 +1,391 @@
+//! Forward (merged) messages.
+//!
+//! A received forward only carries an id, its nodes are fetched with `get_forward_msg`, see
+//! [`OnebotV11Bot::get_forward_message`]. Implementations disagree on the response: the standard
+//! answers `message` with `node` segments, go-cqhttp and NapCat answer `messages` with message
+//! objects, all of them are read. With [`ForwardConfig::expand_incoming`], forwards in received
+//! messages reach handlers already expanded.
+//!
+//! Messages made only of [`MessageSegment::ForwardNode`] and
+//! [`MessageSegment::ForwardCustomNode`] are sent as a forward, see
+//! [`OnebotV11Bot::send_forward_message`]. `send_message` sends them that way too and answers
+//! with the message id only, the forward's `res_id` is on what `send_forward_message` returns.
+
+use std::{collections::HashMap, time::Duration};
+
+use futures_util::future::BoxFuture;
+use onebot_v11::{
+    api::payload::{
+        ApiPayload, GetForwardMsg, MessageType, SendGroupForwardMsg, SendMsg, SendPrivateForwardMsg,
+    },
+    traits::EndPoint as _,
+};
+use oxidebot::{
+    api::payload::SendMessageTarget,
+    event::Event,
+    source::{
+        message::{Message, MessageSegment},
+        user::{User, UserGroupInfo, UserProfile},
+    },
+};
+use serde_json::Value;
+
+use crate::{
+    bot::{api::OnebotV11Bot, connect::OnebotConnect},
+    error::{payload_value, OnebotError},
+    id::{GroupId, UserId},
+    media,
+    segment::{cast_segment, parse_segment},
+};
+
+/// How long a received message waits for its forwards before reaching handlers without them.
+const EXPAND_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Config for forward messages.
+///
+/// Forwards nested in a forward are expanded up to `max_depth` levels below it, deeper ones
+/// stay [`MessageSegment::ForwardNode`]. With `expand_incoming`, forwards in received messages
+/// are fetched before the message reaches handlers, which holds up the events after it.
+/// Forwards are sent through `send`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ForwardConfig {
+    pub max_depth: usize,
+    pub expand_incoming: bool,
+    pub send: ForwardSendApi,
+}
+
+impl Default for ForwardConfig {
+    fn default() -> Self {
+        ForwardConfig {
+            max_depth: 3,
+            expand_incoming: false,
+            send: ForwardSendApi::ForwardMsg,
+        }
+    }
+}
+
+/// The api forward messages are sent with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ForwardSendApi {
+    /// `send_group_forward_msg` and `send_private_forward_msg`, from go-cqhttp and supported
+    /// by most implementations.
+    #[default]
+    ForwardMsg,
+    /// `send_msg` with the nodes as its message. NapCat's `send_forward_msg` is this same
+    /// call under another name, and NapCat and LLOneBot send a message made only of nodes as a
+    /// forward.
+    SendMsg,
+}
+
+/// A forward message that was sent.
+///
+/// `res_id` is the id of its content, what [`OnebotV11Bot::get_forward_message`] takes,
+/// when the implementation reports it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SentForward {
+    pub message_id: String,
+    pub res_id: Option<String>,
+}
+
+/// Whether `message` is made only of forward nodes, and is sent as a forward.
+pub fn is_forward(message: &[MessageSegment]) -> bool {
+    !message.is_empty()
+        && message.iter().all(|segment| {
+            matches!(
+                segment,
+                MessageSegment::ForwardNode { .. } | MessageSegment::ForwardCustomNode { .. }
+            )
+        })
+}
+
+impl<C: OnebotConnect> OnebotV11Bot<C> {
+    /// The nodes of forward `forward_id`, as [`MessageSegment::ForwardCustomNode`]s carrying
+    /// their sender and segments.
+    ///
+    /// A nested forward is replaced by its own nodes within the node holding it. One that fails
+    /// to load is kept as a [`MessageSegment::ForwardNode`].
+    pub async fn get_forward_message(
+        &self,
+        forward_id: &str,
+    ) -> Result<Vec<MessageSegment>, OnebotError> {
+        self.expand_forward(forward_id.to_string(), self.forwards.max_depth)
+            .await
+    }
+
+    /// Send `nodes` as a single forward message, with [`ForwardConfig::send`].
+    pub async fn send_forward_message(
+        &self,
+        nodes: Vec<MessageSegment>,
+        target: SendMessageTarget,
+    ) -> Result<SentForward, OnebotError> {
+        let messages: Vec<_> = nodes.into_iter().map(parse_segment).collect();
+        let mut prepared = messages.clone();
+        let send_msg = self.forwards.send == ForwardSendApi::SendMsg;
+        let server = self.media_server.as_ref();
+        let (payload, group_id, peer_id, _published) = match target {
+            SendMessageTarget::Group(id) => {
+                let action = if send_msg {
+                    "send_msg"
+                } else {
+                    "send_group_forward_msg"
+                };
+                let group_id = GroupId::parse_for(action, &id)?;
+                let published = media::prepare(&mut prepared, &self.media, server, action).await?;
+                let payload = if send_msg {
+                    ApiPayload::SendMsg(SendMsg {
+                        message_type: MessageType::Group,
+                        user_id: None,
+                        group_id: Some(group_id.into()),
+                        message: prepared,
+                        auto_escape: true,
+                    })
+                } else {
+                    ApiPayload::SendGroupForwardMsg(SendGroupForwardMsg {
+                        group_id: group_id.into(),
+                        messages: prepared,
+                    })
+                };
+                (payload, Some(group_id.to_string()), None, published)
+            }
+            SendMessageTarget::Private(id) => {
+                let action = if send_msg {
+                    "send_msg"
+                } else {
+                    "send_private_forward_msg"
+                };
+                let user_id = UserId::parse_for(action, &id)?;
+                let published = media::prepare(&mut prepared, &self.media, server, action).await?;
+                let payload = if send_msg {
+                    ApiPayload::SendMsg(SendMsg {
+                        message_type: MessageType::Private,
+                        user_id: Some(user_id.into()),
+                        group_id: None,
+                        message: prepared,
+                        auto_escape: true,
+                    })
+                } else {
+                    ApiPayload::SendPrivateForwardMsg(SendPrivateForwardMsg {
+                        user_id: user_id.into(),
+                        messages: prepared,
+                    })
+                };
+                (payload, None, Some(user_id.to_string()), published)
+            }
+        };
+        let action = payload.endpoint();
+        let error_payload = payload_value(&payload);
+        let raw = self.call_api_raw(payload).await?;
+        let data = &raw["data"];
+        let Some(message_id) = id_string(&data["message_id"]) else {
+            return Err(OnebotError::UnexpectedResponse {
+                action,
+                payload: error_payload,
+                response: raw,
+            });
+        };
+        // go-cqhttp calls it `forward_id`.
+        let res_id = id_string(&data["res_id"]).or_else(|| id_string(&data["forward_id"]));
+        self.save_sent(message_id.clone(), group_id, peer_id, messages)
+            .await;
+        Ok(SentForward { message_id, res_id })
+    }
+
+    /// The nodes of the forwards in a received message, by forward id, for
+    /// [`ForwardConfig::expand_incoming`].
+    pub(crate) async fn expand_incoming(
+        &self,
+        event: &onebot_v11::Event,
+    ) -> HashMap<String, Vec<MessageSegment>> {
+        let mut forwards = HashMap::new();
+        for forward_id in forward_ids(event) {
+            match tokio::time::timeout(EXPAND_TIMEOUT, self.get_forward_message(&forward_id)).await
+            {
+                Ok(Ok(nodes)) => {
+                    forwards.insert(forward_id, nodes);
+                }
+                Ok(Err(e)) => {
+                    tracing::warn!("Onebotv11: Failed to expand forward {}: {}", forward_id, e)
+                }
+                Err(_) => tracing::warn!("Onebotv11: Expanding forward {} timed out", forward_id),
+            }
+        }
+        forwards
+    }
+
+    fn expand_forward(
+        &self,
+        forward_id: String,
+        depth: usize,
+    ) -> BoxFuture<'_, Result<Vec<MessageSegment>, OnebotError>> {
+        Box::pin(async move {
+            let payload = ApiPayload::GetForwardMsg(GetForwardMsg { id: forward_id });
+            let action = payload.endpoint();
+            let error_payload = payload_value(&payload);
+            let raw = self.call_api_raw(payload).await?;
+            let Some(nodes) = nodes(&raw["data"]) else {
+                return Err(OnebotError::UnexpectedResponse {
+                    action,
+                    payload: error_payload,
+                    response: raw,
+                });
+            };
+            let mut segments = Vec::with_capacity(nodes.len());
+            for node in nodes {
+                segments.push(self.node(node, depth).await);
+            }
+            Ok(segments)
+        })
+    }
+
+    fn node<'a>(&'a self, node: &'a Value, depth: usize) -> BoxFuture<'a, MessageSegment> {
+        Box::pin(async move {
+            let data = if node["type"] == "node" {
+                &node["data"]
+            } else {
+                node
+            };
+            let content = match data.get("content").or_else(|| data.get("message")) {
+                Some(Value::Array(content)) => content.clone(),
+                Some(Value::String(text)) => {
+                    vec![serde_json::json!({ "type": "text", "data": { "text": text } })]
+                }
+                _ => Vec::new(),
+            };
+            let mut segments = Vec::with_capacity(content.len());
+            for segment in content {
+                if segment["type"] != "forward" {
+                    segments.push(segment_from_value(segment));
+                    continue;
+                }
+                let forward_id = id_string(&segment["data"]["id"]).unwrap_or_default();
+                if depth == 0 {
+                    segments.push(MessageSegment::ForwardNode {
+                        message_id: forward_id,
+                    });
+                    continue;
+                }
+                // NapCat sends the nodes of nested forwards along, the others only the id.
+                let expanded = match &segment["data"]["content"] {
+                    Value::Array(inner) => {
+                        let mut nodes = Vec::with_capacity(inner.len());
+                        for node in inner {
+                            nodes.push(self.node(node, depth - 1).await);
+                        }
+                        Ok(nodes)
+                    }
+                    _ => self.expand_forward(forward_id.clone(), depth - 1).await,
+                };
+                match expanded {
+                    Ok(nodes) => segments.extend(nodes),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Onebotv11: Failed to expand nested forward {}: {}",
+                            forward_id,
+                            e
+                        );
+                        segments.push(MessageSegment::ForwardNode {
+                            message_id: forward_id,
+                        });
+                    }
+                }
+            }
+            MessageSegment::ForwardCustomNode {
+                user: self.node_user(data),
+                message: Message {
+                    id: id_string(&data["message_id"]).unwrap_or_default(),
+                    segments,
+                },
+            }
+        })
+    }
+
+    fn node_user(&self, data: &Value) -> Option<User> {
+        let sender = &data["sender"];
+        let id = [&data["user_id"], &data["uin"], &sender["user_id"]]
+            .into_iter()
+            .find_map(id_string)?;
+        let nickname = [&data["nickname"], &data["name"], &sender["nickname"]]
+            .into_iter()
+            .find_map(|name| name.as_str().map(str::to_string));
+        Some(User {
+            profile: Some(UserProfile {
+                nickname,
+                avatar: self.avatars.user(&id),
+                ..Default::default()
+            }),
+            group_info: sender["card"]
+                .as_str()
+                .filter(|card| !card.is_empty())
+                .map(|card| UserGroupInfo {
+                    alias: Some(card.to_string()),
+                    ..Default::default()
+                }),
+            id,
+        })
+    }
+}
+
+/// The nodes of a `get_forward_msg` response, `None` if it has none in any known shape.
+fn nodes(data: &Value) -> Option<&Vec<Value>> {
+    data.get("messages")
+        .or_else(|| data.get("message"))
+        .and_then(Value::as_array)
+        .or_else(|| data.as_array())
+}
+
+/// Ids are numbers or strings depending on the implementation.
+fn id_string(id: &Value) -> Option<String> {
+    match id {
+        Value::String(id) if !id.is_empty() => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// A segment of a node, segments this crate can't parse are kept as
+/// [`MessageSegment::CustomValue`].
+fn segment_from_value(segment: Value) -> MessageSegment {
+    match serde_json::from_value::<onebot_v11::MessageSegment>(segment.clone()) {
+        Ok(parsed) => cast_segment(parsed),
+        Err(_) => MessageSegment::CustomValue {
+            r#type: segment["type"].as_str().unwrap_or_default().to_string(),
+            data: segment["data"].clone(),
+        },
+    }
+}
+
+/// The ids of the forwards in a received message.
+fn forward_ids(event: &onebot_v11::Event) -> Vec<String> {
+    use onebot_v11::event::message::Message;
+    let onebot_v11::Event::Message(event) = event else {
+        return Vec::new();
+    };
+    let segments = match event {
+        Message::PrivateMessage(event) => &event.message,
+        Message::GroupMessage(event) => &event.message,
+    };
+    segments
+        .iter()
+        .filter_map(|segment| match segment {
+            onebot_v11::MessageSegment::Forward { data } => Some(data.id.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Replace the forwards of a message with their nodes.
+pub(crate) fn fill(event: &mut Event, forwards: &HashMap<String, Vec<MessageSegment>>) {
+    let Event::MessageEvent(event) = event else {
+        return;
+    };
+    event.message.segments = std::mem::take(&mut event.message.segments)
+        .into_iter()
+        .flat_map(|segment| match segment {
+            MessageSegment::ForwardNode { message_id } => match forwards.get(&message_id) {
+                Some(nodes) => nodes.clone(),
+                None => vec![MessageSegment::ForwardNode { message_id }],
+            },
+            segment => vec![segment],
+        })
+        .collect();
+}
@@ -0,0 +1,261 @@
+//! Opt-in persistent message history.
+//!
+//! With a [`HistoryBackend`] set by
+//! [`OnebotV11Bot::with_history`](crate::bot::api::OnebotV11Bot::with_history), the bot saves
+//! every message it receives and every message it sends, with the ids `send_message` returned.
+//! `get_message_detail` answers from it before asking the implementation, whose `get_msg` only
+//! knows recent messages.
+//!
+//! Messages are kept as the OneBot segments that were received or sent, so what
+//! [`crate::segment::LOSSY_PATHS`] lists as lost on the way out is lost here too.
+
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::RwLock,
+};
+
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use oxidebot::source::message::MessageSegment;
+
+use crate::segment::cast_segment;
+
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteHistory;
+
+/// A message as the history keeps it.
+///
+/// Message ids are only unique per account, `self_id` is the account that received or sent
+/// it. The bot keys messages by its connection's account: the `self_id` of reverse websocket
+/// accounts, the configured `bot_id` of other transports. Without one, received messages are
+/// kept under their event's `self_id` and the bot asks `get_login_info` for its own.
+/// `peer_id` is the other side of a private chat, the sender of a received message or the
+/// recipient of a sent one, `None` in groups. `nickname` is the sender's, if known.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StoredMessage {
+    pub self_id: String,
+    pub id: String,
+    pub group_id: Option<String>,
+    pub peer_id: Option<String>,
+    pub user_id: String,
+    pub nickname: Option<String>,
+    pub time: DateTime<Utc>,
+    pub message: Vec<onebot_v11::MessageSegment>,
+    pub outgoing: bool,
+}
+
+impl StoredMessage {
+    pub fn segments(&self) -> Vec<MessageSegment> {
+        self.message.iter().cloned().map(cast_segment).collect()
+    }
+
+    /// The text segments joined, what keyword queries search.
+    pub fn text(&self) -> String {
+        self.message
+            .iter()
+            .filter_map(|segment| match segment {
+                onebot_v11::MessageSegment::Text { data } => Some(data.text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// A received message event, `None` for any other event.
+    pub fn from_event(event: &onebot_v11::Event) -> Option<Self> {
+        use onebot_v11::event::message::Message;
+        let onebot_v11::Event::Message(event) = event else {
+            return None;
+        };
+        Some(match event {
+            Message::PrivateMessage(event) => StoredMessage {
+                self_id: event.self_id.to_string(),
+                id: event.message_id.to_string(),
+                group_id: None,
+                peer_id: Some(event.user_id.to_string()),
+                user_id: event.user_id.to_string(),
+                nickname: event.sender.nickname.clone(),
+                time: DateTime::from_timestamp(event.time, 0).unwrap_or_default(),
+                message: event.message.clone(),
+                outgoing: false,
+            },
+            Message::GroupMessage(event) => StoredMessage {
+                self_id: event.self_id.to_string(),
+                id: event.message_id.to_string(),
+                group_id: Some(event.group_id.to_string()),
+                peer_id: None,
+                user_id: event.user_id.to_string(),
+                nickname: event.sender.nickname.clone(),
+                time: DateTime::from_timestamp(event.time, 0).unwrap_or_default(),
+                message: event.message.clone(),
+                outgoing: false,
+            },
+        })
+    }
+}
+
+/// Which messages a [`HistoryBackend::query`] returns, every set field must match.
+///
+/// `since` is inclusive and `until` exclusive. `keyword` is searched in
+/// [`StoredMessage::text`]. Results are newest first, at most `limit` of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    pub self_id: Option<String>,
+    pub group_id: Option<String>,
+    pub peer_id: Option<String>,
+    pub user_id: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub keyword: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    pub fn matches(&self, message: &StoredMessage) -> bool {
+        self.self_id
+            .as_ref()
+            .is_none_or(|id| message.self_id == *id)
+            && self
+                .group_id
+                .as_ref()
+                .is_none_or(|id| message.group_id.as_ref() == Some(id))
+            && self
+                .peer_id
+                .as_ref()
+                .is_none_or(|id| message.peer_id.as_ref() == Some(id))
+            && self
+                .user_id
+                .as_ref()
+                .is_none_or(|id| message.user_id == *id)
+            && self.since.is_none_or(|since| message.time >= since)
+            && self.until.is_none_or(|until| message.time < until)
+            && self
+                .keyword
+                .as_ref()
+                .is_none_or(|keyword| message.text().contains(keyword.as_str()))
+    }
+}
+
+/// Where the history is kept.
+///
+/// Saving a message with an id already saved for its account replaces it.
+pub trait HistoryBackend: Send + Sync + 'static {
+    fn save(&self, message: StoredMessage) -> BoxFuture<'_, anyhow::Result<()>>;
+
+    fn get<'a>(
+        &'a self,
+        self_id: &'a str,
+        message_id: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<Option<StoredMessage>>>;
+
+    fn query<'a>(
+        &'a self,
+        query: &'a HistoryQuery,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<StoredMessage>>>;
+}
+
+/// Keeps the last `capacity` messages in memory, [`MemoryHistory::DEFAULT_CAPACITY`] unless
+/// [`MemoryHistory::bounded`].
+pub struct MemoryHistory {
+    capacity: usize,
+    held: RwLock<Held>,
+}
+
+/// Messages by account and id, with the order they were saved in. A message saved again gets a
+/// new place in `order`, its old one is skipped by its stale `seq`.
+#[derive(Default)]
+struct Held {
+    seq: u64,
+    messages: HashMap<(String, String), (u64, StoredMessage)>,
+    order: VecDeque<(u64, (String, String))>,
+}
+
+impl Held {
+    fn is_current(&self, seq: u64, key: &(String, String)) -> bool {
+        self.messages.get(key).is_some_and(|(held, _)| *held == seq)
+    }
+}
+
+impl MemoryHistory {
+    pub const DEFAULT_CAPACITY: usize = 10_000;
+
+    pub fn new() -> Self {
+        Self::bounded(Self::DEFAULT_CAPACITY)
+    }
+
+    pub fn bounded(capacity: usize) -> Self {
+        MemoryHistory {
+            capacity,
+            held: RwLock::default(),
+        }
+    }
+}
+
+impl Default for MemoryHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HistoryBackend for MemoryHistory {
+    fn save(&self, message: StoredMessage) -> BoxFuture<'_, anyhow::Result<()>> {
+        let mut held = self.held.write().unwrap_or_else(|e| e.into_inner());
+        held.seq += 1;
+        let seq = held.seq;
+        let key = (message.self_id.clone(), message.id.clone());
+        held.messages.insert(key.clone(), (seq, message));
+        held.order.push_back((seq, key));
+        while held.messages.len() > self.capacity {
+            let Some((seq, key)) = held.order.pop_front() else {
+                break;
+            };
+            if held.is_current(seq, &key) {
+                held.messages.remove(&key);
+            }
+        }
+        if held.order.len() > held.messages.len() * 2 {
+            let order = std::mem::take(&mut held.order);
+            held.order = order
+                .into_iter()
+                .filter(|(seq, key)| held.is_current(*seq, key))
+                .collect();
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn get<'a>(
+        &'a self,
+        self_id: &'a str,
+        message_id: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<Option<StoredMessage>>> {
+        let held = self.held.read().unwrap_or_else(|e| e.into_inner());
+        let message = held
+            .messages
+            .get(&(self_id.to_string(), message_id.to_string()))
+            .map(|(_, message)| message.clone());
+        Box::pin(async { Ok(message) })
+    }
+
+    fn query<'a>(
+        &'a self,
+        query: &'a HistoryQuery,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<StoredMessage>>> {
+        let held = self.held.read().unwrap_or_else(|e| e.into_inner());
+        let mut found: Vec<_> = held
+            .order
+            .iter()
+            .rev()
+            .filter_map(|(seq, key)| match held.messages.get(key) {
+                Some((held, message)) if held == seq => Some(message),
+                _ => None,
+            })
+            .filter(|message| query.matches(message))
+            .cloned()
+            .collect();
+        found.sort_by_key(|message| std::cmp::Reverse(message.time));
+        found.truncate(query.limit.unwrap_or(usize::MAX));
+        Box::pin(async { Ok(found) })
+    }
+}
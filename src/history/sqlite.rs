@@ -0,0 +1,189 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Context as _;
+use chrono::DateTime;
+use futures_util::future::BoxFuture;
+use rusqlite::{types::Value, Connection, OptionalExtension as _, Row};
+
+use super::{HistoryBackend, HistoryQuery, StoredMessage};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS messages (
+    self_id TEXT NOT NULL,
+    id TEXT NOT NULL,
+    group_id TEXT,
+    peer_id TEXT,
+    user_id TEXT NOT NULL,
+    nickname TEXT,
+    time INTEGER NOT NULL,
+    text TEXT NOT NULL,
+    message TEXT NOT NULL,
+    outgoing INTEGER NOT NULL,
+    PRIMARY KEY (self_id, id)
+);
+CREATE INDEX IF NOT EXISTS messages_group_time ON messages (group_id, time);
+CREATE INDEX IF NOT EXISTS messages_peer_time ON messages (peer_id, time);
+CREATE INDEX IF NOT EXISTS messages_user_time ON messages (user_id, time);
+";
+
+const COLUMNS: &str = "self_id, id, group_id, peer_id, user_id, nickname, time, message, outgoing";
+
+/// Keeps the history in an SQLite database, queries run on tokio's blocking threads.
+///
+/// Times are stored as unix seconds, segments as their OneBot JSON.
+pub struct SqliteHistory {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteHistory {
+    /// Open or create the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteHistory {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> anyhow::Result<T> + Send + 'static,
+    ) -> BoxFuture<'static, anyhow::Result<T>> {
+        let connection = self.connection.clone();
+        Box::pin(async move {
+            tokio::task::spawn_blocking(move || {
+                f(&connection.lock().unwrap_or_else(|e| e.into_inner()))
+            })
+            .await?
+        })
+    }
+}
+
+fn stored_message(row: &Row) -> rusqlite::Result<(StoredMessage, String)> {
+    Ok((
+        StoredMessage {
+            self_id: row.get(0)?,
+            id: row.get(1)?,
+            group_id: row.get(2)?,
+            peer_id: row.get(3)?,
+            user_id: row.get(4)?,
+            nickname: row.get(5)?,
+            time: DateTime::from_timestamp(row.get(6)?, 0).unwrap_or_default(),
+            message: Vec::new(),
+            outgoing: row.get(8)?,
+        },
+        row.get(7)?,
+    ))
+}
+
+fn with_segments(
+    (mut message, segments): (StoredMessage, String),
+) -> anyhow::Result<StoredMessage> {
+    message.message = serde_json::from_str(&segments)
+        .with_context(|| format!("segments of stored message {}", message.id))?;
+    Ok(message)
+}
+
+impl HistoryBackend for SqliteHistory {
+    fn save(&self, message: StoredMessage) -> BoxFuture<'_, anyhow::Result<()>> {
+        self.run(move |connection| {
+            connection.execute(
+                "INSERT OR REPLACE INTO messages
+                 (self_id, id, group_id, peer_id, user_id, nickname, time, text, message, outgoing)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                (
+                    &message.self_id,
+                    &message.id,
+                    &message.group_id,
+                    &message.peer_id,
+                    &message.user_id,
+                    &message.nickname,
+                    message.time.timestamp(),
+                    message.text(),
+                    serde_json::to_string(&message.message)?,
+                    message.outgoing,
+                ),
+            )?;
+            Ok(())
+        })
+    }
+
+    fn get<'a>(
+        &'a self,
+        self_id: &'a str,
+        message_id: &'a str,
+    ) -> BoxFuture<'a, anyhow::Result<Option<StoredMessage>>> {
+        let (self_id, message_id) = (self_id.to_string(), message_id.to_string());
+        self.run(move |connection| {
+            connection
+                .query_row(
+                    &format!(
+                        "SELECT {} FROM messages WHERE self_id = ?1 AND id = ?2",
+                        COLUMNS
+                    ),
+                    [self_id, message_id],
+                    stored_message,
+                )
+                .optional()?
+                .map(with_segments)
+                .transpose()
+        })
+    }
+
+    fn query<'a>(
+        &'a self,
+        query: &'a HistoryQuery,
+    ) -> BoxFuture<'a, anyhow::Result<Vec<StoredMessage>>> {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        let mut condition = |sql: &str, value: Value| {
+            params.push(value);
+            conditions.push(sql.replace('?', &format!("?{}", params.len())));
+        };
+        if let Some(self_id) = &query.self_id {
+            condition("self_id = ?", Value::Text(self_id.clone()));
+        }
+        if let Some(group_id) = &query.group_id {
+            condition("group_id = ?", Value::Text(group_id.clone()));
+        }
+        if let Some(peer_id) = &query.peer_id {
+            condition("peer_id = ?", Value::Text(peer_id.clone()));
+        }
+        if let Some(user_id) = &query.user_id {
+            condition("user_id = ?", Value::Text(user_id.clone()));
+        }
+        if let Some(since) = query.since {
+            condition("time >= ?", Value::Integer(since.timestamp()));
+        }
+        if let Some(until) = query.until {
+            condition("time < ?", Value::Integer(until.timestamp()));
+        }
+        if let Some(keyword) = &query.keyword {
+            condition("instr(text, ?) > 0", Value::Text(keyword.clone()));
+        }
+        let mut sql = format!("SELECT {} FROM messages", COLUMNS);
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY time DESC, rowid DESC");
+        if let Some(limit) = query.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+        self.run(move |connection| {
+            let mut statement = connection.prepare(&sql)?;
+            let rows = statement.query_map(rusqlite::params_from_iter(params), stored_message)?;
+            rows.map(|row| with_segments(row?)).collect()
+        })
+    }
+}
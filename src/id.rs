@@ -0,0 +1,112 @@
+//! Validated numeric ids.
+//!
+//! oxidebot passes ids around as strings, OneBot v11 wants integers. Parsing
+//! through these types makes a typo fail the call instead of sending it to id `0`.
+
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+use crate::error::OnebotError;
+
+/// Why a string is not a valid id.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIdError {
+    /// What the id was meant to be, e.g. `group id`.
+    pub kind: &'static str,
+    /// What a valid id looks like.
+    pub expected: &'static str,
+    /// The string that failed to parse.
+    pub value: String,
+}
+
+impl Display for ParseIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} {:?}, expected {}",
+            self.kind, self.value, self.expected
+        )
+    }
+}
+
+impl std::error::Error for ParseIdError {}
+
+macro_rules! id_type {
+    ($(#[$meta:meta])* $name:ident, $kind:literal, $valid:expr, $expected:literal) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(i64);
+
+        impl $name {
+            #[doc = concat!("Wrap a raw id, `None` unless it is ", $expected, ".")]
+            pub fn new(id: i64) -> Option<Self> {
+                let valid: fn(i64) -> bool = $valid;
+                valid(id).then_some(Self(id))
+            }
+
+            pub fn get(self) -> i64 {
+                self.0
+            }
+
+            #[allow(clippy::result_large_err)]
+            pub(crate) fn parse_for(action: &str, id: &str) -> Result<Self, OnebotError> {
+                id.parse().map_err(|source| OnebotError::InvalidId {
+                    action: action.to_string(),
+                    source,
+                })
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = ParseIdError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.trim()
+                    .parse()
+                    .ok()
+                    .and_then(Self::new)
+                    .ok_or_else(|| ParseIdError {
+                        kind: $kind,
+                        expected: $expected,
+                        value: s.to_string(),
+                    })
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl From<$name> for i64 {
+            fn from(id: $name) -> i64 {
+                id.0
+            }
+        }
+    };
+}
+
+id_type!(
+    /// A QQ group number.
+    GroupId,
+    "group id",
+    |id| id > 0,
+    "a positive integer"
+);
+id_type!(
+    /// A QQ account number.
+    UserId,
+    "user id",
+    |id| id > 0,
+    "a positive integer"
+);
+id_type!(
+    /// A message id assigned by the implementation, some of them hand out negative ones.
+    MessageId,
+    "message id",
+    |id| id != 0,
+    "a non-zero integer"
+);
@@ -1,7 +1,23 @@
+pub mod avatar;
 pub mod bot;
+pub mod cache;
+pub mod error;
 pub mod event;
+pub mod forward;
+pub mod history;
+pub mod id;
+pub mod media;
+pub mod record;
+pub mod reply;
 pub mod segment;
+pub mod split;
+pub mod store;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod ttl;
+pub use bot::http::OnebotV11HttpBot;
+pub use bot::replay::OnebotV11ReplayBot;
 pub use bot::ws::OnebotV11WsBot;
-pub use bot::ws_reverse::OnebotV11ReverseWsBot;
+pub use bot::ws_reverse::{OnebotV11ReverseWsAccountBot, OnebotV11ReverseWsBot};
 
-const PLATFORM: &'static str = "onebot_v11";
+const PLATFORM: &str = "onebot_v11";
@@ -0,0 +1,388 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context as _;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use mime_guess::mime::{self, Mime};
+use onebot_v11::{
+    api::payload::{ApiPayload, GetFile, GetImage, GetRecord},
+    traits::EndPoint as _,
+};
+use oxidebot::source::message::File;
+use serde_json::json;
+use sha1::{Digest as _, Sha1};
+use tokio::sync::Mutex;
+
+use crate::{
+    bot::{api::OnebotV11Bot, connect::OnebotConnect},
+    error::{payload_value, OnebotError},
+};
+
+use super::{local_path, uri_source};
+
+/// Config for [`OnebotV11Bot::fetch_file`].
+///
+/// Downloads give up after `timeout`, and files over `max_size` bytes are refused. With
+/// `cache_dir`, fetched files are kept in a `fetched` directory under it, the least recently used
+/// removed once all of them take up more than `cache_size` bytes. Files carrying base64 aren't
+/// cached.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FetchConfig {
+    pub timeout: Duration,
+    pub max_size: u64,
+    pub cache_dir: Option<PathBuf>,
+    pub cache_size: u64,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        FetchConfig {
+            timeout: Duration::from_secs(60),
+            max_size: 100 * 1024 * 1024,
+            cache_dir: None,
+            cache_size: 512 * 1024 * 1024,
+        }
+    }
+}
+
+/// A fetched file, its `size` and `mime` filled in from the content.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FetchedFile {
+    pub file: File,
+    pub bytes: Vec<u8>,
+}
+
+/// Magic numbers of the formats QQ sends, with the offset they are found at.
+const SIGNATURES: &[(usize, &[u8], &str)] = &[
+    (0, b"\x89PNG\r\n\x1a\n", "image/png"),
+    (0, b"\xff\xd8\xff", "image/jpeg"),
+    (0, b"GIF8", "image/gif"),
+    (8, b"WEBP", "image/webp"),
+    (0, b"#!SILK", "audio/silk"),
+    // Tencent's SILK starts with an extra byte.
+    (1, b"#!SILK", "audio/silk"),
+    (0, b"#!AMR", "audio/amr"),
+    (0, b"ID3", "audio/mpeg"),
+    (0, b"\xff\xfb", "audio/mpeg"),
+    (0, b"OggS", "audio/ogg"),
+    (0, b"fLaC", "audio/flac"),
+    (8, b"WAVE", "audio/wav"),
+    (4, b"ftyp", "video/mp4"),
+    (0, b"%PDF-", "application/pdf"),
+];
+
+/// The MIME type of `bytes` by their magic number, `None` for formats it doesn't know.
+pub fn sniff_mime(bytes: &[u8]) -> Option<Mime> {
+    SIGNATURES
+        .iter()
+        .find(|(at, signature, _)| bytes.get(*at..at + signature.len()) == Some(signature))
+        .and_then(|(_, _, mime)| mime.parse().ok())
+}
+
+/// The directory under [`FetchConfig::cache_dir`] the cache keeps its files in.
+const CACHE_DIR: &str = "fetched";
+
+/// Whether `name` is one the cache gives its files, the hex of a sha1.
+fn is_cache_name(name: &str) -> bool {
+    name.len() == 40
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+}
+
+/// Whether `name` is made of a content hash, as QQ names received media, `<md5>.jpg` and the
+/// like, so that it alone identifies the file.
+fn is_hash_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default();
+    stem.len() >= 32 && stem.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+pub(crate) struct Fetcher {
+    config: FetchConfig,
+    client: reqwest::Client,
+    cache: Mutex<()>,
+}
+
+impl Fetcher {
+    pub(crate) fn new(config: FetchConfig) -> Self {
+        Fetcher {
+            config,
+            // `download` applies the timeout itself.
+            client: reqwest::Client::new(),
+            cache: Mutex::new(()),
+        }
+    }
+
+    fn check_size(&self, size: u64) -> anyhow::Result<()> {
+        if size > self.config.max_size {
+            anyhow::bail!(
+                "file is {} bytes, over the limit of {}",
+                size,
+                self.config.max_size
+            );
+        }
+        Ok(())
+    }
+
+    async fn download(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        tokio::time::timeout(self.config.timeout, self.download_body(url))
+            .await
+            .with_context(|| format!("download timed out after {:?}", self.config.timeout))?
+    }
+
+    async fn download_body(&self, url: &str) -> anyhow::Result<Vec<u8>> {
+        let mut response = self.client.get(url).send().await?.error_for_status()?;
+        if let Some(size) = response.content_length() {
+            self.check_size(size)?;
+        }
+        // Checked as it comes in, without a Content-Length the body is all there is to go by.
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+            self.check_size(bytes.len() as u64)?;
+        }
+        Ok(bytes)
+    }
+
+    async fn read(&self, path: &Path) -> anyhow::Result<Vec<u8>> {
+        self.check_size(tokio::fs::metadata(path).await?.len())?;
+        Ok(tokio::fs::read(path).await?)
+    }
+
+    fn decode(&self, base64: &str) -> anyhow::Result<Vec<u8>> {
+        let bytes = STANDARD.decode(base64.trim_start_matches("base64://"))?;
+        self.check_size(bytes.len() as u64)?;
+        Ok(bytes)
+    }
+
+    /// The content of `source`, a url, a local path or base64.
+    async fn source(&self, source: &str) -> anyhow::Result<Vec<u8>> {
+        if source.starts_with("base64://") {
+            self.decode(source)
+        } else if source.starts_with("http://") || source.starts_with("https://") {
+            self.download(source).await
+        } else {
+            let path = local_path(source).unwrap_or_else(|| PathBuf::from(source));
+            self.read(&path)
+                .await
+                .with_context(|| format!("can't read {}", path.display()))
+        }
+    }
+
+    /// The content `file` carries or points at, `None` if it does neither.
+    async fn direct(&self, file: &File) -> Option<anyhow::Result<Vec<u8>>> {
+        if let Some(base64) = &file.base64 {
+            return Some(self.decode(base64));
+        }
+        let uri = file.uri.as_ref()?;
+        Some(self.source(&uri_source(uri)).await)
+    }
+
+    fn cache_path(&self, file: &File) -> Option<PathBuf> {
+        let dir = self.config.cache_dir.as_ref()?;
+        // Decoding it again is as quick as reading it back.
+        if file.base64.is_some() {
+            return None;
+        }
+        // Names of received media made of their hash outlive their urls, QQ's expire. Any other
+        // name only tells files apart together with the url.
+        let key = match (&file.id, &file.uri) {
+            (Some(id), _) if !id.is_empty() => format!("id:{}", id),
+            _ if is_hash_name(&file.name) => format!("name:{}", file.name),
+            (_, Some(uri)) => format!("uri:{}\n{}", uri, file.name),
+            _ => return None,
+        };
+        Some(
+            dir.join(CACHE_DIR)
+                .join(hex::encode(Sha1::digest(key.as_bytes()))),
+        )
+    }
+
+    async fn cached(&self, path: &Path) -> Option<Vec<u8>> {
+        let _cache = self.cache.lock().await;
+        let bytes = tokio::fs::read(path).await.ok()?;
+        // The modification time orders entries for eviction.
+        let path = path.to_path_buf();
+        let touched = tokio::task::spawn_blocking(move || {
+            std::fs::File::options()
+                .append(true)
+                .open(path)?
+                .set_modified(SystemTime::now())
+        })
+        .await;
+        if let Ok(Err(e)) = touched {
+            tracing::debug!("Onebotv11: Failed to touch cached file: {}", e);
+        }
+        Some(bytes)
+    }
+
+    async fn store(&self, path: &Path, bytes: &[u8]) {
+        let _cache = self.cache.lock().await;
+        if let Err(e) = self.write_and_evict(path, bytes).await {
+            tracing::warn!(
+                "Onebotv11: Failed to cache fetched file {}: {}",
+                path.display(),
+                e
+            );
+        }
+    }
+
+    async fn write_and_evict(&self, path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        tokio::fs::create_dir_all(dir).await?;
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, bytes).await?;
+        tokio::fs::rename(&partial, path).await?;
+
+        // Only what the cache wrote is counted or removed.
+        let mut entries = Vec::new();
+        let mut read_dir = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            if !entry.file_name().to_str().is_some_and(is_cache_name) {
+                continue;
+            }
+            let metadata = entry.metadata().await?;
+            if metadata.is_file() {
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                entries.push((modified, metadata.len(), entry.path()));
+            }
+        }
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        // The file just written is the newest, even when the clock doesn't tell it apart.
+        for (_, len, entry) in entries {
+            if total <= self.config.cache_size {
+                break;
+            }
+            if entry == path {
+                continue;
+            }
+            tokio::fs::remove_file(&entry).await?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+/// The api that fetches `file` when its url doesn't work, chosen by its MIME type or extension.
+fn fallback_payload(file: &File) -> Option<ApiPayload> {
+    let mime = file
+        .mime
+        .clone()
+        .or_else(|| mime_guess::from_path(&file.name).first());
+    let type_ = mime.as_ref().map(Mime::type_);
+    if !file.name.is_empty() && type_ == Some(mime::IMAGE) {
+        return Some(ApiPayload::GetImage(GetImage {
+            file: file.name.clone(),
+        }));
+    }
+    // QQ's own voice formats, which `mime_guess` doesn't know.
+    let voice = Path::new(&file.name)
+        .extension()
+        .is_some_and(|extension| extension == "amr" || extension == "silk" || extension == "slk");
+    if !file.name.is_empty() && (type_ == Some(mime::AUDIO) || voice) {
+        return Some(ApiPayload::GetRecord(GetRecord {
+            file: file.name.clone(),
+            out_format: "mp3".to_string(),
+        }));
+    }
+    let file_id = match &file.id {
+        Some(id) if !id.is_empty() => id.clone(),
+        _ if !file.name.is_empty() => file.name.clone(),
+        _ => return None,
+    };
+    Some(ApiPayload::GetFile(GetFile { file_id }))
+}
+
+impl<C: OnebotConnect> OnebotV11Bot<C> {
+    /// Download `file`, as [`crate::segment::cast_segment`] makes it for received images,
+    /// records, videos and files.
+    ///
+    /// `file` is fetched from its url, or when it has none or the url fails, as QQ's do once they
+    /// have expired, through `get_image`, `get_record` or `get_file`. Records fetched that way
+    /// come as mp3. See [`FetchConfig`] for limits and caching, set with
+    /// [`OnebotV11Bot::with_fetch`].
+    pub async fn fetch_file(&self, file: &File) -> Result<FetchedFile, OnebotError> {
+        let fetcher = &self.fetcher;
+        let cache_path = fetcher.cache_path(file);
+        if let Some(path) = &cache_path {
+            if let Some(bytes) = fetcher.cached(path).await {
+                return Ok(fetched(file, bytes));
+            }
+        }
+        let bytes = match fetcher.direct(file).await {
+            Some(Ok(bytes)) => bytes,
+            direct => match fallback_payload(file) {
+                Some(payload) => {
+                    if let Some(Err(e)) = direct {
+                        tracing::debug!(
+                            "Onebotv11: Failed to fetch {}, trying {}: {:#}",
+                            file.name,
+                            payload.endpoint(),
+                            e
+                        );
+                    }
+                    self.fetch_through(payload).await?
+                }
+                None => {
+                    return Err(OnebotError::Transport {
+                        action: "fetch_file".to_string(),
+                        payload: json!({ "name": file.name }),
+                        source: match direct {
+                            Some(Err(e)) => e,
+                            _ => anyhow::anyhow!("nothing to fetch the file from"),
+                        },
+                    })
+                }
+            },
+        };
+        if let Some(path) = &cache_path {
+            fetcher.store(path, &bytes).await;
+        }
+        Ok(fetched(file, bytes))
+    }
+
+    /// Fetch through `payload`, reading whichever of base64, a url or a path the response has.
+    async fn fetch_through(&self, payload: ApiPayload) -> Result<Vec<u8>, OnebotError> {
+        let action = payload.endpoint();
+        let error_payload = payload_value(&payload);
+        let raw = self.call_api_raw(payload).await?;
+        let data = &raw["data"];
+        let field = |name: &str| data[name].as_str().filter(|value| !value.is_empty());
+        let result = if let Some(base64) = field("base64") {
+            self.fetcher.decode(base64)
+        } else if let Some(url) = field("url").filter(|url| url.starts_with("http")) {
+            self.fetcher.download(url).await
+        } else if let Some(file) = field("file") {
+            self.fetcher.source(file).await
+        } else {
+            return Err(OnebotError::UnexpectedResponse {
+                action,
+                payload: error_payload,
+                response: raw,
+            });
+        };
+        result.map_err(|source| OnebotError::Transport {
+            action,
+            payload: error_payload,
+            source,
+        })
+    }
+}
+
+fn fetched(file: &File, bytes: Vec<u8>) -> FetchedFile {
+    let mime = sniff_mime(&bytes)
+        .or_else(|| file.mime.clone())
+        .or_else(|| mime_guess::from_path(&file.name).first());
+    FetchedFile {
+        file: File {
+            base64: None,
+            mime,
+            size: Some(bytes.len() as u64),
+            ..file.clone()
+        },
+        bytes,
+    }
+}
@@ -0,0 +1,229 @@
+//! Local files and in-memory bytes as media to send.
+//!
+//! [`parse_segment`](crate::segment::parse_segment) sends a [`File`] carrying `base64` as a
+//! `base64://` source, and one whose `uri` is a local path, as [`File::try_from_path`] and
+//! [`file_from_path`] make, as a `file://` URI. By default both are sent as they are. For an
+//! implementation that can't read the bot's files, turn [`MediaConfig::shared_fs`] off and the
+//! bot reads local files and sends them as `base64://` too. Configure it with
+//! [`OnebotV11Bot::with_media`](crate::bot::api::OnebotV11Bot::with_media).
+//!
+//! For an implementation on another host, a [`MediaServer`] publishes what isn't shared over
+//! HTTP instead.
+//!
+//! Received media is downloaded with
+//! [`OnebotV11Bot::fetch_file`](crate::bot::api::OnebotV11Bot::fetch_file).
+
+mod fetch;
+mod server;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use hyper::Uri;
+use oxidebot::source::message::File;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+
+use crate::error::OnebotError;
+
+pub(crate) use fetch::Fetcher;
+pub use fetch::{sniff_mime, FetchConfig, FetchedFile};
+pub use server::{MediaServer, MediaServerConfig, Published};
+
+/// What may not appear in the path of a `file://` URI.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Config for local and in-memory media.
+///
+/// With `shared_fs`, the default, the implementation runs where it can read the bot's files and
+/// gets their paths untouched, they may not even exist where the bot runs. Otherwise the bot
+/// reads them and sends their content, or with a [`MediaServer`] publishes them there. Base64
+/// content and files the bot reads over `max_size` bytes are refused before anything is sent,
+/// 30 MiB by default, about what implementations take in one call once it is base64 encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MediaConfig {
+    pub shared_fs: bool,
+    pub max_size: u64,
+}
+
+impl Default for MediaConfig {
+    fn default() -> Self {
+        MediaConfig {
+            shared_fs: true,
+            max_size: 30 * 1024 * 1024,
+        }
+    }
+}
+
+/// A file at a local path, without touching the filesystem. Relative paths are taken from the
+/// current directory.
+pub fn file_from_path(path: impl AsRef<Path>) -> File {
+    let path = path.as_ref();
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut encoded = String::new();
+    for component in absolute.to_string_lossy().replace('\\', "/").split('/') {
+        if !component.is_empty() {
+            encoded.push('/');
+            encoded.extend(utf8_percent_encode(component, PATH));
+        }
+    }
+    File {
+        id: None,
+        name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        uri: format!("file://localhost{}", encoded).parse().ok(),
+        base64: None,
+        mime: mime_guess::from_path(path).first(),
+        size: None,
+    }
+}
+
+/// A file made of `bytes`, its MIME type guessed from `name`.
+pub fn file_from_bytes(name: impl Into<String>, bytes: impl AsRef<[u8]>) -> File {
+    let name = name.into();
+    let bytes = bytes.as_ref();
+    File {
+        id: None,
+        mime: mime_guess::from_path(&name).first(),
+        name,
+        uri: None,
+        base64: Some(STANDARD.encode(bytes)),
+        size: Some(bytes.len() as u64),
+    }
+}
+
+/// `base64` as a OneBot file source.
+pub(crate) fn base64_source(base64: String) -> String {
+    if base64.starts_with("base64://") {
+        base64
+    } else {
+        format!("base64://{}", base64)
+    }
+}
+
+/// `uri` as a OneBot file source, a local path becoming a `file://` URI.
+pub(crate) fn uri_source(uri: &Uri) -> String {
+    match uri.scheme_str() {
+        Some("file") => format!("file://{}", uri.path()),
+        None if uri.authority().is_none() && uri.path().starts_with('/') => {
+            format!("file://{}", uri.path())
+        }
+        _ => uri.to_string(),
+    }
+}
+
+/// The local path of a `file://` source.
+fn local_path(source: &str) -> Option<PathBuf> {
+    let path = source.strip_prefix("file://")?;
+    let path = path.strip_prefix("localhost").unwrap_or(path);
+    let path = percent_decode_str(path).decode_utf8().ok()?;
+    // `file:///C:/...` on Windows.
+    let path = match path.as_bytes() {
+        [b'/', _, b':', ..] => &path[1..],
+        _ => &path,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// Check the local and base64 sources of `segments` against `config`, unless the filesystem is
+/// shared publishing them on `server` if there is one, or else reading local files into
+/// base64. What was published stays so until the returned entries drop.
+pub(crate) async fn prepare(
+    segments: &mut [onebot_v11::MessageSegment],
+    config: &MediaConfig,
+    server: Option<&Arc<MediaServer>>,
+    action: &str,
+) -> Result<Vec<Published>, OnebotError> {
+    let mut pending = Vec::new();
+    sources(segments, &mut pending);
+    let mut published = Vec::new();
+    for source in pending {
+        if let Some(entry) = prepare_source(source, config, server, action).await? {
+            *source = entry.url().to_string();
+            published.push(entry);
+        }
+    }
+    Ok(published)
+}
+
+fn sources<'a>(segments: &'a mut [onebot_v11::MessageSegment], found: &mut Vec<&'a mut String>) {
+    use onebot_v11::MessageSegment as S;
+    for segment in segments {
+        match segment {
+            S::Image { data } => found.push(&mut data.file),
+            S::Record { data } => found.push(&mut data.file),
+            S::Video { data } => found.push(&mut data.file),
+            S::File { data } => found.push(&mut data.file),
+            S::CustomNode { data } => sources(&mut data.content, found),
+            _ => {}
+        }
+    }
+}
+
+async fn prepare_source(
+    source: &mut String,
+    config: &MediaConfig,
+    server: Option<&Arc<MediaServer>>,
+    action: &str,
+) -> Result<Option<Published>, OnebotError> {
+    let too_large = |what: &str, size: u64| {
+        OnebotError::invalid_argument(
+            action,
+            format!(
+                "{} is {} bytes, over the limit of {}",
+                what, size, config.max_size
+            ),
+        )
+    };
+    if let Some(encoded) = source.strip_prefix("base64://") {
+        let size = encoded.trim_end_matches('=').len() as u64 * 3 / 4;
+        if size > config.max_size {
+            return Err(too_large("base64 content", size));
+        }
+        let Some(server) = server else {
+            return Ok(None);
+        };
+        let bytes = STANDARD.decode(encoded).map_err(|e| {
+            OnebotError::invalid_argument(action, format!("invalid base64 content: {}", e))
+        })?;
+        return Ok(Some(server.publish_bytes("", bytes)));
+    }
+    if config.shared_fs {
+        return Ok(None);
+    }
+    let Some(path) = local_path(source) else {
+        return Ok(None);
+    };
+    let unreadable = |e: std::io::Error| {
+        OnebotError::invalid_argument(action, format!("can't read {}: {}", path.display(), e))
+    };
+    let size = tokio::fs::metadata(&path).await.map_err(unreadable)?.len();
+    if size > config.max_size {
+        return Err(too_large(&path.display().to_string(), size));
+    }
+    if let Some(server) = server {
+        return Ok(Some(server.publish_path(path)));
+    }
+    let bytes = tokio::fs::read(&path).await.map_err(unreadable)?;
+    *source = format!("base64://{}", STANDARD.encode(bytes));
+    Ok(None)
+}
@@ -0,0 +1,294 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::stream;
+use http_body_util::{combinators::BoxBody, BodyExt, Full, StreamBody};
+use hyper::{
+    body::{Bytes, Frame, Incoming},
+    server::conn::http1,
+    service::service_fn,
+    Method, Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use percent_encoding::utf8_percent_encode;
+use tokio::{io::AsyncReadExt, net::TcpListener, time::Instant};
+
+use crate::error::ConnectError;
+
+use super::PATH;
+
+/// How much of a published file is read at a time while it's served.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+type Body = BoxBody<Bytes, std::io::Error>;
+
+/// Config for [`MediaServer`].
+///
+/// The server listens on `host`/`port`, and the implementation reaches it at `public_url`, or at
+/// the address it listens on when `public_url` is unset, so a proxy in front of it must strip
+/// any path of its own. Entries live for `ttl` at most.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MediaServerConfig {
+    pub host: String,
+    pub port: u16,
+    pub public_url: Option<String>,
+    pub ttl: Duration,
+}
+
+impl Default for MediaServerConfig {
+    fn default() -> Self {
+        MediaServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 8083,
+            public_url: None,
+            ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Content {
+    Path(PathBuf),
+    Bytes(Bytes),
+}
+
+#[derive(Debug)]
+struct Entry {
+    content: Content,
+    mime: String,
+    expires: Instant,
+}
+
+/// An embedded HTTP server handing outgoing media to the implementation by URL.
+///
+/// Sent as base64, a large video makes a slow message frame, often over the implementation's
+/// frame limit, and an implementation on another host can't read local paths. With a server set
+/// by [`OnebotV11Bot::with_media_server`](crate::bot::api::OnebotV11Bot::with_media_server),
+/// base64 content, and local files once [`MediaConfig::shared_fs`](super::MediaConfig) is off,
+/// are published here under unguessable URLs, which are sent instead. An entry is removed once
+/// the message it was published for is sent, or after [`MediaServerConfig::ttl`] at the latest.
+/// [`MediaConfig::max_size`](super::MediaConfig) still applies.
+#[derive(Debug)]
+pub struct MediaServer {
+    ttl: Duration,
+    base_url: String,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl MediaServer {
+    /// Fails if the server can't bind its address.
+    pub async fn new(config: MediaServerConfig) -> Result<Arc<Self>, ConnectError> {
+        let addr = format!("{}:{}", config.host, config.port);
+        let listener = TcpListener::bind(&addr)
+            .await
+            .map_err(|source| ConnectError::Bind {
+                addr: addr.clone(),
+                source,
+            })?;
+        let base_url = match config.public_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => {
+                let local = listener
+                    .local_addr()
+                    .map_err(|source| ConnectError::Bind { addr, source })?;
+                format!("http://{}", local)
+            }
+        };
+        let self_ = Arc::new(Self {
+            ttl: config.ttl,
+            base_url,
+            entries: Mutex::new(HashMap::new()),
+        });
+        self_.clone().start(listener);
+        Ok(self_)
+    }
+
+    /// Where the implementation reaches this server.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// How many entries are published and not expired yet.
+    pub fn len(&self) -> usize {
+        let now = Instant::now();
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.values().filter(|entry| entry.expires > now).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Publish the file at `path`, read when it is requested, until the returned entry drops.
+    pub fn publish_path(self: &Arc<Self>, path: impl Into<PathBuf>) -> Published {
+        let path = path.into();
+        let mime = mime_guess::from_path(&path).first_or_octet_stream();
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.publish(Content::Path(path), mime.to_string(), &name)
+    }
+
+    /// Publish `bytes` until the returned entry drops, its MIME type guessed from `name`.
+    pub fn publish_bytes(self: &Arc<Self>, name: &str, bytes: impl Into<Bytes>) -> Published {
+        let mime = mime_guess::from_path(name).first_or_octet_stream();
+        self.publish(Content::Bytes(bytes.into()), mime.to_string(), name)
+    }
+
+    fn publish(self: &Arc<Self>, content: Content, mime: String, name: &str) -> Published {
+        let token = hex::encode(rand::random::<[u8; 16]>());
+        let url = if name.is_empty() {
+            format!("{}/{}", self.base_url, token)
+        } else {
+            // The name only helps implementations that look at the extension.
+            format!(
+                "{}/{}/{}",
+                self.base_url,
+                token,
+                utf8_percent_encode(name, PATH)
+            )
+        };
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|_, entry| entry.expires > now);
+        entries.insert(
+            token.clone(),
+            Entry {
+                content,
+                mime,
+                expires: now + self.ttl,
+            },
+        );
+        Published {
+            server: self.clone(),
+            token,
+            url,
+        }
+    }
+
+    fn start(self: Arc<Self>, listener: TcpListener) {
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::warn!("Onebotv11: Failed to accept media connection: {}", e);
+                        continue;
+                    }
+                };
+                let self_ = self.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| self_.clone().handle(req));
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        tracing::warn!("Onebotv11: Media connection error: {}", e);
+                    }
+                });
+            }
+        });
+    }
+
+    async fn handle(self: Arc<Self>, req: Request<Incoming>) -> Result<Response<Body>, Infallible> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return Ok(status_response(StatusCode::METHOD_NOT_ALLOWED));
+        }
+        let token = req.uri().path().trim_start_matches('/');
+        let token = token.split('/').next().unwrap_or_default();
+        let found = {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            match entries.get(token) {
+                Some(entry) if entry.expires <= Instant::now() => {
+                    entries.remove(token);
+                    None
+                }
+                Some(entry) => Some((entry.content.clone(), entry.mime.clone())),
+                None => None,
+            }
+        };
+        let Some((content, mime)) = found else {
+            return Ok(status_response(StatusCode::NOT_FOUND));
+        };
+        let (body, len) = match content {
+            Content::Bytes(bytes) => {
+                let len = bytes.len() as u64;
+                (full(bytes), len)
+            }
+            Content::Path(path) => match open(&path).await {
+                Ok(opened) => opened,
+                Err(e) => {
+                    tracing::warn!(
+                        "Onebotv11: Failed to read published file {}: {}",
+                        path.display(),
+                        e
+                    );
+                    return Ok(status_response(StatusCode::NOT_FOUND));
+                }
+            },
+        };
+        let mut resp = Response::new(body);
+        resp.headers_mut()
+            .insert(hyper::header::CONTENT_LENGTH, len.into());
+        if let Ok(mime) = hyper::header::HeaderValue::from_str(&mime) {
+            resp.headers_mut().insert(hyper::header::CONTENT_TYPE, mime);
+        }
+        Ok(resp)
+    }
+}
+
+/// A published entry, removed from the server when dropped.
+#[derive(Debug)]
+pub struct Published {
+    server: Arc<MediaServer>,
+    token: String,
+    url: String,
+}
+
+impl Published {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+}
+
+impl Drop for Published {
+    fn drop(&mut self) {
+        self.server
+            .entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&self.token);
+    }
+}
+
+/// The file at `path` as a body read a chunk at a time, and its length.
+async fn open(path: &Path) -> std::io::Result<(Body, u64)> {
+    let file = tokio::fs::File::open(path).await?;
+    let len = file.metadata().await?.len();
+    let chunks = stream::try_unfold(file, |mut file| async move {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let read = file.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(None);
+        }
+        chunk.truncate(read);
+        Ok(Some((Frame::data(Bytes::from(chunk)), file)))
+    });
+    Ok((StreamBody::new(chunks).boxed(), len))
+}
+
+fn full(bytes: Bytes) -> Body {
+    Full::new(bytes).map_err(|never| match never {}).boxed()
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(full(Bytes::new()));
+    *resp.status_mut() = status;
+    resp
+}
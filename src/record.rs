@@ -0,0 +1,212 @@
+//! Recording a bot's traffic to reproduce it later.
+//!
+//! A recording is a JSONL file, one [`Record`] per line: every event the bot received and
+//! every api call it made, both as the implementation sent them. [`OnebotV11Bot::with_recorder`]
+//! writes one, [`OnebotV11ReplayBot`](crate::OnebotV11ReplayBot) plays it back through the
+//! same handlers.
+//!
+//! [`OnebotV11Bot::with_recorder`]: crate::bot::api::OnebotV11Bot::with_recorder
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, LineWriter, Write},
+    path::Path,
+    sync::{mpsc, Arc, RwLock},
+    thread::JoinHandle,
+};
+
+use onebot_v11::api::payload::ApiPayload;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use crate::error::{payload_value, OnebotError};
+
+/// One line of a recording.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Record {
+    /// An event the bot received, as the transport got it, even if onebot_v11 can't parse it.
+    Event {
+        /// Unix time in milliseconds.
+        recorded_at: i64,
+        event: Value,
+    },
+    /// An api call, with the raw response or why there was none.
+    Call {
+        /// Unix time in milliseconds, when the call was made.
+        recorded_at: i64,
+        action: String,
+        params: Value,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        response: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+impl Record {
+    pub fn recorded_at(&self) -> i64 {
+        match self {
+            Record::Event { recorded_at, .. } | Record::Call { recorded_at, .. } => *recorded_at,
+        }
+    }
+}
+
+/// Read a recording, failing on the first line that isn't a [`Record`].
+pub fn read_records(path: impl AsRef<Path>) -> io::Result<Vec<Record>> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut records = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", index + 1, e),
+            )
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+enum Command {
+    Line(String),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes a recording, each record is flushed as soon as it's written.
+///
+/// Records are written by a thread of their own, so the bot never waits for the file.
+/// Failing to write is logged and never fails the bot. Dropping the recorder waits for the
+/// records still queued.
+pub struct Recorder {
+    sender: Option<mpsc::Sender<Command>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl Recorder {
+    /// Record to `path`, replacing what was there.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_writer(File::create(path)?))
+    }
+
+    /// Record to the end of `path`, creating it if needed.
+    pub fn append(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_writer(
+            OpenOptions::new().create(true).append(true).open(path)?,
+        ))
+    }
+
+    pub fn from_writer(writer: impl Write + Send + 'static) -> Self {
+        let (sender, commands) = mpsc::channel();
+        let mut writer = LineWriter::new(writer);
+        let writer = std::thread::spawn(move || {
+            for command in commands {
+                match command {
+                    Command::Line(line) => {
+                        if let Err(e) = writer.write_all(line.as_bytes()) {
+                            tracing::warn!("Onebotv11: Failed to write record: {}", e);
+                        }
+                    }
+                    Command::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        Recorder {
+            sender: Some(sender),
+            writer: Some(writer),
+        }
+    }
+
+    pub fn write(&self, record: &Record) {
+        let mut line = match serde_json::to_string(record) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!("Onebotv11: Failed to serialize record: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+        self.send(Command::Line(line));
+    }
+
+    /// Wait until the records written so far are in the file.
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        self.send(Command::Flush(done));
+        let _ = written.await;
+    }
+
+    fn send(&self, command: Command) {
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(command).is_ok());
+        if !sent {
+            tracing::warn!("Onebotv11: Failed to write record: the writer is gone");
+        }
+    }
+
+    pub(crate) fn event(&self, event: &Value) {
+        self.write(&Record::Event {
+            recorded_at: now(),
+            event: event.clone(),
+        });
+    }
+
+    pub(crate) fn call(
+        &self,
+        recorded_at: i64,
+        payload: &ApiPayload,
+        result: &Result<Value, OnebotError>,
+    ) {
+        let (response, error) = match result {
+            Ok(response) => (Some(response.clone()), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        self.write(&Record::Call {
+            recorded_at,
+            action: onebot_v11::traits::EndPoint::endpoint(payload),
+            params: payload_value(payload),
+            response,
+            error,
+        });
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // Closing the channel ends the writer once it's through the queue.
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// Where a connection hands the raw events it receives, once a bot with a [`Recorder`] is
+/// built on it.
+#[derive(Default)]
+pub(crate) struct EventTap(RwLock<Option<Arc<Recorder>>>);
+
+impl EventTap {
+    pub(crate) fn set(&self, recorder: Arc<Recorder>) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Some(recorder);
+    }
+
+    pub(crate) fn record(&self, event: &Value) {
+        if let Some(recorder) = &*self.0.read().unwrap_or_else(|e| e.into_inner()) {
+            recorder.event(event);
+        }
+    }
+}
+
+pub(crate) fn now() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
@@ -0,0 +1,180 @@
+//! Opt-in resolution of the message a received message replies to.
+//!
+//! A reply only carries the quoted message's id. With a resolver, the bot starts fetching the
+//! quoted message as soon as a reply arrives and hands the event to handlers without waiting,
+//! [`quoted_message`] awaits the fetch. Quoted messages are cached, taken from the
+//! [`MessageStore`] when the bot has one and otherwise asked for with `get_message_detail`,
+//! which answers from the history first. Enable it with
+//! [`OnebotV11Bot::with_reply_resolver`](crate::bot::api::OnebotV11Bot::with_reply_resolver).
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::{
+    future::{BoxFuture, Shared},
+    FutureExt as _,
+};
+use oxidebot::{bot::BotObject, event::MessageEvent, matcher::Matcher};
+
+use crate::{
+    event::{event_self_id, EventWrapper},
+    store::{fetch_message, MessageStore},
+    ttl::TtlMap,
+};
+
+/// Config for [`ReplyResolver`].
+///
+/// At most `capacity` quoted messages are cached, none longer than `ttl`. A fetch taking longer
+/// than `timeout` resolves to `None` and is tried again by the next reply to the same message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ReplyResolverConfig {
+    pub capacity: usize,
+    pub ttl: Duration,
+    pub timeout: Duration,
+}
+
+impl Default for ReplyResolverConfig {
+    fn default() -> Self {
+        ReplyResolverConfig {
+            capacity: 1000,
+            ttl: Duration::from_secs(3600),
+            timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// The message a reply quotes, resolving in the background.
+///
+/// Clones share the same fetch.
+#[derive(Clone)]
+pub struct QuotedMessage {
+    message_id: String,
+    message: Shared<BoxFuture<'static, Option<MessageEvent>>>,
+}
+
+impl QuotedMessage {
+    pub fn message_id(&self) -> &str {
+        &self.message_id
+    }
+
+    /// Wait for the quoted message, `None` if it couldn't be fetched in time.
+    pub async fn get(&self) -> Option<MessageEvent> {
+        self.message.clone().await
+    }
+
+    /// The quoted message if it is already resolved.
+    pub fn try_get(&self) -> Option<MessageEvent> {
+        self.message.peek().cloned().flatten()
+    }
+}
+
+/// The message the event of `matcher` replies to, awaiting its resolution.
+///
+/// `None` for events that aren't replies, or that weren't received by a bot with a
+/// [`ReplyResolver`], or whose quoted message couldn't be fetched.
+pub async fn quoted_message(matcher: &Matcher) -> Option<MessageEvent> {
+    let wrapper = matcher
+        .event_object
+        .as_any()
+        .downcast_ref::<EventWrapper>()?;
+    wrapper.1.quoted.as_ref()?.get().await
+}
+
+type Resolutions = TtlMap<(String, String), QuotedMessage>;
+
+/// Quoted messages by the account that received the reply and message id, resolved or still
+/// resolving.
+pub struct ReplyResolver {
+    pub config: ReplyResolverConfig,
+    resolutions: Mutex<Resolutions>,
+}
+
+impl ReplyResolver {
+    pub fn new(config: ReplyResolverConfig) -> Self {
+        ReplyResolver {
+            config,
+            resolutions: Mutex::new(TtlMap::new(config.capacity, config.ttl)),
+        }
+    }
+
+    /// The cached quoted message, if it is resolved.
+    pub fn get(&self, self_id: &str, message_id: &str) -> Option<MessageEvent> {
+        self.lock()
+            .get(&(self_id.to_string(), message_id.to_string()))?
+            .try_get()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Start resolving the message `event` replies to, `None` if it isn't a reply.
+    pub(crate) fn resolve(
+        self: &Arc<Self>,
+        event: &onebot_v11::Event,
+        store: Option<&MessageStore>,
+        bot: &BotObject,
+    ) -> Option<QuotedMessage> {
+        let (message_id, group_id) = replied_message(event)?;
+        let key = (
+            event_self_id(event).unwrap_or_default().to_string(),
+            message_id.clone(),
+        );
+        let mut resolutions = self.lock();
+        if let Some(quoted) = resolutions.get(&key) {
+            return Some(quoted.clone());
+        }
+        let message = match store.and_then(|store| store.get(&key.0, &message_id)) {
+            Some(message) => futures_util::future::ready(Some(message)).boxed(),
+            None => {
+                let resolver = Arc::clone(self);
+                let bot = bot.clone_box();
+                let key = key.clone();
+                async move {
+                    let message =
+                        fetch_message(bot, message_id, group_id, resolver.config.timeout).await;
+                    if message.is_none() {
+                        // So the next reply to it tries again.
+                        resolver.lock().remove(&key);
+                    }
+                    message
+                }
+                .boxed()
+            }
+        };
+        let quoted = QuotedMessage {
+            message_id: key.1.clone(),
+            message: message.shared(),
+        };
+        resolutions.insert(key, quoted.clone());
+        // Polled by a task of its own, so the fetch runs even if no handler awaits it.
+        tokio::spawn(quoted.message.clone());
+        Some(quoted)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Resolutions> {
+        self.resolutions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// The message a received message replies to, and the group it was sent in.
+fn replied_message(event: &onebot_v11::Event) -> Option<(String, Option<String>)> {
+    use onebot_v11::event::message::Message;
+    let onebot_v11::Event::Message(event) = event else {
+        return None;
+    };
+    let (segments, group_id) = match event {
+        Message::PrivateMessage(event) => (&event.message, None),
+        Message::GroupMessage(event) => (&event.message, Some(event.group_id.to_string())),
+    };
+    segments.iter().find_map(|segment| match segment {
+        onebot_v11::MessageSegment::Reply { data } => Some((data.id.clone(), group_id.clone())),
+        _ => None,
+    })
+}
@@ -1,3 +1,10 @@
+//! Conversion between OneBot v11 and oxidebot message segments.
+//!
+//! [`cast_segment`] and [`parse_segment`] are inverses wherever the two models agree.
+//! A segment comes back unchanged from a round trip unless [`LOSSY_PATHS`] lists its type,
+//! and then only the listed fields change. `tests/segment.rs` checks the table against
+//! generated segments in both directions.
+
 use std::str::FromStr;
 
 use onebot_v11::message::segment::{AtData, JsonData, RecordData, VideoData, XmlData};
@@ -6,6 +13,8 @@ use oxidebot::source::{
     user::{User, UserProfile},
 };
 
+use crate::media::{base64_source, uri_source};
+
 pub(crate) fn parse_uri(uri: &str) -> Option<hyper::Uri> {
     if let Ok(uri) = hyper::Uri::from_str(uri) {
         Some(uri)
@@ -15,7 +24,110 @@ pub(crate) fn parse_uri(uri: &str) -> Option<hyper::Uri> {
     }
 }
 
-pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegment {
+/// The direction of a round trip between the two segment models.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoundTrip {
+    /// A received OneBot segment sent back as it is, `parse_segment(cast_segment(segment))`.
+    Received,
+    /// An oxidebot segment as the implementation would report it back,
+    /// `cast_segment(parse_segment(segment))`.
+    Sent,
+}
+
+/// A field a [`RoundTrip`] doesn't preserve.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LossyPath {
+    pub round_trip: RoundTrip,
+    /// The segment the trip starts with, the OneBot `type` for [`RoundTrip::Received`],
+    /// the [`MessageSegment`] variant for [`RoundTrip::Sent`].
+    pub segment: &'static str,
+    /// The field that may change, `None` when the segment comes back as another type.
+    pub field: Option<&'static str>,
+    pub note: &'static str,
+}
+
+const fn lossy(
+    round_trip: RoundTrip,
+    segment: &'static str,
+    field: Option<&'static str>,
+    note: &'static str,
+) -> LossyPath {
+    LossyPath {
+        round_trip,
+        segment,
+        field,
+        note,
+    }
+}
+
+use RoundTrip::{Received, Sent};
+
+/// Everything a round trip loses, segments and fields not listed here survive unchanged.
+#[rustfmt::skip]
+pub const LOSSY_PATHS: &[LossyPath] = &[
+    lossy(Received, "mface", None, "sent back as an image of its url"),
+    lossy(Received, "image", Some("file"), "replaced by the url, empty without one"),
+    lossy(Received, "image", Some("type"), "dropped"),
+    lossy(Received, "image", Some("summary"), "replaced by the received file name"),
+    lossy(Received, "image", Some("url"), "moved to file"),
+    lossy(Received, "image", Some("cache"), "dropped"),
+    lossy(Received, "image", Some("proxy"), "dropped"),
+    lossy(Received, "image", Some("timeout"), "dropped"),
+    lossy(Received, "record", Some("file"), "replaced by the url, empty without one"),
+    lossy(Received, "record", Some("magic"), "dropped"),
+    lossy(Received, "record", Some("url"), "moved to file"),
+    lossy(Received, "record", Some("cache"), "dropped"),
+    lossy(Received, "record", Some("proxy"), "dropped"),
+    lossy(Received, "record", Some("timeout"), "dropped"),
+    lossy(Received, "video", Some("file"), "replaced by the url, empty without one"),
+    lossy(Received, "video", Some("url"), "moved to file"),
+    lossy(Received, "video", Some("cache"), "dropped"),
+    lossy(Received, "video", Some("proxy"), "dropped"),
+    lossy(Received, "video", Some("timeout"), "dropped"),
+    lossy(Received, "file", Some("file"), "replaced by the url, empty without one"),
+    lossy(Received, "file", Some("name"), "replaced by the received file name"),
+    lossy(Received, "file", Some("path"), "dropped, never sent anyway"),
+    lossy(Received, "file", Some("url"), "moved to file"),
+    lossy(Received, "file", Some("file_id"), "dropped, never sent anyway"),
+    lossy(Received, "file", Some("file_size"), "dropped, never sent anyway"),
+    lossy(Received, "rps", None, "sent back as face `Rps`"),
+    lossy(Received, "dice", None, "sent back as face `Dice`"),
+    lossy(Received, "shake", None, "sent back as empty text"),
+    lossy(Received, "poke", None, "sent back as empty text"),
+    lossy(Received, "anonymous", None, "sent back as empty text"),
+    lossy(Received, "share", Some("image"), "normalized as a uri, empty when missing or invalid"),
+    lossy(Received, "location", Some("lat"), "normalized as a float, 0 when invalid"),
+    lossy(Received, "location", Some("lon"), "normalized as a float, 0 when invalid"),
+    lossy(Received, "location", Some("title"), "empty when missing"),
+    lossy(Received, "custom_music", None, "sent back as a share"),
+    lossy(Received, "forward", None, "sent back as a node of the same id"),
+    lossy(Received, "node", Some("name"), "empty when missing"),
+    lossy(Received, "node", Some("uin"), "0 when missing"),
+    lossy(Received, "node", Some("content"), "each segment loses what it loses on its own"),
+    lossy(Sent, "At", None, "user_id `all` comes back as AtAll"),
+    lossy(Sent, "Image", Some("file"), "only base64 or the uri is sent, it comes back as the name"),
+    lossy(Sent, "Video", Some("file"), "only base64 or the uri is sent, it comes back as the name"),
+    lossy(Sent, "Video", Some("length"), "not sent"),
+    lossy(Sent, "Audio", Some("file"), "only base64 or the uri is sent, it comes back as the name"),
+    lossy(Sent, "Audio", Some("length"), "not sent"),
+    lossy(Sent, "File", Some("file"), "only base64 or the uri and the name are sent"),
+    lossy(Sent, "Reference", None, "sent as a reply"),
+    lossy(Sent, "Share", Some("image"), "only the uri is sent, it comes back named after the title"),
+    lossy(Sent, "ForwardCustomNode", Some("user"), "only a numeric id and the nickname are sent"),
+    lossy(Sent, "ForwardCustomNode", Some("message"), "the id is dropped, segments lose what they lose on their own"),
+    lossy(Sent, "CustomString", None, "types other than xml and json are sent as empty text"),
+    lossy(Sent, "CustomValue", None, "types other than valid contact and music are sent as empty text"),
+];
+
+/// Whether [`LOSSY_PATHS`] lists `field` of `segment`, with `field` `None` for a change of type.
+pub fn is_lossy(round_trip: RoundTrip, segment: &str, field: Option<&str>) -> bool {
+    LOSSY_PATHS
+        .iter()
+        .any(|path| path.round_trip == round_trip && path.segment == segment && path.field == field)
+}
+
+/// Convert a received OneBot segment.
+pub fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegment {
     match segment {
         onebot_v11::MessageSegment::Text { data } => MessageSegment::Text { content: data.text },
         onebot_v11::MessageSegment::Face { data } => MessageSegment::Emoji { id: data.id },
@@ -41,36 +153,20 @@ pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegmen
             let file = Some(File {
                 name: name.clone(),
                 uri: data.url.and_then(|url| parse_uri(&url)),
-                mime: {
-                    if let Some(mime) = mime_guess::from_path(&name).first() {
-                        Some(mime)
-                    } else {
-                        if let Some(mime) = mime_guess::from_path(name).first() {
-                            Some(mime)
-                        } else {
-                            None
-                        }
-                    }
-                },
+                mime: mime_guess::from_path(&name).first(),
                 size: None,
                 base64: None,
                 id: None,
             });
-            MessageSegment::Image { file: file }
+            MessageSegment::Image { file }
         }
         onebot_v11::MessageSegment::Record { data } => {
-            let mime = {
-                if let Some(mime) = mime_guess::from_path(&data.file).first() {
-                    Some(mime)
-                } else {
-                    None
-                }
-            };
+            let mime = mime_guess::from_path(&data.file).first();
             MessageSegment::Audio {
                 file: Some(File {
                     name: data.file,
                     uri: data.url.and_then(|u| parse_uri(&u)),
-                    mime: mime,
+                    mime,
                     size: None,
                     base64: None,
                     id: None,
@@ -80,17 +176,11 @@ pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegmen
         }
         onebot_v11::MessageSegment::Video { data } => MessageSegment::Video {
             file: {
-                let mime = {
-                    if let Some(mime) = mime_guess::from_path(&data.file).first() {
-                        Some(mime)
-                    } else {
-                        None
-                    }
-                };
+                let mime = mime_guess::from_path(&data.file).first();
                 Some(File {
                     name: data.file,
                     uri: data.url.and_then(|u| parse_uri(&u)),
-                    mime: mime,
+                    mime,
                     size: None,
                     base64: None,
                     id: None,
@@ -100,26 +190,14 @@ pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegmen
         },
         onebot_v11::MessageSegment::File { data } => MessageSegment::File {
             file: {
-                let mime = {
-                    if let Some(mime) = mime_guess::from_path(&data.file).first() {
-                        Some(mime)
-                    } else {
-                        None
-                    }
-                };
+                let mime = mime_guess::from_path(&data.file).first();
                 Some(File {
                     name: data.file,
                     uri: data.url.and_then(|u| parse_uri(&u)),
-                    mime: mime,
-                    size: {
-                        if let Ok(size) = data.file_size.parse() {
-                            Some(size)
-                        } else {
-                            None
-                        }
-                    },
+                    mime,
+                    size: data.file_size.parse().ok(),
                     base64: None,
-                    id: None,
+                    id: Some(data.file_id).filter(|id| !id.is_empty()),
                 })
             },
         },
@@ -148,15 +226,13 @@ pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegmen
             title: data.title.clone(),
             content: data.content,
             url: data.url,
-            image: data.image.and_then(|u| {
-                Some(File {
-                    name: data.title,
-                    uri: parse_uri(&u),
-                    mime: None,
-                    size: None,
-                    base64: None,
-                    id: None,
-                })
+            image: data.image.map(|u| File {
+                name: data.title,
+                uri: parse_uri(&u),
+                mime: None,
+                size: None,
+                base64: None,
+                id: None,
             }),
         },
         onebot_v11::MessageSegment::Contact { data } => MessageSegment::CustomValue {
@@ -193,15 +269,13 @@ pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegmen
             title: data.title.clone(),
             content: data.content,
             url: data.url,
-            image: data.image.and_then(|u| {
-                Some(File {
-                    name: data.title,
-                    uri: parse_uri(&u),
-                    mime: None,
-                    size: None,
-                    base64: None,
-                    id: None,
-                })
+            image: data.image.map(|u| File {
+                name: data.title,
+                uri: parse_uri(&u),
+                mime: None,
+                size: None,
+                base64: None,
+                id: None,
             }),
         },
         onebot_v11::MessageSegment::Reply { data } => MessageSegment::Reply {
@@ -217,13 +291,10 @@ pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegmen
             user: {
                 match data.uin {
                     Some(uin) => {
-                        let profile = match data.name {
-                            Some(name) => Some(UserProfile {
-                                nickname: Some(name),
-                                ..Default::default()
-                            }),
-                            None => None,
-                        };
+                        let profile = data.name.map(|name| UserProfile {
+                            nickname: Some(name),
+                            ..Default::default()
+                        });
                         Some(User {
                             id: uin.to_string(),
                             profile,
@@ -234,7 +305,7 @@ pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegmen
                 }
             },
             message: {
-                let segments = data.content.into_iter().map(|s| cast_segment(s)).collect();
+                let segments = data.content.into_iter().map(cast_segment).collect();
                 Message {
                     segments,
                     id: String::with_capacity(0),
@@ -252,7 +323,8 @@ pub(crate) fn cast_segment(segment: onebot_v11::MessageSegment) -> MessageSegmen
     }
 }
 
-pub(crate) fn parse_segment(segment: MessageSegment) -> onebot_v11::MessageSegment {
+/// Convert a segment to send.
+pub fn parse_segment(segment: MessageSegment) -> onebot_v11::MessageSegment {
     match segment {
         MessageSegment::Text { content } => onebot_v11::MessageSegment::text(content),
         MessageSegment::Image { file, .. } => {
@@ -262,10 +334,10 @@ pub(crate) fn parse_segment(segment: MessageSegment) -> onebot_v11::MessageSegme
             });
             let file_string;
             if let Some(base64) = file.base64 {
-                file_string = base64;
+                file_string = base64_source(base64);
             } else {
                 if let Some(uri) = file.uri {
-                    file_string = uri.to_string();
+                    file_string = uri_source(&uri);
                 } else {
                     tracing::error!("Onebotv11: MessageSegment::Image uri is None");
                     file_string = String::with_capacity(0);
@@ -279,10 +351,10 @@ pub(crate) fn parse_segment(segment: MessageSegment) -> onebot_v11::MessageSegme
                     if let Some(file) = file {
                         let file_string;
                         if let Some(base64) = file.base64 {
-                            file_string = base64;
+                            file_string = base64_source(base64);
                         } else {
                             if let Some(uri) = file.uri {
-                                file_string = uri.to_string();
+                                file_string = uri_source(&uri);
                             } else {
                                 tracing::error!("OnebotV11: MessageSegment::Video uri is None");
                                 file_string = String::with_capacity(0);
@@ -306,10 +378,10 @@ pub(crate) fn parse_segment(segment: MessageSegment) -> onebot_v11::MessageSegme
                     if let Some(file) = file {
                         let file_string;
                         if let Some(base64) = file.base64 {
-                            file_string = base64;
+                            file_string = base64_source(base64);
                         } else {
                             if let Some(uri) = file.uri {
-                                file_string = uri.to_string();
+                                file_string = uri_source(&uri);
                             } else {
                                 tracing::error!("OnebotV11: MessageSegment::Audio uri is None");
                                 file_string = String::with_capacity(0);
@@ -333,10 +405,10 @@ pub(crate) fn parse_segment(segment: MessageSegment) -> onebot_v11::MessageSegme
                 if let Some(file) = file {
                     let file_string;
                     if let Some(base64) = file.base64 {
-                        file_string = base64;
+                        file_string = base64_source(base64);
                     } else {
                         if let Some(uri) = file.uri {
-                            file_string = uri.to_string();
+                            file_string = uri_source(&uri);
                         } else {
                             tracing::error!("OnebotV11: MessageSegment::File uri is None");
                             file_string = String::with_capacity(0);
@@ -400,13 +472,9 @@ pub(crate) fn parse_segment(segment: MessageSegment) -> onebot_v11::MessageSegme
             onebot_v11::MessageSegment::custom_node(
                 user.id.parse().unwrap_or_default(),
                 user.profile
-                    .and_then(|p| Some(p.nickname.and_then(|n| Some(n)).unwrap_or_default()))
+                    .map(|p| p.nickname.unwrap_or_default())
                     .unwrap_or_default(),
-                message
-                    .segments
-                    .into_iter()
-                    .map(|s| parse_segment(s))
-                    .collect(),
+                message.segments.into_iter().map(parse_segment).collect(),
             )
         }
         MessageSegment::CustomString { r#type, data } => match r#type.as_str() {
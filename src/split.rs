@@ -0,0 +1,126 @@
+//! Opt-in splitting of outgoing messages.
+//!
+//! QQ rejects or cuts off very long text, and messages carrying many images fail now and then.
+//! With a [`SplitConfig`] set by
+//! [`OnebotV11Bot::with_splitter`](crate::bot::api::OnebotV11Bot::with_splitter), `send_message`
+//! sends such a message as several, one after the other, and answers with every id sent. Past
+//! [`SplitConfig::forward_after`] parts, the parts are sent as the nodes of one forward instead.
+
+use oxidebot::source::message::MessageSegment;
+
+/// Config for splitting outgoing messages.
+///
+/// A part holds at most `max_text_len` characters of text and `max_images` images. Text is cut
+/// after the last line break that fits, or else the last sentence end, or else the last
+/// whitespace, and only mid-word when a word alone is longer than `max_text_len`. A message
+/// splitting into more than `forward_after` parts is sent as one forward, see
+/// [`crate::forward`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SplitConfig {
+    pub max_text_len: usize,
+    pub max_images: usize,
+    pub forward_after: Option<usize>,
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        SplitConfig {
+            max_text_len: 3000,
+            max_images: 10,
+            forward_after: None,
+        }
+    }
+}
+
+const SENTENCE_ENDS: &[char] = &['.', '!', '?', ';', '。', '！', '？', '；', '…'];
+
+/// Split `message` into the parts `config` allows, in order. A message within the limits is
+/// returned as its only part.
+pub fn split_message(
+    message: Vec<MessageSegment>,
+    config: &SplitConfig,
+) -> Vec<Vec<MessageSegment>> {
+    let max_text_len = config.max_text_len.max(1);
+    let max_images = config.max_images.max(1);
+    let mut parts = Vec::new();
+    let mut part = Vec::new();
+    let (mut text_len, mut images) = (0, 0);
+    let mut flush = |part: &mut Vec<MessageSegment>, text_len: &mut usize, images: &mut usize| {
+        if !part.is_empty() {
+            parts.push(std::mem::take(part));
+        }
+        *text_len = 0;
+        *images = 0;
+    };
+    for segment in message {
+        match segment {
+            MessageSegment::Text { content } => {
+                let mut rest = content.as_str();
+                while !rest.is_empty() {
+                    let budget = max_text_len - text_len;
+                    let len = rest.chars().count();
+                    if len <= budget {
+                        text_len += len;
+                        part.push(MessageSegment::text(rest));
+                        break;
+                    }
+                    match cut(rest, budget) {
+                        Some(at) => {
+                            part.push(MessageSegment::text(&rest[..at]));
+                            rest = &rest[at..];
+                        }
+                        // Nothing fits behind what the part holds, start the next one.
+                        None if text_len > 0 => {}
+                        None => {
+                            let at = rest
+                                .char_indices()
+                                .nth(budget)
+                                .map_or(rest.len(), |(at, _)| at);
+                            part.push(MessageSegment::text(&rest[..at]));
+                            rest = &rest[at..];
+                        }
+                    }
+                    flush(&mut part, &mut text_len, &mut images);
+                }
+            }
+            MessageSegment::Image { .. } => {
+                if images == max_images {
+                    flush(&mut part, &mut text_len, &mut images);
+                }
+                images += 1;
+                part.push(segment);
+            }
+            segment => part.push(segment),
+        }
+    }
+    flush(&mut part, &mut text_len, &mut images);
+    if parts.is_empty() {
+        parts.push(Vec::new());
+    }
+    parts
+}
+
+/// Where to cut `text` so that at most `budget` characters come before the cut, `None` if no
+/// boundary is within them.
+fn cut(text: &str, budget: usize) -> Option<usize> {
+    let end = text
+        .char_indices()
+        .nth(budget)
+        .map_or(text.len(), |(at, _)| at);
+    let head = &text[..end];
+    let after = |(at, c): (usize, char)| at + c.len_utf8();
+    head.rfind('\n')
+        .map(|at| at + 1)
+        .or_else(|| {
+            head.char_indices()
+                .rev()
+                .find(|(_, c)| SENTENCE_ENDS.contains(c))
+                .map(after)
+        })
+        .or_else(|| {
+            head.char_indices()
+                .rev()
+                .find(|(_, c)| c.is_whitespace())
+                .map(after)
+        })
+}
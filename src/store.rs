@@ -0,0 +1,203 @@
+//! An opt-in store of recently received messages.
+//!
+//! Recall and essence notices only carry a message id. With a store, the bot keeps the message
+//! events it receives and hands those notices to handlers with the message's content and
+//! sender, asking the implementation with `get_msg` for messages it doesn't hold. Enable it
+//! with [`OnebotV11Bot::with_message_store`](crate::bot::api::OnebotV11Bot::with_message_store).
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::{future::BoxFuture, FutureExt};
+use oxidebot::{
+    bot::BotObject,
+    event::{Event, MessageEvent, NoticeEvent},
+    source::{group::Group, message::Message, user::User},
+};
+
+use crate::{
+    event::{event_self_id, merge_profile},
+    ttl::TtlMap,
+};
+
+/// How long a notice waits for `get_msg` before reaching handlers without the content.
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Config for [`MessageStore`].
+///
+/// At most `capacity` messages are kept, none longer than `max_age`. With `fetch_missing`
+/// on, a notice about a message not in the store waits up to 5 seconds for `get_msg`, so it
+/// may reach handlers after events received behind it. With it off, such notices keep their
+/// empty content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MessageStoreConfig {
+    pub capacity: usize,
+    pub max_age: Duration,
+    pub fetch_missing: bool,
+}
+
+impl Default for MessageStoreConfig {
+    fn default() -> Self {
+        MessageStoreConfig {
+            capacity: 1000,
+            max_age: Duration::from_secs(3600),
+            fetch_missing: true,
+        }
+    }
+}
+
+/// Where the message a notice is about comes from.
+pub(crate) enum Lookup {
+    Stored(Box<MessageEvent>),
+    /// A `get_msg` call, the notice waits for it without holding up the events behind it.
+    Fetching(BoxFuture<'static, Option<MessageEvent>>),
+}
+
+/// Recently received message events by the account that received them and message id, which
+/// is only unique per account.
+pub struct MessageStore {
+    pub config: MessageStoreConfig,
+    messages: Mutex<TtlMap<(String, String), MessageEvent>>,
+}
+
+impl MessageStore {
+    pub fn new(config: MessageStoreConfig) -> Self {
+        MessageStore {
+            config,
+            messages: Mutex::new(TtlMap::new(config.capacity, config.max_age)),
+        }
+    }
+
+    pub fn get(&self, self_id: &str, message_id: &str) -> Option<MessageEvent> {
+        self.lock()
+            .get(&(self_id.to_string(), message_id.to_string()))
+            .cloned()
+    }
+
+    pub fn insert(&self, self_id: &str, message: MessageEvent) {
+        self.lock()
+            .insert((self_id.to_string(), message.id.clone()), message);
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The message a recall or essence notice is about, `None` if it isn't one or the message
+    /// is missing and not fetched.
+    pub(crate) fn lookup(
+        self: &Arc<Self>,
+        event: &onebot_v11::Event,
+        bot: &BotObject,
+    ) -> Option<Lookup> {
+        let (message_id, group_id) = referenced_message(event)?;
+        let self_id = event_self_id(event).unwrap_or_default().to_string();
+        if let Some(message) = self.get(&self_id, &message_id) {
+            return Some(Lookup::Stored(Box::new(message)));
+        }
+        if !self.config.fetch_missing {
+            return None;
+        }
+        let store = Arc::clone(self);
+        let bot = bot.clone_box();
+        Some(Lookup::Fetching(
+            async move {
+                let message = fetch_message(bot, message_id, group_id, FETCH_TIMEOUT).await?;
+                store.insert(&self_id, message.clone());
+                Some(message)
+            }
+            .boxed(),
+        ))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TtlMap<(String, String), MessageEvent>> {
+        self.messages.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Ask `bot` for message `message_id` sent in `group_id`, `None` if `get_message_detail` fails
+/// or takes longer than `timeout`.
+pub(crate) async fn fetch_message(
+    bot: BotObject,
+    message_id: String,
+    group_id: Option<String>,
+    timeout: Duration,
+) -> Option<MessageEvent> {
+    let detail =
+        match tokio::time::timeout(timeout, bot.get_message_detail(message_id.clone())).await {
+            Ok(Ok(detail)) => detail,
+            Ok(Err(e)) => {
+                tracing::warn!("Onebotv11: Failed to fetch message {}: {}", message_id, e);
+                return None;
+            }
+            Err(_) => {
+                tracing::warn!("Onebotv11: Fetching message {} timed out", message_id);
+                return None;
+            }
+        };
+    Some(MessageEvent {
+        id: message_id.clone(),
+        time: detail.time,
+        sender: detail.sender.unwrap_or_default(),
+        group: group_id.map(|id| Group { id, profile: None }),
+        message: Message {
+            id: message_id,
+            segments: detail.message,
+        },
+    })
+}
+
+/// The message a recall or essence notice is about, and the group it was sent in.
+fn referenced_message(event: &onebot_v11::Event) -> Option<(String, Option<String>)> {
+    use onebot_v11::event::notice::Notice;
+    let onebot_v11::Event::Notice(notice) = event else {
+        return None;
+    };
+    match notice {
+        Notice::GroupMessageRecall(event) => Some((
+            event.message_id.to_string(),
+            Some(event.group_id.to_string()),
+        )),
+        Notice::FriendMessageRecall(event) => Some((event.message_id.to_string(), None)),
+        Notice::GroupEssenceMessageChange(event) => Some((
+            event.message_id.to_string(),
+            Some(event.group_id.to_string()),
+        )),
+        _ => None,
+    }
+}
+
+/// Fill the content and sender of the message a recall or essence notice is about.
+pub(crate) fn fill(event: &mut Event, message: &MessageEvent) {
+    let fill_user = |user: &mut User| {
+        if user.id == message.sender.id {
+            merge_profile(&mut user.profile, message.sender.profile.clone());
+            if user.group_info.is_none() {
+                user.group_info = message.sender.group_info.clone();
+            }
+        }
+    };
+    match event {
+        Event::NoticeEvent(NoticeEvent::MessageDeletedEvent(event)) => {
+            if let Some(deleted) = &mut event.message {
+                if deleted.segments.is_empty() {
+                    deleted.segments = message.message.segments.clone();
+                }
+            }
+            event.user.iter_mut().for_each(fill_user);
+        }
+        Event::NoticeEvent(NoticeEvent::GroupHightLightChangeEvent(event)) => {
+            if event.message.segments.is_empty() {
+                event.message.segments = message.message.segments.clone();
+            }
+            event.sender.iter_mut().for_each(fill_user);
+        }
+        _ => {}
+    }
+}
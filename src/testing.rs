@@ -0,0 +1,496 @@
+//! A scripted, in-process OneBot v11 implementation for offline tests.
+//!
+//! [`MockOnebot`] stands in for NapCat and friends: [`OnebotV11WsBot`](crate::OnebotV11WsBot)
+//! dials it with [`MockOnebot::serve_ws`], and it dials
+//! [`OnebotV11ReverseWsBot`](crate::OnebotV11ReverseWsBot) with
+//! [`MockOnebot::connect_reverse`]. [`MockOnebot::serve_http`] answers the HTTP API of
+//! [`OnebotV11HttpBot`](crate::OnebotV11HttpBot), whose webhook gets events through
+//! [`MockOnebot::post_event`]. Every api call is recorded and answered with the next canned
+//! response for its action, or an empty `ok` when none is queued, and
+//! [`MockOnebot::push_event`] sends event JSON to every connected bot. With
+//! [`MockOnebot::with_access_token`] it refuses websocket connections that lack the token.
+//! [`start_bot`] runs a bot's event loop, and [`MockOnebot::deliver`] pushes an event and
+//! waits for the handlers to get it.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::{SinkExt as _, StreamExt as _};
+use hmac::{Hmac, Mac as _};
+use http_body_util::{BodyExt as _, Full};
+use hyper::{
+    body::{Bytes, Incoming},
+    server::conn::http1,
+    service::service_fn,
+    Request, Response, StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use onebot_v11::{
+    api::{
+        payload::ApiPayload,
+        resp::{ApiResp, ApiRespData},
+    },
+    connect::{http::HttpConfig, ws::WsConfig, ws_reverse::ReverseWsConfig, WsApiPayload, WsType},
+    traits::EndPoint as _,
+};
+use oxidebot::{matcher::Matcher, BotTrait};
+use serde_json::{json, Value};
+use sha1::Sha1;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+    sync::{broadcast, watch},
+};
+use tokio_tungstenite::{
+    accept_hdr_async, connect_async,
+    tungstenite::{
+        self, client::IntoClientRequest as _, handshake::server::ErrorResponse,
+        http::header::AUTHORIZATION, Message,
+    },
+    WebSocketStream,
+};
+
+use crate::bot::ws_reverse::carries_token;
+
+/// An api call received by [`MockOnebot`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedCall {
+    pub action: String,
+    pub params: Value,
+}
+
+impl RecordedCall {
+    /// Whether this is the call `payload` makes.
+    pub fn is(&self, payload: &ApiPayload) -> bool {
+        *self == RecordedCall::from(payload)
+    }
+}
+
+impl From<&ApiPayload> for RecordedCall {
+    fn from(payload: &ApiPayload) -> Self {
+        RecordedCall {
+            action: payload.endpoint(),
+            params: serde_json::to_value(payload).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Outgoing {
+    /// A frame for every connection, or only those made as the account `to`.
+    Text {
+        text: String,
+        to: Option<i64>,
+    },
+    Close,
+}
+
+#[derive(Debug, Clone)]
+enum Canned {
+    Json(Value),
+    /// An HTTP status without a body, a failed response over websockets.
+    HttpStatus(u16),
+    /// No response at all.
+    Never,
+}
+
+#[derive(Default)]
+struct MockState {
+    calls: Mutex<Vec<RecordedCall>>,
+    responses: Mutex<HashMap<String, VecDeque<Canned>>>,
+}
+
+/// A fake OneBot v11 implementation, cheap to clone, clones share their state.
+#[derive(Clone)]
+pub struct MockOnebot {
+    state: Arc<MockState>,
+    outgoing: broadcast::Sender<Outgoing>,
+    connections: Arc<watch::Sender<usize>>,
+    access_token: Option<String>,
+}
+
+impl Default for MockOnebot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockOnebot {
+    pub fn new() -> Self {
+        MockOnebot {
+            state: Arc::default(),
+            outgoing: broadcast::channel(100).0,
+            connections: Arc::new(watch::channel(0).0),
+            access_token: None,
+        }
+    }
+
+    /// Refuse websocket connections that don't carry `token`, as a header or in the query.
+    pub fn with_access_token(mut self, token: &str) -> Self {
+        self.access_token = Some(token.to_string());
+        self
+    }
+
+    /// Listen on a random local port for [`OnebotV11WsBot`](crate::OnebotV11WsBot),
+    /// returning the config that dials it.
+    pub async fn serve_ws(&self) -> std::io::Result<WsConfig> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let self_ = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let self_ = self_.clone();
+                tokio::spawn(async move {
+                    let token = self_.access_token.clone();
+                    // The handshake callback's error type is fixed by tungstenite.
+                    #[allow(clippy::result_large_err)]
+                    let check = |req: &Request<()>, resp| match &token {
+                        Some(token) if !carries_token(req, token) => {
+                            let mut resp = ErrorResponse::new(None);
+                            *resp.status_mut() = StatusCode::UNAUTHORIZED;
+                            Err(resp)
+                        }
+                        _ => Ok(resp),
+                    };
+                    match accept_hdr_async(stream, check).await {
+                        Ok(ws) => self_.serve_connection(ws, None).await,
+                        Err(e) => tracing::warn!("Onebotv11: Mock handshake failed: {}", e),
+                    }
+                });
+            }
+        });
+        Ok(WsConfig {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            r#type: WsType::Universal,
+            bot_id: None,
+            bot_nick_name: None,
+            access_token: None,
+        })
+    }
+
+    /// Listen on a random local port for the HTTP API of
+    /// [`OnebotV11HttpBot`](crate::OnebotV11HttpBot), returning the config that calls it.
+    pub async fn serve_http(&self) -> std::io::Result<HttpConfig> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let self_ = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let self_ = self_.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| self_.clone().handle_http(req));
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        tracing::warn!("Onebotv11: Mock http connection error: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(HttpConfig {
+            host: addr.ip().to_string(),
+            port: addr.port(),
+            access_token: None,
+        })
+    }
+
+    /// Post `event` to the webhook of an [`OnebotV11HttpBot`](crate::OnebotV11HttpBot)
+    /// configured with `config`, signed with its secret if it has one.
+    pub async fn post_event(
+        &self,
+        config: &crate::bot::http::OnebotV11HttpConfig,
+        event: Value,
+    ) -> reqwest::Result<reqwest::Response> {
+        let url = format!(
+            "http://{}:{}/{}",
+            config.post_host,
+            config.post_port,
+            config.post_suffix.trim_start_matches('/')
+        );
+        let body = event.to_string();
+        let mut request = reqwest::Client::new()
+            .post(url)
+            .header("Content-Type", "application/json");
+        if let Some(secret) = &config.secret {
+            request = request.header("X-Signature", sign(secret, body.as_bytes()));
+        }
+        request.body(body).send().await
+    }
+
+    /// Connect to an [`OnebotV11ReverseWsBot`](crate::OnebotV11ReverseWsBot) listening with
+    /// `config`, as the account `self_id`.
+    pub async fn connect_reverse(
+        &self,
+        config: &ReverseWsConfig,
+        self_id: i64,
+    ) -> Result<(), tungstenite::Error> {
+        let url = format!(
+            "ws://{}:{}/{}",
+            config.host,
+            config.port,
+            config.suffix.trim_start_matches('/')
+        );
+        let mut request = url.into_client_request()?;
+        let headers = request.headers_mut();
+        headers.insert("X-Self-ID", self_id.into());
+        headers.insert("X-Client-Role", "Universal".parse().expect("valid header"));
+        if let Some(token) = &config.access_token {
+            headers.insert(
+                AUTHORIZATION,
+                format!("Bearer {}", token)
+                    .parse()
+                    .map_err(tungstenite::http::Error::from)?,
+            );
+        }
+        let (ws, _) = connect_async(request).await?;
+        let self_ = self.clone();
+        let mut connections = self.connections.subscribe();
+        let before = *connections.borrow_and_update();
+        tokio::spawn(async move { self_.serve_connection(ws, Some(self_id)).await });
+        // Return once the connection listens for pushed events.
+        let _ = connections.wait_for(|n| *n > before).await;
+        Ok(())
+    }
+
+    /// Wait until at least one bot is connected.
+    pub async fn wait_connected(&self) {
+        let _ = self.connections.subscribe().wait_for(|n| *n > 0).await;
+    }
+
+    /// The number of bots connected right now.
+    pub fn connections(&self) -> usize {
+        *self.connections.borrow()
+    }
+
+    /// Queue a response for the next call of `action`, `echo` is filled in.
+    pub fn respond(&self, action: &str, resp: ApiResp) {
+        self.respond_json(action, serde_json::to_value(resp).unwrap_or_default());
+    }
+
+    /// Queue a successful response carrying `data` for the next call of `action`.
+    pub fn respond_ok(&self, action: &str, data: ApiRespData) {
+        self.respond_json(
+            action,
+            json!({ "status": "ok", "retcode": 0, "data": data }),
+        );
+    }
+
+    /// Queue a failed response for the next call of `action`.
+    pub fn respond_failed(&self, action: &str, retcode: i64, wording: &str) {
+        self.respond_json(
+            action,
+            json!({
+                "status": "failed",
+                "retcode": retcode,
+                "data": null,
+                "message": wording,
+                "wording": wording,
+            }),
+        );
+    }
+
+    /// Queue a raw response for the next call of `action`.
+    pub fn respond_json(&self, action: &str, resp: Value) {
+        self.queue(action, Canned::Json(resp));
+    }
+
+    /// Answer the next HTTP call of `action` with `status` and no body.
+    pub fn respond_http_status(&self, action: &str, status: u16) {
+        self.queue(action, Canned::HttpStatus(status));
+    }
+
+    /// Leave the next call of `action` unanswered, as a hung implementation would.
+    pub fn respond_never(&self, action: &str) {
+        self.queue(action, Canned::Never);
+    }
+
+    fn queue(&self, action: &str, canned: Canned) {
+        self.state
+            .responses
+            .lock()
+            .unwrap()
+            .entry(action.to_string())
+            .or_default()
+            .push_back(canned);
+    }
+
+    /// Push an event to every connected bot.
+    pub fn push_event(&self, event: Value) {
+        // Without connections there is nobody to push to.
+        let _ = self.outgoing.send(Outgoing::Text {
+            text: event.to_string(),
+            to: None,
+        });
+    }
+
+    /// Push `event` and wait up to 5 seconds for the next matcher from [`start_bot`].
+    pub async fn deliver(
+        &self,
+        matchers: &mut broadcast::Receiver<Matcher>,
+        event: Value,
+    ) -> Matcher {
+        self.push_event(event);
+        tokio::time::timeout(Duration::from_secs(5), matchers.recv())
+            .await
+            .expect("matcher delivered")
+            .expect("event loop running")
+    }
+
+    /// Push an event only over the connections made by [`MockOnebot::connect_reverse`] as the
+    /// account `self_id`.
+    pub fn push_event_as(&self, self_id: i64, event: Value) {
+        let _ = self.outgoing.send(Outgoing::Text {
+            text: event.to_string(),
+            to: Some(self_id),
+        });
+    }
+
+    /// Close every connection, as if the implementation went away.
+    pub fn disconnect(&self) {
+        let _ = self.outgoing.send(Outgoing::Close);
+    }
+
+    /// Every call received so far, oldest first.
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.state.calls.lock().unwrap().clone()
+    }
+
+    /// The calls received so far, clearing the record.
+    pub fn take_calls(&self) -> Vec<RecordedCall> {
+        std::mem::take(&mut *self.state.calls.lock().unwrap())
+    }
+
+    fn next_canned(&self, action: &str) -> Canned {
+        self.state
+            .responses
+            .lock()
+            .unwrap()
+            .get_mut(action)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| Canned::Json(json!({ "status": "ok", "retcode": 0, "data": null })))
+    }
+
+    fn next_response(&self, call: &WsApiPayload) -> Option<Value> {
+        let mut resp = match self.next_canned(&call.action) {
+            Canned::Json(resp) => resp,
+            Canned::HttpStatus(status) => {
+                json!({ "status": "failed", "retcode": status, "data": null })
+            }
+            Canned::Never => return None,
+        };
+        resp["echo"] = Value::String(call.echo.clone());
+        Some(resp)
+    }
+
+    async fn handle_http(
+        self,
+        req: Request<Incoming>,
+    ) -> Result<Response<Full<Bytes>>, Infallible> {
+        let action = req.uri().path().trim_matches('/').to_string();
+        let body = match req.into_body().collect().await {
+            Ok(body) => body.to_bytes(),
+            Err(_) => Bytes::new(),
+        };
+        let canned = self.next_canned(&action);
+        self.state.calls.lock().unwrap().push(RecordedCall {
+            action,
+            params: serde_json::from_slice(&body).unwrap_or_default(),
+        });
+        let mut resp = Response::new(Full::new(Bytes::new()));
+        match canned {
+            Canned::Json(json) => *resp.body_mut() = Full::new(Bytes::from(json.to_string())),
+            Canned::HttpStatus(status) => {
+                *resp.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::OK)
+            }
+            Canned::Never => std::future::pending().await,
+        }
+        Ok(resp)
+    }
+
+    /// Serve a connection, made as the account `self_id` if known.
+    async fn serve_connection<S>(&self, ws: WebSocketStream<S>, self_id: Option<i64>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (mut write, mut read) = ws.split();
+        let mut outgoing = self.outgoing.subscribe();
+        self.connections.send_modify(|n| *n += 1);
+        loop {
+            tokio::select! {
+                msg = read.next() => match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        let call = match serde_json::from_str::<WsApiPayload>(&text) {
+                            Ok(call) => call,
+                            Err(e) => {
+                                tracing::warn!("Onebotv11: Mock got a bad api call: {}, Raw: {}", e, text);
+                                continue;
+                            }
+                        };
+                        let resp = self.next_response(&call);
+                        // Record before answering, so the call is visible once the bot returns.
+                        self.state.calls.lock().unwrap().push(RecordedCall {
+                            action: call.action,
+                            params: call.params,
+                        });
+                        let Some(resp) = resp else { continue };
+                        if write.send(Message::Text(resp.to_string())).await.is_err() {
+                            break;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                },
+                out = outgoing.recv() => match out {
+                    Ok(Outgoing::Text { text, to }) => {
+                        if to.is_some() && to != self_id {
+                            continue;
+                        }
+                        if write.send(Message::Text(text)).await.is_err() {
+                            break;
+                        }
+                    }
+                    Ok(Outgoing::Close) | Err(broadcast::error::RecvError::Closed) => {
+                        let _ = write.close().await;
+                        break;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                },
+            }
+        }
+        self.connections.send_modify(|n| *n -= 1);
+    }
+}
+
+/// Run `bot`'s event loop in a task, returning the matchers it sends once it is subscribed to
+/// its connection, so every event pushed after this reaches it.
+pub async fn start_bot(bot: &dyn BotTrait) -> broadcast::Receiver<Matcher> {
+    let (sender, matchers) = broadcast::channel(16);
+    let bot = bot.clone_box();
+    let mut events = Box::pin(async move { bot.start_sending_events(sender).await });
+    // The loop subscribes before it first waits for an event, and no transport's subscribe
+    // waits for anything.
+    let _ = futures_util::poll!(events.as_mut());
+    tokio::spawn(events);
+    matchers
+}
+
+/// The `X-Signature` header value of `body` under `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes()).expect("any key length");
+    mac.update(body);
+    format!("sha1={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// A local address nothing listens on right now, for bots that bind their own port.
+pub fn unused_addr() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("bind a local port")
+}
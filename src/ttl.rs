@@ -0,0 +1,95 @@
+//! A map keeping its newest entries for a while, shared by the message store and the reply
+//! resolver.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+/// At most `capacity` entries, none older than `max_age`.
+///
+/// Entries are dropped oldest first. An entry inserted again counts from then, its old place in
+/// `order` is skipped by its stale sequence number.
+pub(crate) struct TtlMap<K, V> {
+    capacity: usize,
+    max_age: Duration,
+    seq: u64,
+    entries: HashMap<K, (u64, Instant, V)>,
+    order: VecDeque<(u64, K)>,
+}
+
+impl<K: Hash + Eq + Clone, V> TtlMap<K, V> {
+    pub(crate) fn new(capacity: usize, max_age: Duration) -> Self {
+        TtlMap {
+            capacity,
+            max_age,
+            seq: 0,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, key: &K) -> Option<&V> {
+        self.evict();
+        self.entries.get(key).map(|(_, _, value)| value)
+    }
+
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        self.seq += 1;
+        self.entries
+            .insert(key.clone(), (self.seq, Instant::now(), value));
+        self.order.push_back((self.seq, key));
+        self.evict();
+    }
+
+    pub(crate) fn remove(&mut self, key: &K) -> Option<V> {
+        let (_, _, value) = self.entries.remove(key)?;
+        self.compact();
+        Some(value)
+    }
+
+    pub(crate) fn len(&mut self) -> usize {
+        self.evict();
+        self.entries.len()
+    }
+
+    fn is_current(&self, seq: u64, key: &K) -> bool {
+        self.entries
+            .get(key)
+            .is_some_and(|(held, _, _)| *held == seq)
+    }
+
+    fn evict(&mut self) {
+        while let Some((seq, oldest)) = self.order.front() {
+            let keep = match self.entries.get(oldest) {
+                Some((held, at, _)) if held == seq => {
+                    at.elapsed() < self.max_age && self.entries.len() <= self.capacity
+                }
+                // Removed, or inserted again since.
+                _ => false,
+            };
+            if keep {
+                break;
+            }
+            if let Some((seq, oldest)) = self.order.pop_front() {
+                if self.is_current(seq, &oldest) {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        self.compact();
+    }
+
+    /// Drop the stale places of entries inserted again or removed, once they are most of
+    /// `order`.
+    fn compact(&mut self) {
+        if self.order.len() > self.entries.len() * 2 {
+            let order = std::mem::take(&mut self.order);
+            self.order = order
+                .into_iter()
+                .filter(|(seq, key)| self.is_current(*seq, key))
+                .collect();
+        }
+    }
+}
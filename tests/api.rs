@@ -0,0 +1,810 @@
+mod common;
+
+use std::time::Duration;
+
+use common::connect;
+use onebot_v11::api::{
+    payload::*,
+    resp::{
+        ApiRespData, CommonClientResponseResult, DelGroupFileResponse, FileInfo, FolderInfo,
+        GetFileResponse, GetFriendListResponseItem, GetGroupFileCountResponse,
+        GetGroupFileListResponse, GetGroupInfoResponse, GetGroupListResponseItem,
+        GetGroupMemberListResponseItem, GetLoginInfoResponse, GetMsgResponse,
+        GetStrangerInfoResponse, GroupFile, MessageSender, SendGroupMsgResponse,
+        SendPrivateMsgResponse, SetGroupFileFolderResponse, TransGroupFileResult,
+    },
+};
+use onebot_v11_oxidebot::{avatar::AvatarConfig, error::OnebotError, testing::MockOnebot};
+use oxidebot::{
+    api::{
+        payload::{GroupAdminChangeType, GroupMuteType, RequestResponse, SendMessageTarget},
+        CallApiTrait,
+    },
+    source::{
+        group::GroupProfile,
+        message::{FsNode, MessageSegment},
+        user::{Role, Sex, UserProfile},
+    },
+};
+use serde_json::json;
+
+fn assert_called(mock: &MockOnebot, payload: ApiPayload) {
+    let calls = mock.take_calls();
+    assert_eq!(calls.len(), 1, "{:?}", calls);
+    assert!(calls[0].is(&payload), "{:?} != {:?}", calls[0], payload);
+}
+
+fn client_result() -> CommonClientResponseResult {
+    CommonClientResponseResult {
+        client_wording: String::new(),
+        ret_code: 0,
+        ret_msg: String::new(),
+    }
+}
+
+fn file_info(file_id: &str, file_name: &str, file_size: &str) -> FileInfo {
+    FileInfo {
+        bus_id: 102,
+        dead_time: 0,
+        download_times: 0,
+        element_id: String::new(),
+        file_id: file_id.to_string(),
+        file_model_id: String::new(),
+        file_name: file_name.to_string(),
+        file_size: file_size.to_string(),
+        is_folder: false,
+        local_path: String::new(),
+        md5: String::new(),
+        modify_time: 0,
+        parent_folder_id: "/".to_string(),
+        sha: String::new(),
+        sha3: String::new(),
+        trans_status: 0,
+        strans_type: 0,
+        upload_time: 0,
+        uploaded_size: file_size.to_string(),
+        uploader_local_path: String::new(),
+        uploader_name: String::new(),
+        uploader_uin: String::new(),
+    }
+}
+
+fn folder_info(folder_id: &str, folder_name: &str, total_file_count: i64) -> FolderInfo {
+    FolderInfo {
+        create_time: 0,
+        create_uin: String::new(),
+        creator_name: String::new(),
+        folder_id: folder_id.to_string(),
+        folder_name: folder_name.to_string(),
+        modify_name: String::new(),
+        modify_time: 0,
+        modify_uin: String::new(),
+        parent_folder_id: "/".to_string(),
+        total_file_count,
+        used_space: "0".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn send_group_message() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "send_group_msg",
+        ApiRespData::SendGroupMsgResponse(SendGroupMsgResponse { message_id: 42 }),
+    );
+    let resp = bot
+        .send_message(
+            vec![MessageSegment::text("hi")],
+            SendMessageTarget::Group("10001".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp[0].sent_message_id, "42");
+    let calls = mock.take_calls();
+    assert_eq!(calls[0].action, "send_group_msg");
+    assert_eq!(
+        calls[0].params,
+        json!({
+            "group_id": 10001,
+            "message": [{ "type": "text", "data": { "text": "hi" } }],
+            "auto_escape": true,
+        })
+    );
+}
+
+#[tokio::test]
+async fn send_private_message() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "send_private_msg",
+        ApiRespData::SendPrivateMsgResponse(SendPrivateMsgResponse { message_id: -7 }),
+    );
+    let resp = bot
+        .send_message(
+            vec![MessageSegment::text("hi")],
+            SendMessageTarget::Private("20002".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(resp[0].sent_message_id, "-7");
+    let calls = mock.take_calls();
+    assert_eq!(calls[0].action, "send_private_msg");
+    assert_eq!(calls[0].params["user_id"], 20002);
+}
+
+#[tokio::test]
+async fn invalid_id_is_not_sent() {
+    let (mock, bot) = connect().await;
+    let e = bot
+        .send_message(
+            vec![MessageSegment::text("hi")],
+            SendMessageTarget::Group("1000l".to_string()),
+        )
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        e.downcast_ref::<OnebotError>(),
+        Some(OnebotError::InvalidId { .. })
+    ));
+    assert!(mock.calls().is_empty());
+}
+
+#[tokio::test]
+async fn failed_status_is_an_api_error() {
+    let (mock, bot) = connect().await;
+    mock.respond_failed("delete_msg", 1404, "message not found");
+    let e = bot.delete_message("5".to_string()).await.unwrap_err();
+    match e.downcast_ref::<OnebotError>() {
+        Some(OnebotError::Api {
+            action,
+            retcode,
+            wording,
+            ..
+        }) => {
+            assert_eq!(action, "delete_msg");
+            assert_eq!(*retcode, 1404);
+            assert_eq!(wording.as_deref(), Some("message not found"));
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn unexpected_response_keeps_the_request() {
+    let (_mock, bot) = connect().await;
+    // The mock answers `ok` without data unless told otherwise.
+    let e = bot.get_message_detail("5".to_string()).await.unwrap_err();
+    match e.downcast_ref::<OnebotError>() {
+        Some(OnebotError::UnexpectedResponse {
+            action, payload, ..
+        }) => {
+            assert_eq!(action, "get_msg");
+            assert_eq!(payload["message_id"], 5);
+        }
+        e => panic!("unexpected error {:?}", e),
+    }
+}
+
+#[tokio::test]
+async fn delete_message() {
+    let (mock, bot) = connect().await;
+    bot.delete_message("5".to_string()).await.unwrap();
+    assert_called(&mock, ApiPayload::DeleteMsg(DeleteMsg { message_id: 5 }));
+}
+
+#[tokio::test]
+async fn get_message_detail() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_msg",
+        ApiRespData::GetMsgResponse(GetMsgResponse {
+            time: 1700000000,
+            message_type: "group".to_string(),
+            message_id: 5,
+            real_id: 5,
+            sender: MessageSender {
+                user_id: Some(20002),
+                nickname: Some("alice".to_string()),
+                card: None,
+                sex: Some("female".to_string()),
+                age: Some(18),
+                area: None,
+                level: None,
+                role: None,
+                title: None,
+            },
+            message: vec![serde_json::from_value(
+                json!({ "type": "text", "data": { "text": "hello" } }),
+            )
+            .unwrap()],
+        }),
+    );
+    let resp = bot.get_message_detail("5".to_string()).await.unwrap();
+    assert_called(&mock, ApiPayload::GetMsg(GetMsg { message_id: 5 }));
+    assert_eq!(resp.message, vec![MessageSegment::text("hello")]);
+    let sender = resp.sender.unwrap();
+    assert_eq!(sender.id, "20002");
+    let profile = sender.profile.unwrap();
+    assert_eq!(profile.nickname.as_deref(), Some("alice"));
+    assert_eq!(profile.sex, Some(Sex::Female));
+    assert_eq!(profile.age, Some(18));
+    assert_eq!(resp.time.unwrap().timestamp(), 1700000000);
+}
+
+#[tokio::test]
+async fn set_message_reaction() {
+    let (mock, bot) = connect().await;
+    bot.set_message_reaction("5".to_string(), "76".to_string())
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetMsgEmojiLike(SetMsgEmojiLike {
+            message_id: "5".to_string(),
+            emoji_id: "76".to_string(),
+        }),
+    );
+}
+
+#[tokio::test]
+async fn get_group_member_list() {
+    let (mock, bot) = connect().await;
+    let member = |user_id: i64, role: &str| GetGroupMemberListResponseItem {
+        group_id: 10001,
+        user_id,
+        nickname: "alice".to_string(),
+        card: "Alice".to_string(),
+        sex: "female".to_string(),
+        age: 18,
+        join_time: 1600000000,
+        last_sent_time: 1700000000,
+        level: "3".to_string(),
+        role: role.to_string(),
+        unfriendly: false,
+        title: "title".to_string(),
+        title_expire_time: 0,
+        card_changeable: true,
+    };
+    mock.respond_ok(
+        "get_group_member_list",
+        ApiRespData::GetGroupMemberListResponse(vec![
+            member(20002, "admin"),
+            member(20003, "owner"),
+            member(20004, "member"),
+        ]),
+    );
+    let resp = bot
+        .get_group_member_list("10001".to_string())
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::GetGroupMemberList(GetGroupMemberList { group_id: 10001 }),
+    );
+    let member = &resp.members[0];
+    assert_eq!(member.id, "20002");
+    assert_eq!(
+        member.profile.as_ref().unwrap().nickname.as_deref(),
+        Some("alice")
+    );
+    let group_info = member.group_info.as_ref().unwrap();
+    assert_eq!(group_info.role, Some(Role::Admin));
+    assert_eq!(group_info.level.as_deref(), Some("3"));
+    assert_eq!(group_info.alias.as_deref(), Some("title"));
+    assert_eq!(group_info.join_time.unwrap().timestamp(), 1600000000);
+    let roles: Vec<_> = resp
+        .members
+        .iter()
+        .map(|member| member.group_info.as_ref().unwrap().role.clone())
+        .collect();
+    assert_eq!(
+        roles,
+        [Some(Role::Admin), Some(Role::Owner), Some(Role::Member)]
+    );
+}
+
+#[tokio::test]
+async fn kick_group_member() {
+    let (mock, bot) = connect().await;
+    bot.kick_group_member("10001".to_string(), "20002".to_string(), Some(true))
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupKick(SetGroupKick {
+            group_id: 10001,
+            user_id: 20002,
+            reject_add_request: true,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn mute_group() {
+    let (mock, bot) = connect().await;
+    bot.mute_group("10001".to_string(), None, GroupMuteType::Mute)
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupWholeBan(SetGroupWholeBan {
+            group_id: 10001,
+            enable: true,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn mute_group_member() {
+    let (mock, bot) = connect().await;
+    bot.mute_group_member(
+        "10001".to_string(),
+        "20002".to_string(),
+        GroupMuteType::Mute,
+        Some(Duration::from_secs(600)),
+    )
+    .await
+    .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupBan(SetGroupBan {
+            group_id: 10001,
+            user_id: 20002,
+            duration: 600,
+        }),
+    );
+    bot.mute_group_member(
+        "10001".to_string(),
+        "20002".to_string(),
+        GroupMuteType::Unmute,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupBan(SetGroupBan {
+            group_id: 10001,
+            user_id: 20002,
+            duration: 0,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn change_group_admin() {
+    let (mock, bot) = connect().await;
+    bot.change_group_admin(
+        "10001".to_string(),
+        "20002".to_string(),
+        GroupAdminChangeType::Unset,
+    )
+    .await
+    .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupAdmin(SetGroupAdmin {
+            group_id: 10001,
+            user_id: 20002,
+            enable: false,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn set_group_member_alias() {
+    let (mock, bot) = connect().await;
+    bot.set_group_member_alias(
+        "10001".to_string(),
+        "20002".to_string(),
+        "Alice".to_string(),
+    )
+    .await
+    .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupCard(SetGroupCard {
+            group_id: 10001,
+            user_id: 20002,
+            card: "Alice".to_string(),
+        }),
+    );
+}
+
+#[tokio::test]
+async fn get_group_profile() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_group_info",
+        ApiRespData::GetGroupInfoResponse(GetGroupInfoResponse {
+            group_id: 10001,
+            group_name: "rustaceans".to_string(),
+            member_count: 42,
+            max_member_count: 500,
+        }),
+    );
+    let resp = bot.get_group_profile("10001".to_string()).await.unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::GetGroupInfo(GetGroupInfo {
+            group_id: 10001,
+            no_cache: true,
+        }),
+    );
+    assert_eq!(resp.profile.name.as_deref(), Some("rustaceans"));
+    assert_eq!(resp.profile.member_count, Some(42));
+    assert_eq!(
+        resp.profile.avatar.unwrap().to_string(),
+        "https://p.qlogo.cn/gh/10001/10001/640"
+    );
+}
+
+#[tokio::test]
+async fn avatar_config() {
+    let (mock, bot) = connect().await;
+    let bot = bot.with_avatars(AvatarConfig {
+        enabled: true,
+        size: 100,
+    });
+    let login_info = || {
+        ApiRespData::GetLoginInfoResponse(GetLoginInfoResponse {
+            user_id: 30003,
+            nickname: "bot".to_string(),
+        })
+    };
+    mock.respond_ok("get_login_info", login_info());
+    let resp = bot.get_bot_profile().await.unwrap();
+    assert_eq!(
+        resp.profile.avatar.unwrap().to_string(),
+        "https://q1.qlogo.cn/g?b=qq&nk=30003&s=100"
+    );
+
+    let bot = bot.with_avatars(AvatarConfig::DISABLED);
+    mock.respond_ok("get_login_info", login_info());
+    let resp = bot.get_bot_profile().await.unwrap();
+    assert_eq!(resp.profile.avatar, None);
+}
+
+#[tokio::test]
+async fn set_group_profile() {
+    let (mock, bot) = connect().await;
+    bot.set_group_profile(
+        "10001".to_string(),
+        GroupProfile {
+            name: Some("crabs".to_string()),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupName(SetGroupName {
+            group_id: 10001,
+            group_name: "crabs".to_string(),
+        }),
+    );
+    let e = bot
+        .set_group_profile("10001".to_string(), GroupProfile::default())
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        e.downcast_ref::<OnebotError>(),
+        Some(OnebotError::InvalidArgument { .. })
+    ));
+    assert!(mock.calls().is_empty());
+}
+
+#[tokio::test]
+async fn get_group_file_count() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_group_file_count",
+        ApiRespData::GetGroupFileCountResponse(GetGroupFileCountResponse { count: 3 }),
+    );
+    let resp = bot
+        .get_group_file_count("10001".to_string(), None)
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::GetGroupFileCount(GetGroupFileCount { group_id: 10001 }),
+    );
+    assert_eq!(resp.count, 3);
+}
+
+#[tokio::test]
+async fn get_group_fs_list() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_group_file_list",
+        ApiRespData::GetGroupFileListResponse(GetGroupFileListResponse {
+            file_list: vec![
+                GroupFile {
+                    file_info: Some(file_info("/f1", "a.txt", "12")),
+                    folder_info: None,
+                    peer_id: "10001".to_string(),
+                    r#type: 1,
+                },
+                GroupFile {
+                    file_info: None,
+                    folder_info: Some(folder_info("/d1", "docs", 4)),
+                    peer_id: "10001".to_string(),
+                    r#type: 2,
+                },
+            ],
+        }),
+    );
+    let resp = bot
+        .get_group_fs_list("10001".to_string(), 0, 20)
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::GetGroupFileList(GetGroupFileList {
+            group_id: 10001,
+            start_index: 0,
+            file_count: 20,
+        }),
+    );
+    match &resp.fs_tree[..] {
+        [FsNode::File(file), FsNode::Folder(folder)] => {
+            assert_eq!(file.id.as_deref(), Some("/f1"));
+            assert_eq!(file.name, "a.txt");
+            assert_eq!(file.size, Some(12));
+            assert_eq!(folder.id, "/d1");
+            assert_eq!(folder.name, "docs");
+            assert_eq!(folder.file_amount, 4);
+        }
+        tree => panic!("unexpected tree {:?}", tree),
+    }
+}
+
+#[tokio::test]
+async fn delete_group_file() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "del_group_file",
+        ApiRespData::DelGroupFileResponse(DelGroupFileResponse {
+            error_msg: String::new(),
+            result: 0,
+            trans_group_file_result: TransGroupFileResult {
+                fail_file_id_list: vec![],
+                result: client_result(),
+                success_file_id_list: vec!["/f1".to_string()],
+            },
+        }),
+    );
+    bot.delete_group_file("10001".to_string(), "/f1".to_string())
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::DelGroupFile(DelGroupFile {
+            group_id: 10001,
+            file_id: "/f1".to_string(),
+        }),
+    );
+}
+
+#[tokio::test]
+async fn delete_group_folder() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "del_group_file_folder",
+        ApiRespData::DelGroupFileFolderResponse(client_result()),
+    );
+    bot.delete_group_folder("10001".to_string(), "/d1".to_string())
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::DelGroupFileFolder(DelGroupFileFolder {
+            group_id: 10001,
+            folder_id: "/d1".to_string(),
+        }),
+    );
+}
+
+#[tokio::test]
+async fn create_group_folder() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "set_group_file_folder",
+        ApiRespData::SetGroupFileFolderResponse(SetGroupFileFolderResponse {
+            group_item: GroupFile {
+                file_info: None,
+                folder_info: Some(folder_info("/d2", "new", 0)),
+                peer_id: "10001".to_string(),
+                r#type: 2,
+            },
+            result: client_result(),
+        }),
+    );
+    bot.create_group_folder("10001".to_string(), "new".to_string(), None)
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupFileFolder(SetGroupFileFolder {
+            group_id: 10001,
+            folder_name: "new".to_string(),
+        }),
+    );
+}
+
+#[tokio::test]
+async fn get_user_profile() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_stranger_info",
+        ApiRespData::GetStrangerInfoResponse(GetStrangerInfoResponse {
+            user_id: 20002,
+            nickname: "alice".to_string(),
+            sex: "female".to_string(),
+            age: 18,
+        }),
+    );
+    let resp = bot.get_user_profile("20002".to_string()).await.unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::GetStrangerInfo(GetStrangerInfo {
+            user_id: 20002,
+            no_cache: true,
+        }),
+    );
+    assert_eq!(resp.profile.nickname.as_deref(), Some("alice"));
+    assert_eq!(resp.profile.sex, Some(Sex::Female));
+    assert_eq!(resp.profile.age, Some(18));
+}
+
+#[tokio::test]
+async fn set_bot_profile() {
+    let (mock, bot) = connect().await;
+    bot.set_bot_profile(UserProfile {
+        avatar: Some("https://example.com/a.png".parse().unwrap()),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetQQAvatar(SetQQAvatar {
+            file: "https://example.com/a.png".to_string(),
+        }),
+    );
+}
+
+#[tokio::test]
+async fn get_bot_profile() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_login_info",
+        ApiRespData::GetLoginInfoResponse(GetLoginInfoResponse {
+            user_id: 30003,
+            nickname: "bot".to_string(),
+        }),
+    );
+    let resp = bot.get_bot_profile().await.unwrap();
+    assert_called(&mock, ApiPayload::GetLoginInfo(GetLoginInfo {}));
+    assert_eq!(resp.profile.nickname.as_deref(), Some("bot"));
+}
+
+#[tokio::test]
+async fn get_bot_friend_list() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_friend_list",
+        ApiRespData::GetFriendListResponse(vec![GetFriendListResponseItem {
+            user_id: 20002,
+            nickname: "alice".to_string(),
+            remark: String::new(),
+        }]),
+    );
+    let resp = bot.get_bot_friend_list().await.unwrap();
+    assert_called(&mock, ApiPayload::GetFriendList(GetFriendList {}));
+    assert_eq!(resp.friends[0].id, "20002");
+    assert_eq!(
+        resp.friends[0]
+            .profile
+            .as_ref()
+            .unwrap()
+            .nickname
+            .as_deref(),
+        Some("alice")
+    );
+}
+
+#[tokio::test]
+async fn get_bot_group_list() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_group_list",
+        ApiRespData::GetGroupListResponse(vec![GetGroupListResponseItem {
+            group_id: 10001,
+            group_name: "rustaceans".to_string(),
+            member_count: 42,
+            max_member_count: 500,
+        }]),
+    );
+    let resp = bot.get_bot_group_list().await.unwrap();
+    assert_called(&mock, ApiPayload::GetGroupList(GetGroupList {}));
+    assert_eq!(resp.groups[0].id, "10001");
+    let profile = resp.groups[0].profile.as_ref().unwrap();
+    assert_eq!(profile.name.as_deref(), Some("rustaceans"));
+    assert_eq!(profile.member_count, Some(42));
+}
+
+#[tokio::test]
+async fn handle_add_friend_request() {
+    let (mock, bot) = connect().await;
+    bot.handle_add_friend_request("flag1".to_string(), RequestResponse::Approve)
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetFriendAddRequest(SetFriendAddRequest {
+            flag: "flag1".to_string(),
+            approve: true,
+            remark: None,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn handle_add_group_request() {
+    let (mock, bot) = connect().await;
+    bot.handle_add_group_request("flag2".to_string(), RequestResponse::Reject)
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupAddRequest(SetGroupAddRequest {
+            flag: "flag2".to_string(),
+            sub_type: "add".to_string(),
+            reason: None,
+            approve: false,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn handle_invite_group_request() {
+    let (mock, bot) = connect().await;
+    bot.handle_invite_group_request("flag3".to_string(), RequestResponse::Approve)
+        .await
+        .unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::SetGroupAddRequest(SetGroupAddRequest {
+            flag: "flag3".to_string(),
+            sub_type: "invite".to_string(),
+            reason: None,
+            approve: true,
+        }),
+    );
+}
+
+#[tokio::test]
+async fn get_file_info() {
+    let (mock, bot) = connect().await;
+    mock.respond_ok(
+        "get_file",
+        ApiRespData::GetFileResponse(GetFileResponse {
+            file: "/tmp/a.txt".to_string(),
+            file_name: "a.txt".to_string(),
+            file_size: 12,
+            base64: "aGVsbG8gd29ybGQK".to_string(),
+        }),
+    );
+    let file = bot.get_file_info("/f1".to_string()).await.unwrap();
+    assert_called(
+        &mock,
+        ApiPayload::GetFile(GetFile {
+            file_id: "/f1".to_string(),
+        }),
+    );
+    assert_eq!(file.name, "a.txt");
+    assert_eq!(file.size, Some(12));
+    assert_eq!(file.base64.as_deref(), Some("aGVsbG8gd29ybGQK"));
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use common::{deliver, notice};
+use onebot_v11::api::resp::{
+    ApiRespData, GetFriendListResponseItem, GetGroupMemberListResponseItem,
+};
+use onebot_v11_oxidebot::{
+    cache::MemberCacheConfig,
+    testing::{start_bot, MockOnebot},
+    OnebotV11WsBot,
+};
+use oxidebot::{
+    api::CallApiTrait,
+    event::{Event, NoticeEvent},
+    matcher::Matcher,
+    source::user::Role,
+};
+use serde_json::json;
+use tokio::sync::broadcast;
+
+async fn connect() -> (MockOnebot, OnebotV11WsBot, broadcast::Receiver<Matcher>) {
+    let (mock, bot) = common::connect().await;
+    let bot = bot.with_member_cache(MemberCacheConfig::default());
+    let matchers = start_bot(&bot).await;
+    (mock, bot, matchers)
+}
+
+fn member(user_id: i64, nickname: &str, role: &str) -> GetGroupMemberListResponseItem {
+    GetGroupMemberListResponseItem {
+        group_id: 10001,
+        user_id,
+        nickname: nickname.to_string(),
+        card: String::new(),
+        sex: "unknown".to_string(),
+        age: 0,
+        join_time: 1600000000,
+        last_sent_time: 1700000000,
+        level: "1".to_string(),
+        role: role.to_string(),
+        unfriendly: false,
+        title: String::new(),
+        title_expire_time: 0,
+        card_changeable: true,
+    }
+}
+
+fn respond_members(mock: &MockOnebot) {
+    mock.respond_ok(
+        "get_group_member_list",
+        ApiRespData::GetGroupMemberListResponse(vec![
+            member(20002, "alice", "owner"),
+            member(20003, "bob", "member"),
+        ]),
+    );
+}
+
+async fn member_ids(bot: &OnebotV11WsBot) -> Vec<String> {
+    bot.get_group_member_list("10001".to_string())
+        .await
+        .unwrap()
+        .members
+        .into_iter()
+        .map(|member| member.id)
+        .collect()
+}
+
+#[tokio::test]
+async fn member_list_is_served_from_cache() {
+    let (mock, bot, _matchers) = connect().await;
+    respond_members(&mock);
+    assert_eq!(member_ids(&bot).await, ["20002", "20003"]);
+    assert_eq!(member_ids(&bot).await, ["20002", "20003"]);
+    assert_eq!(mock.take_calls().len(), 1);
+    assert!(bot.member_cache().unwrap().group_members("10001").is_some());
+}
+
+#[tokio::test]
+async fn notices_are_filled_and_update_the_cache() {
+    let (mock, bot, mut matchers) = connect().await;
+    respond_members(&mock);
+    member_ids(&bot).await;
+
+    let event = deliver(
+        &mock,
+        &mut matchers,
+        notice(
+            "group_admin",
+            json!({ "sub_type": "set", "group_id": 10001, "user_id": 20003 }),
+        ),
+    )
+    .await;
+    let Event::NoticeEvent(NoticeEvent::GroupAdminChangeEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    let profile = event.user.profile.unwrap();
+    assert_eq!(profile.nickname.as_deref(), Some("bob"));
+    assert!(profile.avatar.is_some());
+    assert_eq!(event.user.group_info.unwrap().role, Some(Role::Admin));
+    let members = bot
+        .get_group_member_list("10001".to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        members.members[1].group_info.as_ref().unwrap().role,
+        Some(Role::Admin)
+    );
+
+    let event = deliver(
+        &mock,
+        &mut matchers,
+        notice(
+            "group_decrease",
+            json!({ "sub_type": "leave", "group_id": 10001, "user_id": 20003, "operator_id": 20003 }),
+        ),
+    )
+    .await;
+    let Event::NoticeEvent(NoticeEvent::GroupMemberDecreaseEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.user.profile.unwrap().nickname.as_deref(), Some("bob"));
+    assert_eq!(member_ids(&bot).await, ["20002"]);
+    assert_eq!(mock.take_calls().len(), 1);
+
+    deliver(
+        &mock,
+        &mut matchers,
+        notice(
+            "group_increase",
+            json!({ "sub_type": "approve", "group_id": 10001, "user_id": 20004, "operator_id": 20002 }),
+        ),
+    )
+    .await;
+    respond_members(&mock);
+    member_ids(&bot).await;
+    assert_eq!(mock.take_calls().len(), 1);
+}
+
+#[tokio::test]
+async fn friend_add_is_filled_after_refetch() {
+    let (mock, bot, mut matchers) = connect().await;
+    let friends = |ids: &[i64]| {
+        ApiRespData::GetFriendListResponse(
+            ids.iter()
+                .map(|id| GetFriendListResponseItem {
+                    user_id: *id,
+                    nickname: format!("friend{}", id),
+                    remark: String::new(),
+                })
+                .collect(),
+        )
+    };
+    mock.respond_ok("get_friend_list", friends(&[20002]));
+    bot.get_bot_friend_list().await.unwrap();
+    bot.get_bot_friend_list().await.unwrap();
+    assert_eq!(mock.take_calls().len(), 1);
+
+    deliver(
+        &mock,
+        &mut matchers,
+        notice("friend_add", json!({ "user_id": 20003 })),
+    )
+    .await;
+    mock.respond_ok("get_friend_list", friends(&[20002, 20003]));
+    assert_eq!(bot.get_bot_friend_list().await.unwrap().friends.len(), 2);
+    assert_eq!(mock.take_calls().len(), 1);
+
+    let event = deliver(
+        &mock,
+        &mut matchers,
+        notice(
+            "friend_recall",
+            json!({ "user_id": 20003, "message_id": 7 }),
+        ),
+    )
+    .await;
+    let Event::NoticeEvent(NoticeEvent::MessageDeletedEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(
+        event.user.unwrap().profile.unwrap().nickname.as_deref(),
+        Some("friend20003")
+    );
+}
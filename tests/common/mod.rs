@@ -0,0 +1,82 @@
+//! Fixtures shared by the integration tests, not every test uses all of them.
+#![allow(dead_code)]
+
+use onebot_v11_oxidebot::{testing::MockOnebot, OnebotV11WsBot};
+use oxidebot::{event::Event, matcher::Matcher};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+pub const TIME: i64 = 1700000000;
+pub const SELF_ID: i64 = 30003;
+
+/// A websocket bot connected to a fresh mock.
+pub async fn connect() -> (MockOnebot, OnebotV11WsBot) {
+    let mock = MockOnebot::new();
+    let bot = OnebotV11WsBot::try_new(mock.serve_ws().await.unwrap())
+        .await
+        .unwrap();
+    mock.wait_connected().await;
+    (mock, bot)
+}
+
+pub fn text(text: &str) -> Value {
+    json!({ "type": "text", "data": { "text": text } })
+}
+
+/// A message from alice, 20002, in group 10001.
+pub fn group_message(message_id: i64, message: Value) -> Value {
+    json!({
+        "time": TIME,
+        "self_id": SELF_ID,
+        "post_type": "message",
+        "message_type": "group",
+        "sub_type": "normal",
+        "message_id": message_id,
+        "group_id": 10001,
+        "user_id": 20002,
+        "message": message,
+        "raw_message": "",
+        "font": 0,
+        "sender": { "user_id": 20002, "nickname": "alice" },
+    })
+}
+
+/// A private message from alice, 20002.
+pub fn private_message(message_id: i64, message: Value) -> Value {
+    json!({
+        "time": TIME,
+        "self_id": SELF_ID,
+        "post_type": "message",
+        "message_type": "private",
+        "sub_type": "friend",
+        "message_id": message_id,
+        "user_id": 20002,
+        "message": message,
+        "raw_message": "",
+        "font": 0,
+        "sender": { "user_id": 20002, "nickname": "alice" },
+    })
+}
+
+pub fn notice(notice_type: &str, fields: Value) -> Value {
+    let mut event = json!({
+        "time": TIME,
+        "self_id": SELF_ID,
+        "post_type": "notice",
+        "notice_type": notice_type,
+    });
+    event
+        .as_object_mut()
+        .unwrap()
+        .extend(fields.as_object().unwrap().clone());
+    event
+}
+
+/// Push `event` and return it as handlers got it.
+pub async fn deliver(
+    mock: &MockOnebot,
+    matchers: &mut broadcast::Receiver<Matcher>,
+    event: Value,
+) -> Event {
+    Event::clone(&mock.deliver(matchers, event).await.event)
+}
@@ -0,0 +1,458 @@
+//! Runs the events in `tests/corpus/<implementation>/` through `parse_event` and checks the
+//! fields each one is about.
+//!
+//! Every `<name>.json` has to be listed in [`CASES`] and checked by one of the tests below,
+//! see `tests/corpus/README.md` for where the events come from.
+
+use std::{fs, path::Path, time::Duration};
+
+use onebot_v11_oxidebot::event::{
+    parse_event, FriendInputStatusChangeEventWrapper, GroupFileUploadEventWrapper,
+    GroupLuckyKingEventWrapper, GroupMemberHonorChangeEventWrapper, GroupPokeEventWrapper,
+    HeartbeatWrapper,
+};
+use oxidebot::event::{
+    any::AnyEventDataObject,
+    notice::{
+        GroupAdminChangeType, GroupHightLightChangeType, GroupMemberDecreaseReason,
+        GroupMemberIncreseReason, MuteType,
+    },
+    Event, MetaEvent, NoticeEvent, RequestEvent,
+};
+use oxidebot::source::{
+    message::MessageSegment,
+    user::{Role, Sex},
+};
+
+const CASES: &[&str] = &[
+    "go-cqhttp/group_ban_lift",
+    "go-cqhttp/group_decrease_kick_me",
+    "go-cqhttp/group_message",
+    "go-cqhttp/group_message_string_format",
+    "go-cqhttp/group_request_invite",
+    "go-cqhttp/group_upload",
+    "go-cqhttp/heartbeat",
+    "go-cqhttp/honor_talkative",
+    "go-cqhttp/lifecycle_connect",
+    "go-cqhttp/lucky_king",
+    "go-cqhttp/offline_file",
+    "go-cqhttp/private_message",
+    "lagrange/friend_add",
+    "lagrange/group_admin_set",
+    "lagrange/group_message",
+    "lagrange/group_request_invite",
+    "lagrange/heartbeat",
+    "lagrange/lifecycle_connect",
+    "lagrange/poke",
+    "lagrange/private_message",
+    "llonebot/friend_recall",
+    "llonebot/friend_request",
+    "llonebot/group_ban",
+    "llonebot/group_card",
+    "llonebot/group_decrease_kick",
+    "llonebot/group_increase_approve",
+    "llonebot/group_message",
+    "llonebot/group_upload",
+    "llonebot/lifecycle_connect",
+    "napcat/essence_add",
+    "napcat/group_message",
+    "napcat/group_message_reply_image",
+    "napcat/group_msg_emoji_like",
+    "napcat/group_recall",
+    "napcat/group_request_add",
+    "napcat/heartbeat",
+    "napcat/input_status",
+    "napcat/lifecycle_connect",
+    "napcat/poke",
+    "napcat/private_message",
+];
+
+fn root() -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus")
+}
+
+fn load(case: &str) -> serde_json::Result<onebot_v11::Event> {
+    assert!(CASES.contains(&case), "{} is not in CASES", case);
+    let raw = fs::read_to_string(root().join(format!("{}.json", case))).unwrap();
+    serde_json::from_str(&raw)
+}
+
+fn parse(case: &str) -> Event {
+    let event = load(case).unwrap_or_else(|e| panic!("{}: {}", case, e));
+    parse_event(event).unwrap_or_else(|e| panic!("{}: {}", case, e))
+}
+
+/// The data of an `AnyEvent` of `type_`.
+fn any_data(case: &str, type_: &str) -> AnyEventDataObject {
+    let Event::AnyEvent(event) = parse(case) else {
+        panic!("{}: not an AnyEvent", case);
+    };
+    assert_eq!(event.r#type, type_, "{}", case);
+    event.data
+}
+
+fn downcast<T: 'static>(data: &AnyEventDataObject) -> &T {
+    data.as_any()
+        .downcast_ref()
+        .expect("the data type of the event")
+}
+
+#[test]
+fn every_case_is_listed() {
+    let mut on_disk = Vec::new();
+    for implementation in fs::read_dir(root()).unwrap() {
+        let implementation = implementation.unwrap().path();
+        if !implementation.is_dir() {
+            continue;
+        }
+        for case in fs::read_dir(&implementation).unwrap() {
+            let case = case.unwrap().path();
+            if case.extension().is_some_and(|ext| ext == "json") {
+                let case = case.strip_prefix(root()).unwrap().with_extension("");
+                on_disk.push(case.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    on_disk.sort();
+    assert_eq!(on_disk, CASES);
+}
+
+#[test]
+fn group_messages() {
+    // Case, message id, alias, role, level, segments.
+    let cases = [
+        (
+            "go-cqhttp/group_message",
+            "-1834102874",
+            Some(""),
+            Role::Member,
+            Some(""),
+            vec![MessageSegment::text("hello "), MessageSegment::emoji("178")],
+        ),
+        (
+            "lagrange/group_message",
+            "1901845212",
+            Some(""),
+            Role::Member,
+            Some("0"),
+            vec![MessageSegment::text("hello")],
+        ),
+        (
+            "llonebot/group_message",
+            "1320057",
+            Some("Alice"),
+            Role::Owner,
+            Some(""),
+            vec![MessageSegment::emoji("14"), MessageSegment::text("hi")],
+        ),
+        (
+            "napcat/group_message",
+            "-2147480001",
+            Some(""),
+            Role::Member,
+            None,
+            vec![
+                MessageSegment::at("3889000001"),
+                MessageSegment::text(" hello"),
+            ],
+        ),
+    ];
+    for (case, id, alias, role, level, segments) in cases {
+        let Event::MessageEvent(event) = parse(case) else {
+            panic!("{}: not a message", case);
+        };
+        assert_eq!(event.id, id, "{}", case);
+        assert_eq!(event.message.id, id, "{}", case);
+        assert_eq!(event.time.unwrap().timestamp(), 1729400000, "{}", case);
+        assert_eq!(event.group.unwrap().id, "123456789", "{}", case);
+        assert_eq!(event.sender.id, "1145141919", "{}", case);
+        let profile = event.sender.profile.unwrap();
+        assert_eq!(profile.nickname.as_deref(), Some("alice"), "{}", case);
+        assert_eq!(profile.level.as_deref(), level, "{}", case);
+        let group_info = event.sender.group_info.unwrap();
+        assert_eq!(group_info.alias.as_deref(), alias, "{}", case);
+        assert_eq!(group_info.role, Some(role), "{}", case);
+        assert_eq!(event.message.segments, segments, "{}", case);
+    }
+}
+
+#[test]
+fn lagrange_fills_sex_and_age_for_every_sender() {
+    let Event::MessageEvent(event) = parse("lagrange/group_message") else {
+        panic!("not a message");
+    };
+    let profile = event.sender.profile.unwrap();
+    assert_eq!(profile.sex, Some(Sex::Unknown));
+    assert_eq!(profile.age, Some(0));
+}
+
+#[test]
+fn private_messages() {
+    for (case, id) in [
+        ("go-cqhttp/private_message", "1278230473"),
+        ("lagrange/private_message", "2003195741"),
+        ("napcat/private_message", "592118"),
+    ] {
+        let Event::MessageEvent(event) = parse(case) else {
+            panic!("{}: not a message", case);
+        };
+        assert_eq!(event.id, id, "{}", case);
+        assert!(event.group.is_none(), "{}", case);
+        assert_eq!(event.sender.id, "1145141919", "{}", case);
+        assert!(event.sender.group_info.is_none(), "{}", case);
+        assert_eq!(
+            event.message.segments,
+            [MessageSegment::text("ping")],
+            "{}",
+            case
+        );
+    }
+}
+
+#[test]
+fn napcat_reply_with_image() {
+    let Event::MessageEvent(event) = parse("napcat/group_message_reply_image") else {
+        panic!("not a message");
+    };
+    assert_eq!(event.sender.group_info.unwrap().role, Some(Role::Admin));
+    let [MessageSegment::Reply { message_id }, MessageSegment::Image { file: Some(file) }] =
+        event.message.segments.as_slice()
+    else {
+        panic!("unexpected segments {:?}", event.message.segments);
+    };
+    assert_eq!(message_id, "-2147480001");
+    assert_eq!(file.name, "6B3A1C.jpg");
+    assert_eq!(
+        file.uri.as_ref().map(ToString::to_string).as_deref(),
+        Some("https://multimedia.nt.qq.com.cn/download?appid=1407&fileid=EhQ")
+    );
+    assert_eq!(file.mime, Some(mime_guess::mime::IMAGE_JPEG));
+    // NapCat's `file_size` isn't read.
+    assert_eq!(file.size, None);
+}
+
+#[test]
+fn lifecycle_and_heartbeat() {
+    for implementation in ["go-cqhttp", "lagrange", "llonebot", "napcat"] {
+        let case = format!("{}/lifecycle_connect", implementation);
+        assert!(
+            matches!(parse(&case), Event::MetaEvent(MetaEvent::ConnectEvent)),
+            "{}",
+            case
+        );
+    }
+    for (case, interval) in [
+        ("go-cqhttp/heartbeat", 5000),
+        ("lagrange/heartbeat", 5000),
+        ("napcat/heartbeat", 30000),
+    ] {
+        let data = any_data(case, "meta_event.heartbeat");
+        let heartbeat = downcast::<HeartbeatWrapper>(&data);
+        assert_eq!(heartbeat.interval, interval, "{}", case);
+        assert_eq!(heartbeat.self_id, 3889000001, "{}", case);
+    }
+}
+
+#[test]
+fn mutes() {
+    let Event::NoticeEvent(NoticeEvent::GroupMemberMuteChangeEvent(event)) =
+        parse("llonebot/group_ban")
+    else {
+        panic!("not a member mute");
+    };
+    assert_eq!(event.group.id, "123456789");
+    assert_eq!(event.user.id, "2233445566");
+    assert_eq!(event.operator.unwrap().id, "1145141919");
+    assert_eq!(
+        event.r#type,
+        MuteType::Mute {
+            duration: Some(Duration::from_secs(600))
+        }
+    );
+
+    let Event::NoticeEvent(NoticeEvent::GroupMemberMuteChangeEvent(event)) =
+        parse("go-cqhttp/group_ban_lift")
+    else {
+        panic!("not a member mute");
+    };
+    assert_eq!(event.user.id, "2233445566");
+    assert_eq!(event.r#type, MuteType::UnMute);
+}
+
+#[test]
+fn member_changes() {
+    let Event::NoticeEvent(NoticeEvent::GroupMemberDecreaseEvent(event)) =
+        parse("go-cqhttp/group_decrease_kick_me")
+    else {
+        panic!("not a member decrease");
+    };
+    assert_eq!(event.user.id, "3889000001");
+    let GroupMemberDecreaseReason::KickMe {
+        operator: Some(operator),
+    } = event.reason
+    else {
+        panic!("unexpected reason {:?}", event.reason);
+    };
+    assert_eq!(operator.id, "1145141919");
+
+    let Event::NoticeEvent(NoticeEvent::GroupMemberDecreaseEvent(event)) =
+        parse("llonebot/group_decrease_kick")
+    else {
+        panic!("not a member decrease");
+    };
+    assert_eq!(event.user.id, "2233445566");
+    assert!(matches!(
+        event.reason,
+        GroupMemberDecreaseReason::Kick { operator: Some(ref operator) } if operator.id == "1145141919"
+    ));
+
+    let Event::NoticeEvent(NoticeEvent::GroupMemberIncreseEvent(event)) =
+        parse("llonebot/group_increase_approve")
+    else {
+        panic!("not a member increase");
+    };
+    assert_eq!(event.group.id, "123456789");
+    assert_eq!(event.user.id, "2233445566");
+    assert!(matches!(
+        event.reason,
+        GroupMemberIncreseReason::Approve { operator: Some(ref operator) } if operator.id == "1145141919"
+    ));
+
+    let Event::NoticeEvent(NoticeEvent::GroupAdminChangeEvent(event)) =
+        parse("lagrange/group_admin_set")
+    else {
+        panic!("not an admin change");
+    };
+    assert_eq!(event.user.id, "1145141919");
+    assert_eq!(event.r#type, GroupAdminChangeType::Set);
+
+    let Event::NoticeEvent(NoticeEvent::GroupMemberAliasChangeEvent(event)) =
+        parse("llonebot/group_card")
+    else {
+        panic!("not an alias change");
+    };
+    assert_eq!(event.user.id, "1145141919");
+    assert_eq!(event.old_alias.as_deref(), Some("alice"));
+    assert_eq!(event.new_alias.as_deref(), Some("Alice"));
+}
+
+#[test]
+fn recalls_and_essence() {
+    for (case, group, message_id) in [
+        ("llonebot/friend_recall", None, "592118"),
+        ("napcat/group_recall", Some("123456789"), "-2147480001"),
+    ] {
+        let Event::NoticeEvent(NoticeEvent::MessageDeletedEvent(event)) = parse(case) else {
+            panic!("{}: not a recall", case);
+        };
+        assert_eq!(event.group.map(|group| group.id).as_deref(), group);
+        assert_eq!(event.user.unwrap().id, "1145141919", "{}", case);
+        assert_eq!(event.operator.unwrap().id, "1145141919", "{}", case);
+        assert_eq!(event.message.unwrap().id, message_id, "{}", case);
+    }
+
+    let Event::NoticeEvent(NoticeEvent::GroupHightLightChangeEvent(event)) =
+        parse("napcat/essence_add")
+    else {
+        panic!("not an essence change");
+    };
+    assert_eq!(event.r#type, GroupHightLightChangeType::Set);
+    assert_eq!(event.message.id, "-2147480001");
+    assert_eq!(event.sender.unwrap().id, "1145141919");
+    // NapCat's `operator_id` (2233445566) has no place in onebot_v11's event.
+    assert_eq!(event.operator.unwrap().id, "1145141919");
+}
+
+#[test]
+fn notify_notices() {
+    for case in ["go-cqhttp/group_upload", "llonebot/group_upload"] {
+        let data = any_data(case, "notice.group_upload");
+        let upload = downcast::<GroupFileUploadEventWrapper>(&data);
+        assert_eq!(upload.user_id, 1145141919, "{}", case);
+        assert_eq!(upload.file.name, "notes.txt", "{}", case);
+        assert_eq!(upload.file.size, 1024, "{}", case);
+        assert_eq!(upload.file.busid, 102, "{}", case);
+    }
+
+    for case in ["lagrange/poke", "napcat/poke"] {
+        let data = any_data(case, "notice.notify.poke");
+        let poke = downcast::<GroupPokeEventWrapper>(&data);
+        assert_eq!(poke.group_id, 123456789, "{}", case);
+        assert_eq!(poke.user_id, 1145141919, "{}", case);
+        assert_eq!(poke.target_id, 3889000001, "{}", case);
+    }
+
+    let data = any_data("go-cqhttp/honor_talkative", "notice.notify.honor");
+    let honor = downcast::<GroupMemberHonorChangeEventWrapper>(&data);
+    assert_eq!(honor.honor_type, "talkative");
+    assert_eq!(honor.user_id, 1145141919);
+
+    let data = any_data("go-cqhttp/lucky_king", "notice.notify.lucky_king");
+    let lucky_king = downcast::<GroupLuckyKingEventWrapper>(&data);
+    assert_eq!(lucky_king.user_id, 1145141919);
+    assert_eq!(lucky_king.target_id, 2233445566);
+
+    let data = any_data("napcat/input_status", "notice.notify.input_status");
+    let input = downcast::<FriendInputStatusChangeEventWrapper>(&data);
+    assert_eq!(input.user_id, 1145141919);
+    assert_eq!(input.event_type, 1);
+    assert_eq!(input.status_text, "对方正在输入...");
+}
+
+#[test]
+fn requests() {
+    for (case, id) in [
+        ("go-cqhttp/group_request_invite", "1729400000456789"),
+        ("lagrange/group_request_invite", "1729400000123"),
+    ] {
+        let Event::RequestEvent(RequestEvent::GroupInviteEvent(event)) = parse(case) else {
+            panic!("{}: not a group invite", case);
+        };
+        assert_eq!(event.id, id, "{}", case);
+        assert_eq!(event.group_id, "987654321", "{}", case);
+        assert_eq!(event.user.id, "1145141919", "{}", case);
+    }
+
+    let Event::RequestEvent(RequestEvent::GroupAddEvent(event)) = parse("napcat/group_request_add")
+    else {
+        panic!("not a group add request");
+    };
+    assert_eq!(event.id, "7431926283#123456789|2233445566");
+    assert_eq!(event.group.id, "123456789");
+    assert_eq!(event.user.id, "2233445566");
+    assert_eq!(
+        event.message.as_deref(),
+        Some("问题：来自哪里\n答案：github")
+    );
+
+    let Event::RequestEvent(RequestEvent::FriendAddEvent(event)) = parse("llonebot/friend_request")
+    else {
+        panic!("not a friend request");
+    };
+    assert_eq!(event.id, "u_xyz|1729400000");
+    assert_eq!(event.user.id, "2233445566");
+    assert_eq!(event.message.as_deref(), Some("hello"));
+
+    // Lagrange's `friend_add` notice, someone who already became a friend.
+    let Event::RequestEvent(RequestEvent::FriendAddEvent(event)) = parse("lagrange/friend_add")
+    else {
+        panic!("not a friend add");
+    };
+    assert_eq!(event.user.id, "2233445566");
+    assert_eq!(event.message, None);
+}
+
+#[test]
+fn non_standard_events_fail_to_deserialize() {
+    for (case, error) in [
+        ("go-cqhttp/offline_file", "Invalid notice_type"),
+        ("napcat/group_msg_emoji_like", "Invalid notice_type"),
+        (
+            "go-cqhttp/group_message_string_format",
+            "expected a sequence",
+        ),
+    ] {
+        let e = load(case).unwrap_err();
+        assert!(e.to_string().contains(error), "{}: {}", case, e);
+    }
+}
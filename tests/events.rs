@@ -0,0 +1,515 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{notice, text, SELF_ID, TIME};
+use onebot_v11::connect::ws_reverse::ReverseWsConfig;
+use onebot_v11_oxidebot::{
+    event::parse_event,
+    testing::{start_bot, unused_addr, MockOnebot},
+    OnebotV11ReverseWsBot, OnebotV11WsBot,
+};
+use oxidebot::{
+    event::{
+        notice::{
+            GroupAdminChangeType, GroupHightLightChangeType, GroupMemberDecreaseReason,
+            GroupMemberIncreseReason, MuteType,
+        },
+        Event, MetaEvent, NoticeEvent, RequestEvent,
+    },
+    source::{
+        message::MessageSegment,
+        user::{Role, Sex},
+    },
+};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+struct Harness {
+    mock: MockOnebot,
+    events: broadcast::Receiver<onebot_v11::Event>,
+    _bot: OnebotV11WsBot,
+}
+
+impl Harness {
+    async fn new() -> Self {
+        let (mock, bot) = common::connect().await;
+        let events = bot.subscribe().await;
+        Harness {
+            mock,
+            events,
+            _bot: bot,
+        }
+    }
+
+    /// Push `event` and return it as the bot received it.
+    async fn deliver(&mut self, event: Value) -> onebot_v11::Event {
+        self.mock.push_event(event);
+        tokio::time::timeout(Duration::from_secs(5), self.events.recv())
+            .await
+            .expect("event delivered")
+            .unwrap()
+    }
+
+    async fn parse(&mut self, event: Value) -> Event {
+        parse_event(self.deliver(event).await).unwrap()
+    }
+}
+
+fn lifecycle(sub_type: &str) -> Value {
+    json!({
+        "time": TIME,
+        "self_id": SELF_ID,
+        "post_type": "meta_event",
+        "meta_event_type": "lifecycle",
+        "sub_type": sub_type,
+    })
+}
+
+/// A group message from alice with her full member profile.
+fn group_message() -> Value {
+    let mut event = common::group_message(-12, json!([text("hello")]));
+    event["sender"] = json!({
+        "user_id": 20002,
+        "nickname": "alice",
+        "card": "Alice",
+        "sex": "female",
+        "age": 18,
+        "level": "3",
+        "role": "owner",
+    });
+    event
+}
+
+fn group_request(sub_type: &str) -> Value {
+    json!({
+        "time": TIME,
+        "self_id": SELF_ID,
+        "post_type": "request",
+        "request_type": "group",
+        "sub_type": sub_type,
+        "group_id": 10001,
+        "user_id": 20002,
+        "comment": "let me in",
+        "flag": "flag1",
+    })
+}
+
+#[tokio::test]
+async fn private_message() {
+    let mut h = Harness::new().await;
+    let event = h
+        .parse(json!({
+            "time": TIME,
+            "self_id": SELF_ID,
+            "post_type": "message",
+            "message_type": "private",
+            "sub_type": "friend",
+            "message_id": 12,
+            "user_id": 20002,
+            "message": [{ "type": "text", "data": { "text": "hello" } }],
+            "raw_message": "hello",
+            "font": 0,
+            "sender": { "user_id": 20002, "nickname": "alice", "sex": "male", "age": 20 },
+        }))
+        .await;
+    let Event::MessageEvent(event) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.id, "12");
+    assert_eq!(event.time.unwrap().timestamp(), TIME);
+    assert_eq!(event.sender.id, "20002");
+    let profile = event.sender.profile.unwrap();
+    assert_eq!(profile.nickname.as_deref(), Some("alice"));
+    assert_eq!(profile.sex, Some(Sex::Male));
+    assert_eq!(profile.age, Some(20));
+    assert!(event.group.is_none());
+    assert_eq!(event.message.segments, vec![MessageSegment::text("hello")]);
+}
+
+#[tokio::test]
+async fn group_message_event() {
+    let mut h = Harness::new().await;
+    let event = h.parse(group_message()).await;
+    let Event::MessageEvent(event) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.id, "-12");
+    assert_eq!(event.group.unwrap().id, "10001");
+    assert_eq!(event.sender.id, "20002");
+    let group_info = event.sender.group_info.unwrap();
+    assert_eq!(group_info.alias.as_deref(), Some("Alice"));
+    assert_eq!(group_info.role, Some(Role::Owner));
+    assert_eq!(group_info.level.as_deref(), Some("3"));
+    assert_eq!(event.message.segments, vec![MessageSegment::text("hello")]);
+}
+
+#[tokio::test]
+async fn lifecycle_events() {
+    let mut h = Harness::new().await;
+    for sub_type in ["connect", "enable"] {
+        assert!(matches!(
+            h.parse(lifecycle(sub_type)).await,
+            Event::MetaEvent(MetaEvent::ConnectEvent)
+        ));
+    }
+    assert!(matches!(
+        h.parse(lifecycle("disable")).await,
+        Event::MetaEvent(MetaEvent::DisconnectEvent)
+    ));
+    assert!(parse_event(h.deliver(lifecycle("reboot")).await).is_err());
+}
+
+#[tokio::test]
+async fn heartbeat() {
+    let mut h = Harness::new().await;
+    let event = h
+        .parse(json!({
+            "time": TIME,
+            "self_id": SELF_ID,
+            "post_type": "meta_event",
+            "meta_event_type": "heartbeat",
+            "status": { "online": true, "good": true },
+            "interval": 5000,
+        }))
+        .await;
+    let Event::AnyEvent(event) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.r#type, "meta_event.heartbeat");
+}
+
+#[tokio::test]
+async fn notify_events() {
+    let mut h = Harness::new().await;
+    let cases = [
+        (
+            notice(
+                "group_upload",
+                json!({
+                    "group_id": 10001,
+                    "user_id": 20002,
+                    "file": { "id": "/f1", "name": "a.txt", "size": 12, "busid": 102 },
+                }),
+            ),
+            "notice.group_upload",
+        ),
+        (
+            notice(
+                "notify",
+                json!({ "sub_type": "poke", "group_id": 10001, "user_id": 20002, "target_id": SELF_ID }),
+            ),
+            "notice.notify.poke",
+        ),
+        (
+            notice(
+                "notify",
+                json!({ "sub_type": "lucky_king", "group_id": 10001, "user_id": 20002, "target_id": 20003 }),
+            ),
+            "notice.notify.lucky_king",
+        ),
+        (
+            notice(
+                "notify",
+                json!({ "sub_type": "honor", "group_id": 10001, "honor_type": "talkative", "user_id": 20002 }),
+            ),
+            "notice.notify.honor",
+        ),
+        (
+            notice(
+                "notify",
+                json!({ "sub_type": "input_status", "status_text": "typing", "event_type": 1, "user_id": 20002 }),
+            ),
+            "notice.notify.input_status",
+        ),
+    ];
+    for (event, r#type) in cases {
+        match h.parse(event).await {
+            Event::AnyEvent(event) => assert_eq!(event.r#type, r#type),
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
+
+#[tokio::test]
+async fn group_admin_change() {
+    let mut h = Harness::new().await;
+    for (sub_type, expected) in [
+        ("set", GroupAdminChangeType::Set),
+        ("unset", GroupAdminChangeType::Unset),
+    ] {
+        let event = h
+            .parse(notice(
+                "group_admin",
+                json!({ "sub_type": sub_type, "group_id": 10001, "user_id": 20002 }),
+            ))
+            .await;
+        let Event::NoticeEvent(NoticeEvent::GroupAdminChangeEvent(event)) = event else {
+            panic!("unexpected event {:?}", event);
+        };
+        assert_eq!(event.group.id, "10001");
+        assert_eq!(event.user.id, "20002");
+        assert_eq!(
+            std::mem::discriminant(&event.r#type),
+            std::mem::discriminant(&expected)
+        );
+    }
+}
+
+#[tokio::test]
+async fn group_member_decrease() {
+    let mut h = Harness::new().await;
+    for sub_type in ["leave", "kick", "kick_me"] {
+        let event = h
+            .parse(notice(
+                "group_decrease",
+                json!({ "sub_type": sub_type, "group_id": 10001, "operator_id": 20003, "user_id": 20002 }),
+            ))
+            .await;
+        let Event::NoticeEvent(NoticeEvent::GroupMemberDecreaseEvent(event)) = event else {
+            panic!("unexpected event {:?}", event);
+        };
+        assert_eq!(event.group.id, "10001");
+        assert_eq!(event.user.id, "20002");
+        match (sub_type, event.reason) {
+            ("leave", GroupMemberDecreaseReason::Leave) => {}
+            ("kick", GroupMemberDecreaseReason::Kick { operator })
+            | ("kick_me", GroupMemberDecreaseReason::KickMe { operator }) => {
+                assert_eq!(operator.unwrap().id, "20003")
+            }
+            (sub_type, reason) => panic!("{} mapped to {:?}", sub_type, reason),
+        }
+    }
+}
+
+#[tokio::test]
+async fn group_member_increase() {
+    let mut h = Harness::new().await;
+    for sub_type in ["approve", "invite"] {
+        let event = h
+            .parse(notice(
+                "group_increase",
+                json!({ "sub_type": sub_type, "group_id": 10001, "operator_id": 20003, "user_id": 20002 }),
+            ))
+            .await;
+        let Event::NoticeEvent(NoticeEvent::GroupMemberIncreseEvent(event)) = event else {
+            panic!("unexpected event {:?}", event);
+        };
+        assert_eq!(event.user.id, "20002");
+        match (sub_type, event.reason) {
+            ("approve", GroupMemberIncreseReason::Approve { operator })
+            | ("invite", GroupMemberIncreseReason::Invite { operator, .. }) => {
+                assert_eq!(operator.unwrap().id, "20003")
+            }
+            (sub_type, reason) => panic!("{} mapped to {:?}", sub_type, reason),
+        }
+    }
+}
+
+#[tokio::test]
+async fn group_ban() {
+    let mut h = Harness::new().await;
+    let ban = |sub_type: &str, duration: u64| {
+        notice(
+            "group_ban",
+            json!({
+                "sub_type": sub_type,
+                "group_id": 10001,
+                "operator_id": 20003,
+                "user_id": 20002,
+                "duration": duration,
+            }),
+        )
+    };
+    let event = h.parse(ban("ban", 600)).await;
+    let Event::NoticeEvent(NoticeEvent::GroupMemberMuteChangeEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    let operator = event.operator.unwrap();
+    assert_eq!(operator.id, "20003");
+    // Notices only carry ids, the avatar is all the profile they get.
+    assert_eq!(
+        operator.profile.unwrap().avatar.unwrap().to_string(),
+        "https://q1.qlogo.cn/g?b=qq&nk=20003&s=640"
+    );
+    assert!(event.user.profile.unwrap().nickname.is_none());
+    assert!(matches!(
+        event.r#type,
+        MuteType::Mute { duration: Some(d) } if d == Duration::from_secs(600)
+    ));
+    let event = h.parse(ban("lift_ban", 0)).await;
+    assert!(matches!(
+        event,
+        Event::NoticeEvent(NoticeEvent::GroupMemberMuteChangeEvent(event))
+            if matches!(event.r#type, MuteType::UnMute)
+    ));
+}
+
+#[tokio::test]
+async fn friend_add() {
+    let mut h = Harness::new().await;
+    let event = h
+        .parse(notice("friend_add", json!({ "user_id": 20002 })))
+        .await;
+    let Event::RequestEvent(RequestEvent::FriendAddEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.user.id, "20002");
+    assert!(event.message.is_none());
+}
+
+#[tokio::test]
+async fn message_recall() {
+    let mut h = Harness::new().await;
+    let event = h
+        .parse(notice(
+            "group_recall",
+            json!({ "group_id": 10001, "user_id": 20002, "operator_id": 20003, "message_id": -12 }),
+        ))
+        .await;
+    let Event::NoticeEvent(NoticeEvent::MessageDeletedEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.group.unwrap().id, "10001");
+    assert_eq!(event.user.unwrap().id, "20002");
+    assert_eq!(event.operator.unwrap().id, "20003");
+    assert_eq!(event.message.unwrap().id, "-12");
+
+    let event = h
+        .parse(notice(
+            "friend_recall",
+            json!({ "user_id": 20002, "message_id": 12 }),
+        ))
+        .await;
+    let Event::NoticeEvent(NoticeEvent::MessageDeletedEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert!(event.group.is_none());
+    assert_eq!(event.operator.unwrap().id, "20002");
+    assert_eq!(event.message.unwrap().id, "12");
+}
+
+#[tokio::test]
+async fn essence() {
+    let mut h = Harness::new().await;
+    for (sub_type, expected) in [
+        ("add", GroupHightLightChangeType::Set),
+        ("delete", GroupHightLightChangeType::Unset),
+    ] {
+        let event = h
+            .parse(notice(
+                "essence",
+                json!({
+                    "sub_type": sub_type,
+                    "group_id": 10001,
+                    "user_id": 20003,
+                    "message_id": 12,
+                    "sender_id": 20002,
+                }),
+            ))
+            .await;
+        let Event::NoticeEvent(NoticeEvent::GroupHightLightChangeEvent(event)) = event else {
+            panic!("unexpected event {:?}", event);
+        };
+        assert_eq!(
+            std::mem::discriminant(&event.r#type),
+            std::mem::discriminant(&expected)
+        );
+        assert_eq!(event.message.id, "12");
+        assert_eq!(event.sender.unwrap().id, "20002");
+        assert_eq!(event.operator.unwrap().id, "20003");
+    }
+}
+
+#[tokio::test]
+async fn group_card_change() {
+    let mut h = Harness::new().await;
+    let event = h
+        .parse(notice(
+            "group_card",
+            json!({ "group_id": 10001, "user_id": 20002, "card_new": "Alice", "card_old": "alice" }),
+        ))
+        .await;
+    let Event::NoticeEvent(NoticeEvent::GroupMemberAliasChangeEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.user.id, "20002");
+    assert_eq!(event.old_alias.as_deref(), Some("alice"));
+    assert_eq!(event.new_alias.as_deref(), Some("Alice"));
+}
+
+#[tokio::test]
+async fn friend_request() {
+    let mut h = Harness::new().await;
+    let event = h
+        .parse(json!({
+            "time": TIME,
+            "self_id": SELF_ID,
+            "post_type": "request",
+            "request_type": "friend",
+            "user_id": 20002,
+            "comment": "hi",
+            "flag": "flag0",
+        }))
+        .await;
+    let Event::RequestEvent(RequestEvent::FriendAddEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.id, "flag0");
+    assert_eq!(event.user.id, "20002");
+    assert_eq!(event.message.as_deref(), Some("hi"));
+}
+
+#[tokio::test]
+async fn group_requests() {
+    let mut h = Harness::new().await;
+    let event = h.parse(group_request("add")).await;
+    let Event::RequestEvent(RequestEvent::GroupAddEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.id, "flag1");
+    assert_eq!(event.group.id, "10001");
+    assert_eq!(event.message.as_deref(), Some("let me in"));
+
+    let event = h.parse(group_request("invite")).await;
+    let Event::RequestEvent(RequestEvent::GroupInviteEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.id, "flag1");
+    assert_eq!(event.group_id, "10001");
+
+    assert!(parse_event(h.deliver(group_request("other")).await).is_err());
+}
+
+#[tokio::test]
+async fn reverse_ws_events_carry_their_account() {
+    let addr = unused_addr();
+    let config = || ReverseWsConfig {
+        host: addr.ip().to_string(),
+        port: addr.port(),
+        suffix: "/onebot/v11/ws".to_string(),
+        access_token: None,
+    };
+    let bot = OnebotV11ReverseWsBot::try_new_disconnected(config())
+        .await
+        .unwrap();
+    let mut matchers = start_bot(&bot).await;
+
+    let mock = MockOnebot::new();
+    mock.connect_reverse(&config(), SELF_ID).await.unwrap();
+    let connected = matchers.recv().await.unwrap();
+    assert!(matches!(
+        *connected.event,
+        Event::MetaEvent(MetaEvent::ConnectEvent)
+    ));
+
+    mock.push_event(group_message());
+    let matcher = tokio::time::timeout(Duration::from_secs(5), matchers.recv())
+        .await
+        .expect("event delivered")
+        .unwrap();
+    assert_eq!(
+        matcher.bot.bot_info().await.id.as_deref(),
+        Some(SELF_ID.to_string().as_str())
+    );
+}
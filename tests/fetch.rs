@@ -0,0 +1,238 @@
+mod common;
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use onebot_v11_oxidebot::{
+    media::{sniff_mime, FetchConfig, MediaServer, MediaServerConfig},
+    testing::MockOnebot,
+    OnebotV11WsBot,
+};
+use oxidebot::source::message::File;
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n image";
+
+async fn connect(fetch: FetchConfig) -> (MockOnebot, OnebotV11WsBot) {
+    let (mock, bot) = common::connect().await;
+    (mock, bot.with_fetch(fetch))
+}
+
+async fn media_server() -> std::sync::Arc<MediaServer> {
+    MediaServer::new(MediaServerConfig {
+        port: 0,
+        ttl: Duration::from_secs(60),
+        ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+/// A received image as `cast_segment` makes it, named like QQ names them.
+fn received(name: &str, url: &str) -> File {
+    File {
+        name: name.to_string(),
+        uri: Some(url.parse().unwrap()),
+        mime: mime_guess::from_path(name).first(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn mime_is_sniffed_from_content() {
+    assert_eq!(sniff_mime(PNG), Some(mime_guess::mime::IMAGE_PNG));
+    assert_eq!(
+        sniff_mime(b"\x02#!SILK_V3"),
+        Some("audio/silk".parse().unwrap())
+    );
+    assert_eq!(
+        sniff_mime(b"\0\0\0\x18ftypmp42"),
+        Some("video/mp4".parse().unwrap())
+    );
+    assert_eq!(sniff_mime(b"plain text"), None);
+    assert_eq!(sniff_mime(b""), None);
+}
+
+#[tokio::test]
+async fn files_are_downloaded_from_their_url() {
+    let server = media_server().await;
+    let published = server.publish_bytes("", PNG);
+    let (mock, bot) = connect(FetchConfig::default()).await;
+    let fetched = bot
+        .fetch_file(&received("A1B2.jpg", published.url()))
+        .await
+        .unwrap();
+    assert_eq!(fetched.bytes, PNG);
+    assert_eq!(fetched.file.name, "A1B2.jpg");
+    assert_eq!(fetched.file.size, Some(PNG.len() as u64));
+    assert_eq!(fetched.file.mime, Some(mime_guess::mime::IMAGE_PNG));
+    assert!(mock.calls().is_empty());
+
+    let (_, bot) = connect(FetchConfig {
+        max_size: 4,
+        ..Default::default()
+    })
+    .await;
+    let error = bot
+        .fetch_file(&File {
+            base64: Some(STANDARD.encode(PNG)),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("over the limit of 4"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn expired_urls_fall_back_to_the_api() {
+    let server = media_server().await;
+    let expired = server.publish_bytes("", PNG).url().to_string();
+    let (mock, bot) = connect(FetchConfig::default()).await;
+
+    mock.respond_json(
+        "get_image",
+        json!({ "status": "ok", "retcode": 0, "data": {
+            "file": "/nowhere/A1B2.jpg",
+            "base64": STANDARD.encode(PNG),
+        } }),
+    );
+    let fetched = bot
+        .fetch_file(&received("A1B2.jpg", &expired))
+        .await
+        .unwrap();
+    assert_eq!(fetched.bytes, PNG);
+    let calls = mock.take_calls();
+    assert_eq!(calls[0].action, "get_image");
+    assert_eq!(calls[0].params["file"], "A1B2.jpg");
+
+    // Files go through `get_file` by their id, and without a url at all.
+    let published = server.publish_bytes("", &b"%PDF-1.7"[..]);
+    mock.respond_json(
+        "get_file",
+        json!({ "status": "ok", "retcode": 0, "data": { "file": "", "url": published.url() } }),
+    );
+    let fetched = bot
+        .fetch_file(&File {
+            id: Some("/abc-123".to_string()),
+            name: "report".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(fetched.bytes, b"%PDF-1.7");
+    assert_eq!(fetched.file.mime, Some("application/pdf".parse().unwrap()));
+    let calls = mock.take_calls();
+    assert_eq!(calls[0].action, "get_file");
+    assert_eq!(calls[0].params["file_id"], "/abc-123");
+
+    mock.respond_failed("get_record", 1200, "file not found");
+    let error = bot
+        .fetch_file(&received("voice.amr", &expired))
+        .await
+        .unwrap_err();
+    assert_eq!(error.retcode(), Some(1200));
+    assert_eq!(mock.take_calls()[0].params["out_format"], "mp3");
+}
+
+#[tokio::test]
+async fn fetched_files_are_cached_up_to_the_cap() {
+    let cache_dir =
+        std::env::temp_dir().join(format!("onebot_v11_oxidebot-fetch-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&cache_dir);
+    let (mock, bot) = connect(FetchConfig {
+        cache_dir: Some(cache_dir.clone()),
+        cache_size: PNG.len() as u64 + 4,
+        ..Default::default()
+    })
+    .await;
+    let server = media_server().await;
+    let cached = || {
+        std::fs::read_dir(cache_dir.join("fetched"))
+            .unwrap()
+            .count()
+    };
+    // Whatever else is in the directory is left alone.
+    std::fs::create_dir_all(cache_dir.join("fetched")).unwrap();
+    std::fs::write(cache_dir.join("fetched").join("notes.txt"), PNG).unwrap();
+    std::fs::write(cache_dir.join("unrelated"), PNG).unwrap();
+
+    let published = server.publish_bytes("", PNG);
+    let first = received("A1B2.png", published.url());
+    bot.fetch_file(&first).await.unwrap();
+    drop(published);
+    // The url is gone, and the api isn't asked either.
+    assert_eq!(bot.fetch_file(&first).await.unwrap().bytes, PNG);
+    assert!(mock.calls().is_empty());
+
+    let published = server.publish_bytes("", &b"GIF89a gif"[..]);
+    bot.fetch_file(&received("C3D4.gif", published.url()))
+        .await
+        .unwrap();
+    assert_eq!(cached(), 2);
+    mock.respond_failed("get_image", 1200, "file not found");
+    assert!(bot.fetch_file(&first).await.is_err());
+    assert!(cache_dir.join("unrelated").exists());
+
+    // Names that aren't hashes only count with the url.
+    let published = server.publish_bytes("", &b"GIF89a one"[..]);
+    let one = bot
+        .fetch_file(&received("image.gif", published.url()))
+        .await
+        .unwrap();
+    let published = server.publish_bytes("", &b"GIF89a two"[..]);
+    let two = bot
+        .fetch_file(&received("image.gif", published.url()))
+        .await
+        .unwrap();
+    assert_ne!(one.bytes, two.bytes);
+
+    // Nor is base64 content cached.
+    std::fs::remove_dir_all(cache_dir.join("fetched")).unwrap();
+    bot.fetch_file(&File {
+        name: "0123456789abcdef0123456789abcdef.gif".to_string(),
+        base64: Some(STANDARD.encode(b"GIF89a")),
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+    assert!(!cache_dir.join("fetched").exists());
+    std::fs::remove_dir_all(&cache_dir).unwrap();
+}
+
+#[tokio::test]
+async fn downloads_without_a_length_are_capped() {
+    // A server answering with a body of unknown length, ended by closing the connection.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).await;
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n0123456789")
+                .await;
+        }
+    });
+    let url = format!("http://{}/a.png", addr);
+    let (_, bot) = connect(FetchConfig {
+        max_size: 4,
+        ..Default::default()
+    })
+    .await;
+    let error = bot.fetch_file(&received("", &url)).await.unwrap_err();
+    assert!(
+        format!("{:#}", anyhow::Error::from(error)).contains("over the limit of 4"),
+        "download wasn't capped"
+    );
+
+    let (_, bot) = connect(FetchConfig::default()).await;
+    assert_eq!(
+        bot.fetch_file(&received("", &url)).await.unwrap().bytes,
+        b"0123456789"
+    );
+}
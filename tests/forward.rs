@@ -0,0 +1,274 @@
+mod common;
+
+use common::{private_message, text};
+use onebot_v11::api::resp::{ApiRespData, SendGroupMsgResponse};
+use onebot_v11_oxidebot::{
+    forward::{ForwardConfig, ForwardSendApi},
+    testing::{start_bot, MockOnebot},
+    OnebotV11WsBot,
+};
+use oxidebot::{
+    api::{payload::SendMessageTarget, CallApiTrait},
+    event::Event,
+    source::{
+        message::{Message, MessageSegment},
+        user::{User, UserProfile},
+    },
+};
+use serde_json::{json, Value};
+
+async fn connect(forwards: ForwardConfig) -> (MockOnebot, OnebotV11WsBot) {
+    let (mock, bot) = common::connect().await;
+    (mock, bot.with_forwards(forwards))
+}
+
+fn ok(data: Value) -> Value {
+    json!({ "status": "ok", "retcode": 0, "data": data })
+}
+
+fn node_text(node: &MessageSegment) -> (String, Option<String>, Vec<MessageSegment>) {
+    let MessageSegment::ForwardCustomNode { user, message } = node else {
+        panic!("not a node: {:?}", node);
+    };
+    let user = user.clone().unwrap();
+    (
+        user.id,
+        user.profile.unwrap().nickname,
+        message.segments.clone(),
+    )
+}
+
+#[tokio::test]
+async fn nodes_of_every_shape() {
+    let (mock, bot) = connect(ForwardConfig::default()).await;
+    // The standard shape, nesting a forward by id.
+    mock.respond_json(
+        "get_forward_msg",
+        ok(json!({ "message": [
+            { "type": "node", "data": { "user_id": "20002", "nickname": "alice", "content": [text("look")] } },
+            { "type": "node", "data": { "user_id": 20003, "nickname": "bob", "content": [
+                { "type": "forward", "data": { "id": "inner" } },
+            ] } },
+        ] })),
+    );
+    // go-cqhttp's shape.
+    mock.respond_json(
+        "get_forward_msg",
+        ok(json!({ "messages": [
+            { "sender": { "user_id": 20004, "nickname": "carol" }, "time": 1700000000, "content": "nested" },
+        ] })),
+    );
+
+    let nodes = bot.get_forward_message("outer").await.unwrap();
+    assert_eq!(nodes.len(), 2);
+    assert_eq!(
+        node_text(&nodes[0]),
+        (
+            "20002".to_string(),
+            Some("alice".to_string()),
+            vec![MessageSegment::text("look")]
+        )
+    );
+    let (id, _, segments) = node_text(&nodes[1]);
+    assert_eq!(id, "20003");
+    assert_eq!(segments.len(), 1);
+    assert_eq!(
+        node_text(&segments[0]),
+        (
+            "20004".to_string(),
+            Some("carol".to_string()),
+            vec![MessageSegment::text("nested")]
+        )
+    );
+    let calls = mock.take_calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[1].params["id"], "inner");
+}
+
+#[tokio::test]
+async fn nested_forwards_stop_at_max_depth() {
+    let (mock, bot) = connect(ForwardConfig {
+        max_depth: 1,
+        ..Default::default()
+    })
+    .await;
+    // NapCat's shape, sending the nested nodes along.
+    let napcat_node = |id: i64, content: Value| {
+        json!({
+            "message_id": id,
+            "user_id": 20002,
+            "sender": { "user_id": 20002, "nickname": "alice", "card": "" },
+            "message": content,
+        })
+    };
+    mock.respond_json(
+        "get_forward_msg",
+        ok(json!({ "messages": [napcat_node(1, json!([
+            { "type": "forward", "data": { "id": "middle", "content": [napcat_node(2, json!([
+                { "type": "forward", "data": { "id": "deepest" } },
+            ]))] } },
+        ]))] })),
+    );
+
+    let nodes = bot.get_forward_message("outer").await.unwrap();
+    let MessageSegment::ForwardCustomNode { message, .. } = &nodes[0] else {
+        panic!("not a node: {:?}", nodes[0]);
+    };
+    assert_eq!(message.id, "1");
+    let MessageSegment::ForwardCustomNode { message, .. } = &message.segments[0] else {
+        panic!("not a node: {:?}", message.segments[0]);
+    };
+    assert_eq!(
+        *message,
+        Message {
+            id: "2".to_string(),
+            segments: vec![MessageSegment::ForwardNode {
+                message_id: "deepest".to_string()
+            }],
+        }
+    );
+    assert_eq!(mock.take_calls().len(), 1);
+}
+
+#[tokio::test]
+async fn incoming_forwards_are_expanded() {
+    let (mock, bot) = connect(ForwardConfig {
+        expand_incoming: true,
+        ..Default::default()
+    })
+    .await;
+    let mut matchers = start_bot(&bot).await;
+    mock.respond_json(
+        "get_forward_msg",
+        ok(json!({ "message": [
+            { "type": "node", "data": { "user_id": "20003", "nickname": "bob", "content": [text("inside")] } },
+        ] })),
+    );
+
+    let matcher = mock
+        .deliver(
+            &mut matchers,
+            private_message(
+                12,
+                json!([{ "type": "forward", "data": { "id": "outer" } }]),
+            ),
+        )
+        .await;
+    let Event::MessageEvent(event) = matcher.event.as_ref() else {
+        panic!("unexpected event {:?}", matcher.event);
+    };
+    assert_eq!(event.message.segments.len(), 1);
+    assert_eq!(
+        node_text(&event.message.segments[0]),
+        (
+            "20003".to_string(),
+            Some("bob".to_string()),
+            vec![MessageSegment::text("inside")]
+        )
+    );
+}
+
+fn custom_node(user_id: &str, nickname: &str, text: &str) -> MessageSegment {
+    MessageSegment::ForwardCustomNode {
+        user: Some(User {
+            id: user_id.to_string(),
+            profile: Some(UserProfile {
+                nickname: Some(nickname.to_string()),
+                ..Default::default()
+            }),
+            group_info: None,
+        }),
+        message: Message {
+            id: String::new(),
+            segments: vec![MessageSegment::text(text)],
+        },
+    }
+}
+
+fn sent_ids(sent: Vec<oxidebot::api::SendMessageResponse>) -> Vec<String> {
+    sent.into_iter().map(|sent| sent.sent_message_id).collect()
+}
+
+#[tokio::test]
+async fn nodes_are_sent_as_a_forward() {
+    let (mock, bot) = connect(ForwardConfig::default()).await;
+    mock.respond_json(
+        "send_group_forward_msg",
+        ok(json!({ "message_id": 5, "res_id": "abc" })),
+    );
+    let sent = bot
+        .send_message(
+            vec![
+                custom_node("20002", "alice", "first result"),
+                MessageSegment::ForwardNode {
+                    message_id: "7".to_string(),
+                },
+            ],
+            SendMessageTarget::Group("10001".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(sent_ids(sent), ["5"]);
+    let calls = mock.take_calls();
+    assert_eq!(calls[0].action, "send_group_forward_msg");
+    assert_eq!(calls[0].params["group_id"], 10001);
+    assert_eq!(calls[0].params["messages"].as_array().unwrap().len(), 2);
+    assert_eq!(calls[0].params["messages"][0]["type"], "node");
+    assert_eq!(calls[0].params["messages"][0]["data"]["name"], "alice");
+
+    // The res_id isn't a message id, only the forward api answers with it.
+    mock.respond_json(
+        "send_group_forward_msg",
+        ok(json!({ "message_id": 6, "res_id": "def" })),
+    );
+    let sent = bot
+        .send_forward_message(
+            vec![custom_node("20002", "alice", "first result")],
+            SendMessageTarget::Group("10001".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(sent.message_id, "6");
+    assert_eq!(sent.res_id.as_deref(), Some("def"));
+    mock.take_calls();
+
+    // Anything besides nodes is an ordinary message.
+    mock.respond_ok(
+        "send_group_msg",
+        ApiRespData::SendGroupMsgResponse(SendGroupMsgResponse { message_id: 8 }),
+    );
+    bot.send_message(
+        vec![
+            MessageSegment::text("results:"),
+            custom_node("20002", "alice", "first result"),
+        ],
+        SendMessageTarget::Group("10001".to_string()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(mock.take_calls()[0].action, "send_group_msg");
+}
+
+#[tokio::test]
+async fn forwards_through_send_msg() {
+    let (mock, bot) = connect(ForwardConfig {
+        send: ForwardSendApi::SendMsg,
+        ..Default::default()
+    })
+    .await;
+    mock.respond_json("send_msg", ok(json!({ "message_id": 6 })));
+    let sent = bot
+        .send_forward_message(
+            vec![custom_node("20002", "alice", "first result")],
+            SendMessageTarget::Private("20003".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(sent.message_id, "6");
+    assert_eq!(sent.res_id, None);
+    let calls = mock.take_calls();
+    assert_eq!(calls[0].action, "send_msg");
+    assert_eq!(calls[0].params["message_type"], "private");
+    assert_eq!(calls[0].params["user_id"], 20003);
+    assert_eq!(calls[0].params["message"][0]["type"], "node");
+}
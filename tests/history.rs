@@ -0,0 +1,252 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use common::{group_message, private_message, text};
+use onebot_v11::{
+    api::resp::{ApiRespData, GetLoginInfoResponse, SendGroupMsgResponse},
+    connect::ws::WsConfig,
+};
+use onebot_v11_oxidebot::{
+    history::{HistoryBackend, HistoryQuery, MemoryHistory, SqliteHistory, StoredMessage},
+    testing::{start_bot, MockOnebot},
+    OnebotV11WsBot,
+};
+use oxidebot::{
+    api::{payload::SendMessageTarget, CallApiTrait},
+    source::message::MessageSegment,
+};
+use serde_json::json;
+
+const SELF_ID: &str = "30003";
+
+fn at(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(1700000000 + secs, 0).unwrap()
+}
+
+fn stored(id: &str, group_id: Option<&str>, user_id: &str, secs: i64, text: &str) -> StoredMessage {
+    StoredMessage {
+        self_id: SELF_ID.to_string(),
+        id: id.to_string(),
+        group_id: group_id.map(str::to_string),
+        peer_id: group_id.is_none().then(|| user_id.to_string()),
+        user_id: user_id.to_string(),
+        nickname: Some("alice".to_string()),
+        time: at(secs),
+        message: vec![
+            serde_json::from_value(json!({ "type": "text", "data": { "text": text } })).unwrap(),
+        ],
+        outgoing: false,
+    }
+}
+
+async fn ids(backend: &dyn HistoryBackend, query: HistoryQuery) -> Vec<String> {
+    backend
+        .query(&query)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|message| message.id)
+        .collect()
+}
+
+async fn check_backend(backend: &dyn HistoryBackend) {
+    for message in [
+        stored("1", Some("10001"), "20002", 0, "good morning"),
+        stored("2", Some("10001"), "20003", 60, "morning 100%"),
+        stored("3", None, "20002", 120, "hi there"),
+        stored("4", Some("10002"), "20002", 180, "Morning"),
+    ] {
+        backend.save(message).await.unwrap();
+    }
+    let query = |f: fn(&mut HistoryQuery)| {
+        let mut query = HistoryQuery::default();
+        f(&mut query);
+        query
+    };
+
+    assert_eq!(
+        ids(backend, HistoryQuery::default()).await,
+        ["4", "3", "2", "1"]
+    );
+    assert_eq!(
+        ids(backend, query(|q| q.group_id = Some("10001".into()))).await,
+        ["2", "1"]
+    );
+    assert_eq!(
+        ids(backend, query(|q| q.user_id = Some("20002".into()))).await,
+        ["4", "3", "1"]
+    );
+    assert_eq!(
+        ids(backend, query(|q| q.peer_id = Some("20002".into()))).await,
+        ["3"]
+    );
+    assert_eq!(
+        ids(
+            backend,
+            query(|q| {
+                q.since = Some(at(60));
+                q.until = Some(at(180));
+            })
+        )
+        .await,
+        ["3", "2"]
+    );
+    assert_eq!(
+        ids(backend, query(|q| q.keyword = Some("morning".into()))).await,
+        ["2", "1"]
+    );
+    assert_eq!(
+        ids(backend, query(|q| q.keyword = Some("0%".into()))).await,
+        ["2"]
+    );
+    assert_eq!(ids(backend, query(|q| q.limit = Some(1))).await, ["4"]);
+
+    let message = backend.get(SELF_ID, "3").await.unwrap().unwrap();
+    assert_eq!(message.segments(), vec![MessageSegment::text("hi there")]);
+    assert_eq!(message, stored("3", None, "20002", 120, "hi there"));
+    assert!(backend.get(SELF_ID, "5").await.unwrap().is_none());
+
+    backend
+        .save(stored("3", None, "20002", 120, "edited"))
+        .await
+        .unwrap();
+    assert_eq!(
+        backend.get(SELF_ID, "3").await.unwrap().unwrap().text(),
+        "edited"
+    );
+    assert_eq!(ids(backend, HistoryQuery::default()).await.len(), 4);
+
+    // Another account's message with the same id is kept apart.
+    let other = StoredMessage {
+        self_id: "30004".to_string(),
+        ..stored("3", None, "20002", 240, "other account")
+    };
+    backend.save(other.clone()).await.unwrap();
+    assert_eq!(backend.get("30004", "3").await.unwrap(), Some(other));
+    assert_eq!(
+        backend.get(SELF_ID, "3").await.unwrap().unwrap().text(),
+        "edited"
+    );
+    assert_eq!(
+        ids(backend, query(|q| q.self_id = Some(SELF_ID.into()))).await,
+        ["4", "3", "2", "1"]
+    );
+}
+
+#[tokio::test]
+async fn memory_backend() {
+    check_backend(&MemoryHistory::new()).await;
+
+    let bounded = MemoryHistory::bounded(2);
+    for id in ["1", "2", "3"] {
+        bounded
+            .save(stored(id, None, "20002", 0, id))
+            .await
+            .unwrap();
+    }
+    assert!(bounded.get(SELF_ID, "1").await.unwrap().is_none());
+    // Saving a message again makes it the newest.
+    bounded
+        .save(stored("2", None, "20002", 0, "2"))
+        .await
+        .unwrap();
+    bounded
+        .save(stored("4", None, "20002", 0, "4"))
+        .await
+        .unwrap();
+    assert!(bounded.get(SELF_ID, "3").await.unwrap().is_none());
+    assert_eq!(ids(&bounded, HistoryQuery::default()).await, ["4", "2"]);
+}
+
+#[tokio::test]
+async fn sqlite_backend() {
+    check_backend(&SqliteHistory::open_in_memory().unwrap()).await;
+
+    let path = std::env::temp_dir().join(format!(
+        "onebot_v11_oxidebot-history-{}.sqlite",
+        std::process::id()
+    ));
+    SqliteHistory::open(&path)
+        .unwrap()
+        .save(stored("1", Some("10001"), "20002", 0, "kept"))
+        .await
+        .unwrap();
+    let reopened = SqliteHistory::open(&path).unwrap();
+    assert_eq!(
+        reopened.get(SELF_ID, "1").await.unwrap().unwrap().text(),
+        "kept"
+    );
+    drop(reopened);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn bot_saves_received_and_sent_messages() {
+    let mock = MockOnebot::new();
+    let config = WsConfig {
+        bot_id: Some(SELF_ID.to_string()),
+        ..mock.serve_ws().await.unwrap()
+    };
+    let bot = OnebotV11WsBot::try_new(config)
+        .await
+        .unwrap()
+        .with_history(MemoryHistory::new());
+    mock.wait_connected().await;
+    let mut matchers = start_bot(&bot).await;
+
+    mock.deliver(&mut matchers, group_message(-12, json!([text("hello")])))
+        .await;
+
+    mock.respond_ok(
+        "send_group_msg",
+        ApiRespData::SendGroupMsgResponse(SendGroupMsgResponse { message_id: 99 }),
+    );
+    bot.send_message(
+        vec![MessageSegment::text("hi alice")],
+        SendMessageTarget::Group("10001".to_string()),
+    )
+    .await
+    .unwrap();
+    mock.take_calls();
+
+    let history = bot.history().unwrap();
+    let sent = history.get(SELF_ID, "99").await.unwrap().unwrap();
+    assert!(sent.outgoing);
+    assert_eq!(sent.group_id.as_deref(), Some("10001"));
+    assert_eq!(sent.text(), "hi alice");
+
+    let detail = bot.get_message_detail("-12".to_string()).await.unwrap();
+    assert_eq!(detail.message, vec![MessageSegment::text("hello")]);
+    let sender = detail.sender.unwrap();
+    assert_eq!(sender.id, "20002");
+    let profile = sender.profile.unwrap();
+    assert_eq!(profile.nickname.as_deref(), Some("alice"));
+    assert!(profile.avatar.is_some());
+    assert!(mock.calls().is_empty());
+}
+
+#[tokio::test]
+async fn bot_without_a_bot_id_keeps_messages_under_the_event_account() {
+    let (mock, bot) = common::connect().await;
+    let bot = bot.with_history(MemoryHistory::new());
+    let mut matchers = start_bot(&bot).await;
+
+    mock.deliver(&mut matchers, private_message(7, json!([text("hello")])))
+        .await;
+    let history = bot.history().unwrap();
+    assert!(history.get(SELF_ID, "7").await.unwrap().is_some());
+
+    // Looking a message up asks the implementation which account the bot is.
+    mock.respond_ok(
+        "get_login_info",
+        ApiRespData::GetLoginInfoResponse(GetLoginInfoResponse {
+            user_id: SELF_ID.parse().unwrap(),
+            nickname: "bot".to_string(),
+        }),
+    );
+    let detail = bot.get_message_detail("7".to_string()).await.unwrap();
+    assert_eq!(detail.message, vec![MessageSegment::text("hello")]);
+    let calls = mock.take_calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].action, "get_login_info");
+}
@@ -0,0 +1,241 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use onebot_v11::api::{
+    payload::{ApiPayload, GetLoginInfo},
+    resp::{ApiRespData, SendGroupMsgResponse},
+};
+use onebot_v11_oxidebot::{
+    bot::http::{OnebotV11HttpConfig, QuickOperation},
+    error::OnebotError,
+    testing::{sign, start_bot, unused_addr, MockOnebot},
+    OnebotV11HttpBot,
+};
+use oxidebot::{
+    api::{payload::SendMessageTarget, CallApiTrait},
+    event::Event,
+    source::message::MessageSegment,
+};
+use serde_json::{json, Value};
+
+async fn connect(
+    config: OnebotV11HttpConfig,
+) -> (MockOnebot, OnebotV11HttpBot, OnebotV11HttpConfig) {
+    let mock = MockOnebot::new();
+    let config = OnebotV11HttpConfig {
+        api: mock.serve_http().await.unwrap(),
+        post_port: unused_addr().port(),
+        ..config
+    };
+    let bot = OnebotV11HttpBot::try_new(config.clone()).await.unwrap();
+    (mock, bot, config)
+}
+
+fn group_message(text: &str) -> Value {
+    common::group_message(12, json!([common::text(text)]))
+}
+
+#[tokio::test]
+async fn api_calls_go_over_http() {
+    let (mock, bot, _) = connect(OnebotV11HttpConfig::default()).await;
+    mock.respond_ok(
+        "send_group_msg",
+        ApiRespData::SendGroupMsgResponse(SendGroupMsgResponse { message_id: 7 }),
+    );
+    let sent = bot
+        .send_message(
+            vec![MessageSegment::text("hello")],
+            SendMessageTarget::Group("10001".to_string()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(sent[0].sent_message_id, "7");
+    let calls = mock.take_calls();
+    assert_eq!(calls[0].action, "send_group_msg");
+    assert_eq!(calls[0].params["group_id"], 10001);
+    assert_eq!(calls[0].params["message"][0]["data"]["text"], "hello");
+}
+
+#[tokio::test]
+async fn http_failures_are_typed() {
+    let (mock, bot, _) = connect(OnebotV11HttpConfig::default()).await;
+    let login_info = || bot.call_api(ApiPayload::GetLoginInfo(GetLoginInfo {}));
+    for (status, retryable) in [(503, true), (429, true), (404, false)] {
+        mock.respond_http_status("get_login_info", status);
+        let error = login_info().await.unwrap_err();
+        assert!(
+            matches!(error, OnebotError::Http { status: s, .. } if s == status),
+            "{:?}",
+            error
+        );
+        assert_eq!(error.is_retryable(), retryable, "{}", status);
+    }
+    mock.respond_failed("get_login_info", 100, "not logged in");
+    let error = login_info().await.unwrap_err();
+    assert_eq!(error.retcode(), Some(100));
+    assert!(!error.is_retryable());
+}
+
+#[tokio::test]
+async fn webhook_events_reach_handlers() {
+    let (mock, bot, config) = connect(OnebotV11HttpConfig::default()).await;
+    let mut matchers = start_bot(&bot).await;
+    let resp = mock.post_event(&config, group_message("hi")).await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let matcher = tokio::time::timeout(Duration::from_secs(5), matchers.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let Event::MessageEvent(event) = matcher.event.as_ref() else {
+        panic!("unexpected event {:?}", matcher.event);
+    };
+    assert_eq!(event.message.segments, [MessageSegment::text("hi")]);
+}
+
+fn webhook_url(config: &OnebotV11HttpConfig) -> String {
+    format!(
+        "http://{}:{}/{}",
+        config.post_host, config.post_port, config.post_suffix
+    )
+}
+
+#[tokio::test]
+async fn webhook_reports_must_be_signed() {
+    let (mock, bot, config) = connect(OnebotV11HttpConfig {
+        secret: Some("s3cret".to_string()),
+        ..Default::default()
+    })
+    .await;
+    let mut matchers = start_bot(&bot).await;
+    let body = group_message("signed").to_string();
+    let post = |signature: Option<String>| {
+        let request = reqwest::Client::new()
+            .post(webhook_url(&config))
+            .body(body.clone());
+        match signature {
+            Some(signature) => request.header("X-Signature", signature),
+            None => request,
+        }
+        .send()
+    };
+
+    assert_eq!(post(None).await.unwrap().status(), 401);
+    assert_eq!(
+        post(Some(sign("wrong", body.as_bytes())))
+            .await
+            .unwrap()
+            .status(),
+        403
+    );
+    assert_eq!(
+        post(Some("sha1=zz".to_string())).await.unwrap().status(),
+        403
+    );
+    assert!(matchers.try_recv().is_err());
+
+    assert_eq!(
+        post(Some(sign("s3cret", body.as_bytes())))
+            .await
+            .unwrap()
+            .status(),
+        204
+    );
+    tokio::time::timeout(Duration::from_secs(5), matchers.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    // What the mock posts is signed the same way.
+    assert_eq!(
+        mock.post_event(&config, group_message("again"))
+            .await
+            .unwrap()
+            .status(),
+        204
+    );
+}
+
+#[tokio::test]
+async fn oversized_reports_are_refused() {
+    let (_, _bot, config) = connect(OnebotV11HttpConfig {
+        secret: Some("s3cret".to_string()),
+        ..Default::default()
+    })
+    .await;
+    let resp = reqwest::Client::new()
+        .post(webhook_url(&config))
+        .body(vec![b' '; 5 * 1024 * 1024])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 413);
+}
+
+#[tokio::test]
+async fn handlers_answer_held_reports() {
+    let (mock, bot, config) = connect(OnebotV11HttpConfig {
+        quick_operation_timeout: Some(Duration::from_secs(30)),
+        ..Default::default()
+    })
+    .await;
+    let mut matchers = start_bot(&bot).await;
+    let handler = tokio::spawn(async move {
+        let matcher = matchers.recv().await.unwrap();
+        QuickOperation::reply(vec![MessageSegment::text("pong")])
+            .answer(&matcher)
+            .unwrap();
+        assert!(QuickOperation::default().answer(&matcher).is_err());
+    });
+    let resp = mock
+        .post_event(&config, group_message("ping"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(
+        body,
+        json!({ "reply": [{ "type": "text", "data": { "text": "pong" } }] })
+    );
+    handler.await.unwrap();
+}
+
+#[tokio::test]
+async fn held_reports_are_released_once_handled() {
+    let (mock, bot, config) = connect(OnebotV11HttpConfig {
+        quick_operation_timeout: Some(Duration::from_secs(30)),
+        ..Default::default()
+    })
+    .await;
+    let mut matchers = start_bot(&bot).await;
+
+    // A handler that doesn't answer.
+    let handler = tokio::spawn(async move {
+        let matcher = matchers.recv().await.unwrap();
+        drop(matcher);
+        matchers
+    });
+    let started = Instant::now();
+    let resp = mock.post_event(&config, group_message("hi")).await.unwrap();
+    assert_eq!(resp.status(), 204);
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    // No handlers at all.
+    drop(handler.await.unwrap());
+    let started = Instant::now();
+    let resp = mock.post_event(&config, group_message("hi")).await.unwrap();
+    assert_eq!(resp.status(), 204);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn unheld_reports_cannot_be_answered() {
+    let (mock, bot, config) = connect(OnebotV11HttpConfig::default()).await;
+    let mut matchers = start_bot(&bot).await;
+    mock.post_event(&config, group_message("hi")).await.unwrap();
+    let matcher = tokio::time::timeout(Duration::from_secs(5), matchers.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    let error = QuickOperation::default().answer(&matcher).unwrap_err();
+    assert!(error.to_string().contains("not waiting"), "{}", error);
+}
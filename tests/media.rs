@@ -0,0 +1,202 @@
+mod common;
+
+use std::time::Duration;
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use onebot_v11::api::resp::{ApiRespData, SendGroupMsgResponse};
+use onebot_v11_oxidebot::{
+    media::{file_from_bytes, file_from_path, MediaConfig, MediaServer, MediaServerConfig},
+    testing::MockOnebot,
+    OnebotV11WsBot,
+};
+use oxidebot::{
+    api::{payload::SendMessageTarget, CallApiTrait},
+    source::message::{File, MessageSegment},
+};
+use serde_json::Value;
+
+async fn connect(media: MediaConfig) -> (MockOnebot, OnebotV11WsBot) {
+    let (mock, bot) = common::connect().await;
+    (mock, bot.with_media(media))
+}
+
+async fn send_image(mock: &MockOnebot, bot: &OnebotV11WsBot, file: File) -> anyhow::Result<Value> {
+    mock.respond_ok(
+        "send_group_msg",
+        ApiRespData::SendGroupMsgResponse(SendGroupMsgResponse { message_id: 1 }),
+    );
+    bot.send_message(
+        vec![MessageSegment::image(file)],
+        SendMessageTarget::Group("10001".to_string()),
+    )
+    .await?;
+    Ok(mock.take_calls().remove(0).params["message"][0]["data"]["file"].clone())
+}
+
+fn temp_file(name: &str, content: &[u8]) -> std::path::PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("onebot_v11_oxidebot-media-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn bytes_are_sent_as_base64() {
+    let (mock, bot) = connect(MediaConfig::default()).await;
+    let file = file_from_bytes("chart.png", b"\x89PNG chart");
+    assert_eq!(file.mime, Some(mime_guess::mime::IMAGE_PNG));
+    assert_eq!(file.size, Some(10));
+    assert_eq!(
+        send_image(&mock, &bot, file).await.unwrap(),
+        format!("base64://{}", STANDARD.encode(b"\x89PNG chart"))
+    );
+}
+
+const NOT_SHARED: MediaConfig = MediaConfig {
+    shared_fs: false,
+    max_size: u64::MAX,
+};
+
+#[tokio::test]
+async fn local_files_are_read_unless_shared() {
+    let path = temp_file("local chart.png", b"chart");
+    let file = file_from_path(&path);
+    assert_eq!(file.name, "local chart.png");
+    assert_eq!(file.mime, Some(mime_guess::mime::IMAGE_PNG));
+
+    let (mock, bot) = connect(NOT_SHARED).await;
+    assert_eq!(
+        send_image(&mock, &bot, file.clone()).await.unwrap(),
+        format!("base64://{}", STANDARD.encode(b"chart"))
+    );
+    // What oxidebot's own constructor makes is a local path too.
+    let own = File::try_from_path(temp_file("own.png", b"own"))
+        .await
+        .unwrap();
+    assert_eq!(
+        send_image(&mock, &bot, own).await.unwrap(),
+        format!("base64://{}", STANDARD.encode(b"own"))
+    );
+
+    let (mock, bot) = connect(MediaConfig::default()).await;
+    let sent = send_image(&mock, &bot, file).await.unwrap();
+    let sent = sent.as_str().unwrap();
+    assert!(sent.starts_with("file:///"), "{}", sent);
+    assert!(sent.ends_with("/local%20chart.png"), "{}", sent);
+    // Shared paths may only exist where the implementation runs, and aren't checked.
+    let sent = send_image(&mock, &bot, file_from_path("/only/over/there.png"))
+        .await
+        .unwrap();
+    assert_eq!(sent, "file:///only/over/there.png");
+}
+
+#[tokio::test]
+async fn oversized_and_missing_files_are_refused() {
+    let (mock, bot) = connect(MediaConfig {
+        max_size: 4,
+        ..NOT_SHARED
+    })
+    .await;
+    let path = temp_file("large.png", b"too large");
+    let error = send_image(&mock, &bot, file_from_path(&path))
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("over the limit of 4"),
+        "{}",
+        error
+    );
+    assert!(
+        send_image(&mock, &bot, file_from_bytes("large.png", b"too large"))
+            .await
+            .is_err()
+    );
+    assert!(send_image(
+        &mock,
+        &bot,
+        file_from_path(path.with_file_name("missing.png"))
+    )
+    .await
+    .is_err());
+    assert!(mock.calls().is_empty());
+}
+
+async fn media_server(ttl: Duration) -> std::sync::Arc<MediaServer> {
+    MediaServer::new(MediaServerConfig {
+        port: 0,
+        ttl,
+        ..Default::default()
+    })
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn published_media_is_served_until_dropped() {
+    let server = media_server(Duration::from_secs(60)).await;
+    let published = server.publish_bytes("chart.png", &b"chart"[..]);
+    let url = published.url().to_string();
+    assert!(url.starts_with(server.base_url()), "{}", url);
+    assert!(url.ends_with("/chart.png"), "{}", url);
+    let resp = reqwest::get(&url).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "image/png");
+    assert_eq!(resp.bytes().await.unwrap(), &b"chart"[..]);
+
+    // Anything but the whole token is unknown.
+    let guessed = format!(
+        "{}/{}",
+        server.base_url(),
+        &url[server.base_url().len() + 1..][..8]
+    );
+    assert_eq!(reqwest::get(guessed).await.unwrap().status(), 404);
+    drop(published);
+    assert!(server.is_empty());
+    assert_eq!(reqwest::get(&url).await.unwrap().status(), 404);
+
+    let server = media_server(Duration::from_millis(50)).await;
+    let published = server.publish_path(temp_file("expiring.png", b"chart"));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(server.is_empty());
+    assert_eq!(reqwest::get(published.url()).await.unwrap().status(), 404);
+}
+
+#[tokio::test]
+async fn published_files_are_streamed() {
+    let server = media_server(Duration::from_secs(60)).await;
+    // Several chunks, the last one short.
+    let content: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+    let published = server.publish_path(temp_file("streamed.bin", &content));
+    let resp = reqwest::get(published.url()).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.content_length(), Some(content.len() as u64));
+    assert_eq!(resp.bytes().await.unwrap(), content);
+}
+
+#[tokio::test]
+async fn sent_media_goes_through_the_server() {
+    let server = media_server(Duration::from_secs(60)).await;
+    let (mock, bot) = connect(NOT_SHARED).await;
+    let bot = bot.with_media_server(server.clone());
+    for file in [
+        file_from_path(temp_file("served.png", b"chart")),
+        file_from_bytes("served.png", b"chart"),
+    ] {
+        let sent = send_image(&mock, &bot, file).await.unwrap();
+        let sent = sent.as_str().unwrap();
+        assert!(sent.starts_with(server.base_url()), "{}", sent);
+        // Gone once the implementation has answered.
+        assert!(server.is_empty());
+    }
+    // Remote URLs are left alone.
+    let remote = File {
+        uri: Some("https://example.com/a.png".parse().unwrap()),
+        ..Default::default()
+    };
+    assert_eq!(
+        send_image(&mock, &bot, remote).await.unwrap(),
+        "https://example.com/a.png"
+    );
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use common::SELF_ID;
+use onebot_v11::api::resp::{ApiRespData, GetLoginInfoResponse};
+use onebot_v11_oxidebot::{
+    bot::replay::ReplayConfig,
+    error::OnebotError,
+    record::{read_records, Record, Recorder},
+    testing::start_bot,
+    OnebotV11ReplayBot,
+};
+use oxidebot::{event::Event, matcher::Matcher, BotTrait};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+fn recording(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "onebot_v11_oxidebot-{}-{}.jsonl",
+        name,
+        std::process::id()
+    ))
+}
+
+fn group_message(message_id: i64) -> Value {
+    common::group_message(message_id, json!([common::text("hello")]))
+}
+
+async fn next_message(matchers: &mut broadcast::Receiver<Matcher>) -> Matcher {
+    loop {
+        let matcher = tokio::time::timeout(Duration::from_secs(5), matchers.recv())
+            .await
+            .expect("matcher delivered")
+            .unwrap();
+        if matches!(*matcher.event, Event::MessageEvent(_)) {
+            return matcher;
+        }
+    }
+}
+
+fn message_id(matcher: &Matcher) -> String {
+    match &*matcher.event {
+        Event::MessageEvent(event) => event.id.clone(),
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+#[tokio::test]
+async fn record_then_replay() {
+    let path = recording("round_trip");
+    let (mock, bot) = common::connect().await;
+    let bot = bot.with_recorder(Recorder::create(&path).unwrap());
+    let mut matchers = start_bot(&bot).await;
+
+    // Fields onebot_v11 doesn't know are recorded too.
+    let mut event = group_message(-12);
+    event["message_seq"] = json!(77);
+    mock.push_event(event.clone());
+    let matcher = next_message(&mut matchers).await;
+    mock.respond_ok(
+        "get_login_info",
+        ApiRespData::GetLoginInfoResponse(GetLoginInfoResponse {
+            user_id: SELF_ID,
+            nickname: "bot".to_string(),
+        }),
+    );
+    let profile = matcher.bot.get_bot_profile().await.unwrap();
+    assert_eq!(profile.profile.nickname.as_deref(), Some("bot"));
+
+    bot.recorder().unwrap().flush().await;
+    let records = read_records(&path).unwrap();
+    assert!(matches!(
+        &records[..],
+        [Record::Event { event: recorded, .. }, Record::Call { action, response: Some(_), .. }]
+            if action == "get_login_info" && *recorded == event
+    ));
+
+    let replay = OnebotV11ReplayBot::try_new(ReplayConfig {
+        path: path.clone(),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(replay.bot_info().await.id.as_deref(), Some("30003"));
+    let mut matchers = start_bot(&replay).await;
+    let matcher = next_message(&mut matchers).await;
+    assert_eq!(message_id(&matcher), "-12");
+    let profile = matcher.bot.get_bot_profile().await.unwrap();
+    assert_eq!(profile.profile.nickname.as_deref(), Some("bot"));
+    let error = matcher.bot.get_bot_profile().await.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<OnebotError>(),
+        Some(OnebotError::Transport { .. })
+    ));
+    tokio::time::timeout(Duration::from_secs(5), replay.finished())
+        .await
+        .unwrap();
+    std::fs::remove_file(path).unwrap();
+}
+
+#[tokio::test]
+async fn replay_in_real_time() {
+    let path = recording("real_time");
+    let recorder = Recorder::create(&path).unwrap();
+    for (recorded_at, event) in [
+        (1_000, group_message(1)),
+        // Skipped, as it was when received.
+        (1_200, json!({ "post_type": "unknown" })),
+        (1_400, group_message(2)),
+    ] {
+        recorder.write(&Record::Event { recorded_at, event });
+    }
+    drop(recorder);
+
+    let replay = OnebotV11ReplayBot::try_new(ReplayConfig {
+        path: path.clone(),
+        real_time: true,
+        speed: 2.0,
+    })
+    .unwrap();
+    let mut matchers = start_bot(&replay).await;
+    let first = next_message(&mut matchers).await;
+    let started = Instant::now();
+    let second = next_message(&mut matchers).await;
+    assert!(started.elapsed() >= Duration::from_millis(150));
+    assert_eq!(
+        (message_id(&first), message_id(&second)),
+        ("1".to_string(), "2".to_string())
+    );
+    std::fs::remove_file(path).unwrap();
+}
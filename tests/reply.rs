@@ -0,0 +1,138 @@
+mod common;
+
+use common::{group_message, text};
+use onebot_v11::api::resp::{ApiRespData, GetMsgResponse, MessageSender};
+use onebot_v11_oxidebot::{
+    reply::{quoted_message, ReplyResolverConfig},
+    store::MessageStoreConfig,
+    testing::{start_bot, MockOnebot},
+    OnebotV11WsBot,
+};
+use oxidebot::{matcher::Matcher, source::message::MessageSegment};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+async fn connect(store: bool) -> (MockOnebot, OnebotV11WsBot, broadcast::Receiver<Matcher>) {
+    let (mock, bot) = common::connect().await;
+    let mut bot = bot.with_reply_resolver(ReplyResolverConfig::default());
+    if store {
+        bot = bot.with_message_store(MessageStoreConfig::default());
+    }
+    let matchers = start_bot(&bot).await;
+    (mock, bot, matchers)
+}
+
+fn reply_to(message_id: i64, replied: &str) -> Value {
+    group_message(
+        message_id,
+        json!([
+            { "type": "reply", "data": { "id": replied } },
+            text("translate this"),
+        ]),
+    )
+}
+
+fn get_msg_response(text: &str) -> ApiRespData {
+    ApiRespData::GetMsgResponse(GetMsgResponse {
+        time: 1700000000,
+        message_type: "group".to_string(),
+        message_id: 12,
+        real_id: 12,
+        sender: MessageSender {
+            user_id: Some(20003),
+            nickname: Some("bob".to_string()),
+            card: None,
+            sex: None,
+            age: None,
+            area: None,
+            level: None,
+            role: None,
+            title: None,
+        },
+        message: vec![
+            serde_json::from_value(json!({ "type": "text", "data": { "text": text } })).unwrap(),
+        ],
+    })
+}
+
+#[tokio::test]
+async fn reply_to_a_held_message() {
+    let (mock, _bot, mut matchers) = connect(true).await;
+    mock.deliver(&mut matchers, group_message(-12, json!([text("bonjour")])))
+        .await;
+
+    let matcher = mock.deliver(&mut matchers, reply_to(13, "-12")).await;
+    let quoted = quoted_message(&matcher).await.unwrap();
+    assert_eq!(
+        quoted.message.segments,
+        vec![MessageSegment::text("bonjour")]
+    );
+    assert_eq!(quoted.sender.id, "20002");
+    assert!(mock.calls().is_empty());
+}
+
+#[tokio::test]
+async fn quoted_messages_are_fetched_once() {
+    let (mock, bot, mut matchers) = connect(false).await;
+    mock.respond_ok("get_msg", get_msg_response("bonjour"));
+
+    let first = mock.deliver(&mut matchers, reply_to(13, "12")).await;
+    let second = mock.deliver(&mut matchers, reply_to(14, "12")).await;
+    let quoted = quoted_message(&second).await.unwrap();
+    assert_eq!(
+        quoted.message.segments,
+        vec![MessageSegment::text("bonjour")]
+    );
+    assert_eq!(quoted.group.unwrap().id, "10001");
+    assert_eq!(
+        quoted.sender.profile.unwrap().nickname.as_deref(),
+        Some("bob")
+    );
+    assert_eq!(quoted_message(&first).await.unwrap().id, "12");
+    assert!(bot.reply_resolver().unwrap().get("30003", "12").is_some());
+    assert_eq!(mock.take_calls().len(), 1);
+
+    let plain = mock
+        .deliver(&mut matchers, group_message(15, json!([text("hi")])))
+        .await;
+    assert!(quoted_message(&plain).await.is_none());
+}
+
+#[tokio::test]
+async fn failed_fetches_are_retried() {
+    let (mock, bot, mut matchers) = connect(false).await;
+    mock.respond_failed("get_msg", 100, "message not found");
+
+    let matcher = mock.deliver(&mut matchers, reply_to(13, "12")).await;
+    assert!(quoted_message(&matcher).await.is_none());
+    assert!(bot.reply_resolver().unwrap().is_empty());
+
+    mock.respond_ok("get_msg", get_msg_response("found"));
+    let matcher = mock.deliver(&mut matchers, reply_to(14, "12")).await;
+    assert_eq!(
+        quoted_message(&matcher).await.unwrap().message.segments,
+        vec![MessageSegment::text("found")]
+    );
+    assert_eq!(mock.take_calls().len(), 2);
+}
+
+#[tokio::test]
+async fn quoted_messages_are_kept_per_account() {
+    let (mock, bot, mut matchers) = connect(false).await;
+    mock.respond_ok("get_msg", get_msg_response("bonjour"));
+    let matcher = mock.deliver(&mut matchers, reply_to(13, "12")).await;
+    quoted_message(&matcher).await.unwrap();
+
+    mock.respond_ok("get_msg", get_msg_response("hallo"));
+    let mut other = reply_to(13, "12");
+    other["self_id"] = json!(30004);
+    let matcher = mock.deliver(&mut matchers, other).await;
+    assert_eq!(
+        quoted_message(&matcher).await.unwrap().message.segments,
+        vec![MessageSegment::text("hallo")]
+    );
+    let resolver = bot.reply_resolver().unwrap();
+    assert_eq!(resolver.len(), 2);
+    assert!(resolver.get("30003", "12").is_some());
+    assert_eq!(mock.take_calls().len(), 2);
+}
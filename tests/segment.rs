@@ -0,0 +1,477 @@
+use std::collections::{BTreeMap, HashSet};
+
+use onebot_v11::message::segment::{
+    ContactData, ContactType, CustomNodeData, File as OnebotFile, ImageData, LocationData,
+    RecordData, VideoData,
+};
+use onebot_v11_oxidebot::segment::{cast_segment, is_lossy, parse_segment, RoundTrip, LOSSY_PATHS};
+use oxidebot::source::{
+    message::{File, Message, MessageSegment},
+    user::{User, UserProfile},
+};
+use proptest::{
+    prelude::*,
+    strategy::ValueTree,
+    test_runner::{Config, TestRunner},
+};
+use serde_json::{json, Value};
+
+type Losses = HashSet<(String, Option<String>)>;
+
+fn url() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "https://example\\.com/[a-z]{1,8}\\.(png|jpg|amr|mp4)",
+        Just("https://example.com".to_string()),
+        "http://[a-z]{3,8}\\.cn/download\\?fileid=[A-Za-z0-9]{4,12}",
+    ]
+}
+
+fn file_name() -> impl Strategy<Value = String> {
+    "[A-F0-9]{8}\\.(jpg|png|gif|amr|mp4|txt)"
+}
+
+fn id() -> impl Strategy<Value = String> {
+    "-?[1-9][0-9]{0,9}"
+}
+
+fn flag() -> impl Strategy<Value = Option<u8>> {
+    proptest::option::of(0..=1u8)
+}
+
+fn coordinate() -> impl Strategy<Value = String> {
+    prop_oneof![
+        (-90.0..90.0f64).prop_map(|c| c.to_string()),
+        (-90.0..90.0f64).prop_map(|c| format!("{:.6}", c)),
+        Just("unknown".to_string()),
+    ]
+}
+
+fn onebot_leaf() -> impl Strategy<Value = onebot_v11::MessageSegment> {
+    use onebot_v11::MessageSegment as S;
+    prop_oneof![
+        any::<String>().prop_map(S::text),
+        "[0-9]{1,3}".prop_map(S::face),
+        (
+            any::<String>(),
+            url(),
+            "[0-9]{5}",
+            "[0-9]{6}",
+            "[a-f0-9]{16}"
+        )
+            .prop_map(|(summary, url, emoji_id, package_id, key)| S::mface(
+                summary, url, emoji_id, package_id, key
+            )),
+        prop_oneof!["[1-9][0-9]{4,10}", Just("all".to_string())].prop_map(S::at),
+        (
+            file_name(),
+            proptest::option::of(Just("flash".to_string())),
+            proptest::option::of(any::<String>()),
+            proptest::option::of(url()),
+            flag(),
+            flag(),
+            proptest::option::of(any::<u32>()),
+        )
+            .prop_map(
+                |(file, r#type, summary, url, cache, proxy, timeout)| S::Image {
+                    data: ImageData {
+                        file,
+                        r#type,
+                        summary,
+                        url,
+                        cache,
+                        proxy,
+                        timeout,
+                    },
+                }
+            ),
+        (
+            file_name(),
+            flag(),
+            proptest::option::of(url()),
+            flag(),
+            flag(),
+            proptest::option::of(any::<u32>()),
+        )
+            .prop_map(|(file, magic, url, cache, proxy, timeout)| S::Record {
+                data: RecordData {
+                    file,
+                    magic,
+                    url,
+                    cache,
+                    proxy,
+                    timeout,
+                },
+            }),
+        (
+            file_name(),
+            proptest::option::of(url()),
+            flag(),
+            flag(),
+            proptest::option::of(any::<u32>()),
+        )
+            .prop_map(|(file, url, cache, proxy, timeout)| S::Video {
+                data: VideoData {
+                    file,
+                    url,
+                    cache,
+                    proxy,
+                    timeout,
+                },
+            }),
+        (
+            file_name(),
+            proptest::option::of(file_name()),
+            "(/tmp/[a-z]{1,8})?",
+            proptest::option::of(url()),
+            "(/[a-f0-9]{8})?",
+            "([0-9]{1,6})?",
+        )
+            .prop_map(|(file, name, path, url, file_id, file_size)| S::File {
+                data: OnebotFile {
+                    file,
+                    name,
+                    path,
+                    url,
+                    file_id,
+                    file_size,
+                },
+            }),
+        Just(S::rps()),
+        Just(S::dice()),
+        Just(S::shake()),
+        "[1-9][0-9]{0,3}".prop_map(|id| S::poke("126".to_string(), id)),
+        proptest::option::of(any::<bool>()).prop_map(S::anonymous),
+        (
+            url(),
+            any::<String>(),
+            proptest::option::of(any::<String>()),
+            proptest::option::of(url()),
+        )
+            .prop_map(|(url, title, content, image)| S::share(url, title, content, image)),
+        (
+            prop_oneof![Just(ContactType::Group), Just(ContactType::QQ)],
+            "[1-9][0-9]{4,10}"
+        )
+            .prop_map(|(r#type, id)| S::Contact {
+                data: ContactData { r#type, id },
+            }),
+        (
+            coordinate(),
+            coordinate(),
+            proptest::option::of(any::<String>()),
+            proptest::option::of(any::<String>()),
+        )
+            .prop_map(|(lat, lon, title, content)| S::Location {
+                data: LocationData {
+                    lat,
+                    lon,
+                    title,
+                    content,
+                },
+            }),
+        (
+            prop_oneof![Just("qq"), Just("163"), Just("xm")],
+            "[0-9]{1,10}"
+        )
+            .prop_map(|(r#type, id)| S::music(r#type.to_string(), id)),
+        (
+            url(),
+            url(),
+            any::<String>(),
+            proptest::option::of(any::<String>()),
+            proptest::option::of(url()),
+        )
+            .prop_map(|(url, audio, title, content, image)| S::music_custom(
+                "custom", url, audio, title, content, image
+            )),
+        id().prop_map(S::reply),
+        "[A-Za-z0-9]{8,20}".prop_map(S::forward),
+        id().prop_map(S::node),
+        any::<String>().prop_map(|data| S::Xml {
+            data: onebot_v11::message::segment::XmlData { data },
+        }),
+        any::<String>().prop_map(|data| S::Json {
+            data: onebot_v11::message::segment::JsonData { data },
+        }),
+    ]
+}
+
+fn onebot_segment() -> impl Strategy<Value = onebot_v11::MessageSegment> {
+    prop_oneof![
+        4 => onebot_leaf(),
+        1 => (
+            proptest::option::of(any::<String>()),
+            proptest::option::of(1..i64::MAX),
+            proptest::collection::vec(onebot_leaf(), 0..3),
+        )
+            .prop_map(|(name, uin, content)| onebot_v11::MessageSegment::CustomNode {
+                data: CustomNodeData { name, uin, content },
+            }),
+    ]
+}
+
+fn file() -> impl Strategy<Value = File> {
+    (
+        proptest::option::of("[a-f0-9]{8}"),
+        file_name(),
+        proptest::option::of(url()),
+        proptest::option::of("base64://[A-Za-z0-9+/]{4,16}"),
+        proptest::option::of(0..u64::MAX),
+    )
+        .prop_map(|(id, name, uri, base64, size)| File {
+            id,
+            mime: mime_guess::from_path(&name).first(),
+            uri: uri.map(|uri| uri.parse().unwrap()),
+            name,
+            base64,
+            size,
+        })
+}
+
+fn oxidebot_leaf() -> impl Strategy<Value = MessageSegment> {
+    use MessageSegment as S;
+    prop_oneof![
+        any::<String>().prop_map(S::text),
+        proptest::option::of(file()).prop_map(|file| S::Image { file }),
+        (
+            proptest::option::of(file()),
+            proptest::option::of(0..i32::MAX)
+        )
+            .prop_map(|(file, length)| S::Video { file, length }),
+        (
+            proptest::option::of(file()),
+            proptest::option::of(0..i32::MAX)
+        )
+            .prop_map(|(file, length)| S::Audio { file, length }),
+        proptest::option::of(file()).prop_map(|file| S::File { file }),
+        id().prop_map(|message_id| S::Reply { message_id }),
+        prop_oneof!["[1-9][0-9]{4,10}", Just("all".to_string())]
+            .prop_map(|user_id| S::At { user_id }),
+        Just(S::AtAll),
+        id().prop_map(|message_id| S::Reference { message_id }),
+        (
+            any::<String>(),
+            proptest::option::of(any::<String>()),
+            url(),
+            proptest::option::of(file()),
+        )
+            .prop_map(|(title, content, url, image)| S::Share {
+                title,
+                content,
+                url,
+                image,
+            }),
+        (
+            -90.0..90.0f64,
+            -180.0..180.0f64,
+            any::<String>(),
+            proptest::option::of(any::<String>()),
+        )
+            .prop_map(|(latitude, longitude, title, content)| S::Location {
+                latitude,
+                longitude,
+                title,
+                content,
+            }),
+        prop_oneof![
+            "[0-9]{1,3}",
+            Just("Rps".to_string()),
+            Just("Dice".to_string())
+        ]
+        .prop_map(|id| S::Emoji { id }),
+        id().prop_map(|message_id| S::ForwardNode { message_id }),
+        (
+            prop_oneof![Just("xml"), Just("json"), Just("markdown")],
+            any::<String>()
+        )
+            .prop_map(|(r#type, data)| S::CustomString {
+                r#type: r#type.to_string(),
+                data,
+            }),
+        prop_oneof![
+            "[1-9][0-9]{4,10}".prop_map(|id| ("contact", json!({ "type": "qq", "id": id }))),
+            "[0-9]{1,10}".prop_map(|id| ("music", json!({ "type": "163", "id": id }))),
+            any::<String>().prop_map(|data| ("keyboard", json!({ "data": data }))),
+        ]
+        .prop_map(|(r#type, data)| S::CustomValue {
+            r#type: r#type.to_string(),
+            data,
+        }),
+    ]
+}
+
+fn oxidebot_segment() -> impl Strategy<Value = MessageSegment> {
+    prop_oneof![
+        4 => oxidebot_leaf(),
+        1 => (
+            proptest::option::of(("[1-9][0-9]{4,10}|[a-z]{4}", proptest::option::of(any::<String>()))),
+            "[0-9]{0,6}",
+            proptest::collection::vec(oxidebot_leaf(), 0..3),
+        )
+            .prop_map(|(user, id, segments)| MessageSegment::ForwardCustomNode {
+                user: user.map(|(id, nickname)| User {
+                    id,
+                    profile: nickname.map(|nickname| UserProfile {
+                        nickname: Some(nickname),
+                        ..Default::default()
+                    }),
+                    group_info: None,
+                }),
+                message: Message { id, segments },
+            }),
+    ]
+}
+
+/// The OneBot type and data fields of `segment`, including the receive-only ones serde skips.
+fn onebot_fields(segment: &onebot_v11::MessageSegment) -> (String, BTreeMap<String, Value>) {
+    let value = serde_json::to_value(segment).unwrap();
+    let mut fields: BTreeMap<String, Value> = value["data"]
+        .as_object()
+        .map(|data| data.clone().into_iter().collect())
+        .unwrap_or_default();
+    if let onebot_v11::MessageSegment::File { data } = segment {
+        fields.insert("path".to_string(), json!(data.path));
+        fields.insert("url".to_string(), json!(data.url));
+        fields.insert("file_id".to_string(), json!(data.file_id));
+        fields.insert("file_size".to_string(), json!(data.file_size));
+    }
+    (value["type"].as_str().unwrap().to_string(), fields)
+}
+
+/// The variant name and fields of `segment`, each field as its `Debug` output.
+fn oxidebot_fields(segment: &MessageSegment) -> (&'static str, BTreeMap<&'static str, String>) {
+    use MessageSegment as S;
+    let (name, fields): (_, Vec<(_, &dyn std::fmt::Debug)>) = match segment {
+        S::Text { content } => ("Text", vec![("content", content)]),
+        S::Image { file } => ("Image", vec![("file", file)]),
+        S::Video { file, length } => ("Video", vec![("file", file), ("length", length)]),
+        S::Audio { file, length } => ("Audio", vec![("file", file), ("length", length)]),
+        S::File { file } => ("File", vec![("file", file)]),
+        S::Reply { message_id } => ("Reply", vec![("message_id", message_id)]),
+        S::At { user_id } => ("At", vec![("user_id", user_id)]),
+        S::AtAll => ("AtAll", vec![]),
+        S::Reference { message_id } => ("Reference", vec![("message_id", message_id)]),
+        S::Share {
+            title,
+            content,
+            url,
+            image,
+        } => (
+            "Share",
+            vec![
+                ("title", title),
+                ("content", content),
+                ("url", url),
+                ("image", image),
+            ],
+        ),
+        S::Location {
+            latitude,
+            longitude,
+            title,
+            content,
+        } => (
+            "Location",
+            vec![
+                ("latitude", latitude),
+                ("longitude", longitude),
+                ("title", title),
+                ("content", content),
+            ],
+        ),
+        S::Emoji { id } => ("Emoji", vec![("id", id)]),
+        S::ForwardNode { message_id } => ("ForwardNode", vec![("message_id", message_id)]),
+        S::ForwardCustomNode { user, message } => (
+            "ForwardCustomNode",
+            vec![("user", user), ("message", message)],
+        ),
+        S::CustomString { r#type, data } => {
+            ("CustomString", vec![("type", r#type), ("data", data)])
+        }
+        S::CustomValue { r#type, data } => ("CustomValue", vec![("type", r#type), ("data", data)]),
+    };
+    let fields = fields
+        .into_iter()
+        .map(|(field, value)| (field, format!("{:?}", value)))
+        .collect();
+    (name, fields)
+}
+
+fn diff<K: Ord + ToString, V: PartialEq>(
+    before: (impl ToString, BTreeMap<K, V>),
+    after: (impl ToString, BTreeMap<K, V>),
+) -> Losses {
+    let (name, before) = (before.0.to_string(), before.1);
+    if name != after.0.to_string() {
+        return HashSet::from([(name, None)]);
+    }
+    let after = after.1;
+    before
+        .keys()
+        .chain(after.keys())
+        .filter(|field| before.get(*field) != after.get(*field))
+        .map(|field| (name.clone(), Some(field.to_string())))
+        .collect()
+}
+
+fn received_losses(segment: &onebot_v11::MessageSegment) -> Losses {
+    let round_trip = parse_segment(cast_segment(segment.clone()));
+    diff(onebot_fields(segment), onebot_fields(&round_trip))
+}
+
+fn sent_losses(segment: &MessageSegment) -> Losses {
+    let round_trip = cast_segment(parse_segment(segment.clone()));
+    diff(oxidebot_fields(segment), oxidebot_fields(&round_trip))
+}
+
+fn unlisted(round_trip: RoundTrip, losses: Losses) -> Losses {
+    losses
+        .into_iter()
+        .filter(|(segment, field)| !is_lossy(round_trip, segment, field.as_deref()))
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn received_segments_only_lose_listed_fields(segment in onebot_segment()) {
+        let unlisted = unlisted(RoundTrip::Received, received_losses(&segment));
+        prop_assert!(unlisted.is_empty(), "{:?} lost {:?}", segment, unlisted);
+    }
+
+    #[test]
+    fn sent_segments_only_lose_listed_fields(segment in oxidebot_segment()) {
+        let unlisted = unlisted(RoundTrip::Sent, sent_losses(&segment));
+        prop_assert!(unlisted.is_empty(), "{:?} lost {:?}", segment, unlisted);
+    }
+}
+
+/// Every loss in the table shows up, so the table doesn't outlive a fix.
+#[test]
+fn every_listed_loss_happens() {
+    let mut runner = TestRunner::new_with_rng(
+        Config::default(),
+        proptest::test_runner::TestRng::deterministic_rng(Config::default().rng_algorithm),
+    );
+    let mut observed = HashSet::new();
+    for _ in 0..4096 {
+        let segment = onebot_segment().new_tree(&mut runner).unwrap().current();
+        for (segment, field) in received_losses(&segment) {
+            observed.insert((RoundTrip::Received, segment, field));
+        }
+        let segment = oxidebot_segment().new_tree(&mut runner).unwrap().current();
+        for (segment, field) in sent_losses(&segment) {
+            observed.insert((RoundTrip::Sent, segment, field));
+        }
+    }
+    let never: Vec<_> = LOSSY_PATHS
+        .iter()
+        .filter(|path| {
+            !observed.contains(&(
+                path.round_trip,
+                path.segment.to_string(),
+                path.field.map(str::to_string),
+            ))
+        })
+        .collect();
+    assert!(never.is_empty(), "never lost: {:#?}", never);
+}
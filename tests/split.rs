@@ -0,0 +1,162 @@
+use onebot_v11::api::resp::{ApiRespData, GetLoginInfoResponse, SendGroupMsgResponse};
+use onebot_v11_oxidebot::{
+    split::{split_message, SplitConfig},
+    testing::MockOnebot,
+    OnebotV11WsBot,
+};
+use oxidebot::{
+    api::{payload::SendMessageTarget, CallApiTrait},
+    source::message::{File, MessageSegment},
+};
+use serde_json::json;
+
+fn config(max_text_len: usize, max_images: usize) -> SplitConfig {
+    SplitConfig {
+        max_text_len,
+        max_images,
+        forward_after: None,
+    }
+}
+
+fn texts(parts: &[Vec<MessageSegment>]) -> Vec<Vec<String>> {
+    parts
+        .iter()
+        .map(|part| {
+            part.iter()
+                .map(|segment| match segment {
+                    MessageSegment::Text { content } => content.clone(),
+                    MessageSegment::Image { .. } => "<image>".to_string(),
+                    other => format!("{:?}", other),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn image() -> MessageSegment {
+    MessageSegment::image(File {
+        id: None,
+        name: "a.png".to_string(),
+        uri: Some("https://example.com/a.png".parse().unwrap()),
+        base64: None,
+        mime: None,
+        size: None,
+    })
+}
+
+#[test]
+fn text_is_cut_on_boundaries() {
+    let split = |text: &str, max: usize| {
+        texts(&split_message(
+            vec![MessageSegment::text(text)],
+            &config(max, 10),
+        ))
+    };
+    assert_eq!(split("short", 10), [["short"]]);
+    assert_eq!(
+        split("first line\nsecond line", 16),
+        [["first line\n"], ["second line"]]
+    );
+    assert_eq!(
+        split("One. Two three. Four", 16),
+        [["One. Two three."], [" Four"]]
+    );
+    assert_eq!(split("one two three", 9), [["one two "], ["three"]]);
+    assert_eq!(split("一二三四五", 2), [["一二"], ["三四"], ["五"]]);
+    assert_eq!(split("你好。世界", 4), [["你好。"], ["世界"]]);
+}
+
+#[test]
+fn parts_fill_up_across_segments() {
+    let parts = split_message(
+        vec![
+            MessageSegment::reply("12"),
+            MessageSegment::text("abc"),
+            image(),
+            MessageSegment::text("defgh"),
+            image(),
+            image(),
+        ],
+        &config(5, 1),
+    );
+    assert_eq!(
+        texts(&parts),
+        [
+            vec!["Reply { message_id: \"12\" }", "abc", "<image>"],
+            vec!["defgh", "<image>"],
+            vec!["<image>"],
+        ]
+    );
+    assert_eq!(split_message(Vec::new(), &config(5, 2)), [Vec::new()]);
+}
+
+#[tokio::test]
+async fn long_messages_are_sent_in_parts() {
+    let mock = MockOnebot::new();
+    let bot = OnebotV11WsBot::try_new(mock.serve_ws().await.unwrap())
+        .await
+        .unwrap()
+        .with_splitter(config(11, 10));
+    mock.wait_connected().await;
+    for message_id in [1, 2] {
+        mock.respond_ok(
+            "send_group_msg",
+            ApiRespData::SendGroupMsgResponse(SendGroupMsgResponse { message_id }),
+        );
+    }
+    let sent = bot
+        .send_message(
+            vec![MessageSegment::text("first line\nsecond")],
+            SendMessageTarget::Group("10001".to_string()),
+        )
+        .await
+        .unwrap();
+    let ids: Vec<_> = sent.into_iter().map(|sent| sent.sent_message_id).collect();
+    assert_eq!(ids, ["1", "2"]);
+    let calls = mock.take_calls();
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[1].params["message"][0]["data"]["text"], "second");
+}
+
+#[tokio::test]
+async fn many_parts_become_a_forward() {
+    let mock = MockOnebot::new();
+    let bot = OnebotV11WsBot::try_new(mock.serve_ws().await.unwrap())
+        .await
+        .unwrap()
+        .with_splitter(SplitConfig {
+            max_text_len: 4,
+            max_images: 10,
+            forward_after: Some(2),
+        });
+    mock.wait_connected().await;
+    mock.respond_ok(
+        "get_login_info",
+        ApiRespData::GetLoginInfoResponse(GetLoginInfoResponse {
+            user_id: 30001,
+            nickname: "bot".to_string(),
+        }),
+    );
+    mock.respond_json(
+        "send_group_forward_msg",
+        json!({ "status": "ok", "retcode": 0, "data": { "message_id": 9, "forward_id": "res" } }),
+    );
+    let sent = bot
+        .send_message(
+            vec![MessageSegment::text("one two six ten")],
+            SendMessageTarget::Group("10001".to_string()),
+        )
+        .await
+        .unwrap();
+    let ids: Vec<_> = sent.into_iter().map(|sent| sent.sent_message_id).collect();
+    assert_eq!(ids, ["9"]);
+    let calls = mock.take_calls();
+    assert_eq!(calls.len(), 2);
+    // The bot wasn't told its account, so it asked before sending itself as the sender.
+    assert_eq!(calls[0].action, "get_login_info");
+    let nodes = calls[1].params["messages"].as_array().unwrap();
+    assert_eq!(nodes.len(), 4);
+    assert_eq!(nodes[0]["data"]["uin"], 30001);
+    assert_eq!(nodes[0]["data"]["name"], "bot");
+    assert_eq!(nodes[3]["data"]["content"][0]["data"]["text"], "ten");
+}
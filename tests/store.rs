@@ -0,0 +1,190 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::{deliver, group_message, notice, text};
+use onebot_v11::api::resp::{ApiRespData, GetMsgResponse, MessageSender};
+use onebot_v11_oxidebot::{
+    store::{MessageStore, MessageStoreConfig},
+    testing::{start_bot, MockOnebot},
+    OnebotV11WsBot,
+};
+use oxidebot::{
+    event::{Event, MessageEvent, NoticeEvent},
+    matcher::Matcher,
+    source::message::{Message, MessageSegment},
+};
+use serde_json::json;
+use tokio::sync::broadcast;
+
+async fn connect(
+    config: MessageStoreConfig,
+) -> (MockOnebot, OnebotV11WsBot, broadcast::Receiver<Matcher>) {
+    let (mock, bot) = common::connect().await;
+    let bot = bot.with_message_store(config);
+    let matchers = start_bot(&bot).await;
+    (mock, bot, matchers)
+}
+
+fn message(id: &str) -> MessageEvent {
+    MessageEvent {
+        id: id.to_string(),
+        message: Message {
+            id: id.to_string(),
+            segments: vec![MessageSegment::text(id)],
+        },
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn recall_of_a_held_message() {
+    let (mock, bot, mut matchers) = connect(Default::default()).await;
+    deliver(
+        &mock,
+        &mut matchers,
+        group_message(-12, json!([text("hello")])),
+    )
+    .await;
+    assert!(bot.message_store().unwrap().get("30003", "-12").is_some());
+
+    let event = deliver(
+        &mock,
+        &mut matchers,
+        notice(
+            "group_recall",
+            json!({ "group_id": 10001, "user_id": 20002, "operator_id": 20002, "message_id": -12 }),
+        ),
+    )
+    .await;
+    let Event::NoticeEvent(NoticeEvent::MessageDeletedEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(
+        event.message.unwrap().segments,
+        vec![MessageSegment::text("hello")]
+    );
+    assert_eq!(
+        event.user.unwrap().profile.unwrap().nickname.as_deref(),
+        Some("alice")
+    );
+    assert!(mock.calls().is_empty());
+}
+
+#[tokio::test]
+async fn essence_of_a_missing_message_is_fetched() {
+    let (mock, _bot, mut matchers) = connect(Default::default()).await;
+    mock.respond_ok(
+        "get_msg",
+        ApiRespData::GetMsgResponse(GetMsgResponse {
+            time: 1700000000,
+            message_type: "group".to_string(),
+            message_id: 12,
+            real_id: 12,
+            sender: MessageSender {
+                user_id: Some(20002),
+                nickname: Some("alice".to_string()),
+                card: None,
+                sex: None,
+                age: None,
+                area: None,
+                level: None,
+                role: None,
+                title: None,
+            },
+            message: vec![serde_json::from_value(
+                json!({ "type": "text", "data": { "text": "pinned" } }),
+            )
+            .unwrap()],
+        }),
+    );
+    let event = deliver(
+        &mock,
+        &mut matchers,
+        notice(
+            "essence",
+            json!({ "sub_type": "add", "group_id": 10001, "user_id": 20003, "message_id": 12, "sender_id": 20002 }),
+        ),
+    )
+    .await;
+    let Event::NoticeEvent(NoticeEvent::GroupHightLightChangeEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert_eq!(event.message.segments, vec![MessageSegment::text("pinned")]);
+    assert_eq!(
+        event.sender.unwrap().profile.unwrap().nickname.as_deref(),
+        Some("alice")
+    );
+    assert_eq!(mock.take_calls().len(), 1);
+}
+
+#[tokio::test]
+async fn missing_message_without_fetching() {
+    let (mock, _bot, mut matchers) = connect(MessageStoreConfig {
+        fetch_missing: false,
+        ..Default::default()
+    })
+    .await;
+    let event = deliver(
+        &mock,
+        &mut matchers,
+        notice(
+            "friend_recall",
+            json!({ "user_id": 20002, "message_id": 7 }),
+        ),
+    )
+    .await;
+    let Event::NoticeEvent(NoticeEvent::MessageDeletedEvent(event)) = event else {
+        panic!("unexpected event {:?}", event);
+    };
+    assert!(event.message.unwrap().segments.is_empty());
+    assert!(mock.calls().is_empty());
+}
+
+#[tokio::test]
+async fn fetching_doesnt_hold_up_later_events() {
+    let (mock, _bot, mut matchers) = connect(Default::default()).await;
+    mock.respond_never("get_msg");
+    mock.push_event(notice(
+        "group_recall",
+        json!({ "group_id": 10001, "user_id": 20002, "operator_id": 20002, "message_id": -99 }),
+    ));
+    let started = Instant::now();
+    let event = deliver(
+        &mock,
+        &mut matchers,
+        group_message(-13, json!([text("next")])),
+    )
+    .await;
+    assert!(matches!(event, Event::MessageEvent(_)), "{:?}", event);
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn store_is_bounded() {
+    let store = MessageStore::new(MessageStoreConfig {
+        capacity: 2,
+        ..Default::default()
+    });
+    for id in ["1", "2", "3"] {
+        store.insert("30003", message(id));
+    }
+    assert_eq!(store.len(), 2);
+    assert!(store.get("30003", "1").is_none());
+    assert_eq!(
+        store.get("30003", "3").unwrap().message.segments[0],
+        MessageSegment::text("3")
+    );
+    // Message ids are only unique per account.
+    assert!(store.get("30004", "3").is_none());
+    store.insert("30004", message("3"));
+    assert_eq!(store.len(), 2);
+    assert!(store.get("30003", "3").is_some());
+
+    let store = MessageStore::new(MessageStoreConfig {
+        max_age: Duration::ZERO,
+        ..Default::default()
+    });
+    store.insert("30003", message("1"));
+    assert!(store.is_empty());
+}
@@ -0,0 +1,210 @@
+use std::time::Duration;
+
+use onebot_v11::api::payload::{ApiPayload, GetLoginInfo};
+use onebot_v11_oxidebot::{
+    bot::ws::{OnebotV11WsConfig, ReconnectConfig, TokenPlacement},
+    error::{ConnectError, OnebotError},
+    testing::{start_bot, MockOnebot},
+    OnebotV11WsBot,
+};
+use oxidebot::{
+    event::{Event, MetaEvent},
+    matcher::Matcher,
+};
+use serde_json::json;
+use tokio::sync::broadcast;
+
+fn fast_reconnect() -> ReconnectConfig {
+    ReconnectConfig {
+        initial_delay: Duration::from_millis(10),
+        max_delay: Duration::from_millis(50),
+        jitter: 0.0,
+        ..Default::default()
+    }
+}
+
+async fn next_meta(matchers: &mut broadcast::Receiver<Matcher>) -> MetaEvent {
+    let matcher = tokio::time::timeout(Duration::from_secs(5), matchers.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    match matcher.event.as_ref() {
+        Event::MetaEvent(event) => event.clone(),
+        event => panic!("unexpected event {:?}", event),
+    }
+}
+
+async fn wait_connections(mock: &MockOnebot, n: usize) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while mock.connections() != n {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[test]
+fn reconnect_delays_back_off() {
+    let config = ReconnectConfig {
+        initial_delay: Duration::from_secs(1),
+        max_delay: Duration::from_secs(10),
+        multiplier: 2.0,
+        jitter: 0.0,
+        start_disconnected: false,
+    };
+    let delays: Vec<_> = (0..6)
+        .map(|attempt| config.delay(attempt).as_secs())
+        .collect();
+    assert_eq!(delays, [1, 2, 4, 8, 10, 10]);
+    assert_eq!(config.delay(u32::MAX), Duration::from_secs(10));
+
+    let jittered = ReconnectConfig {
+        jitter: 0.5,
+        ..config
+    };
+    for _ in 0..100 {
+        let delay = jittered.delay(1).as_secs_f64();
+        assert!((1.0..=3.0).contains(&delay), "{}", delay);
+    }
+
+    let unbounded = ReconnectConfig {
+        max_delay: Duration::MAX,
+        jitter: 1.0,
+        ..jittered
+    };
+    // Jittered past `Duration::MAX`, without panicking.
+    for _ in 0..100 {
+        assert!(unbounded.delay(u32::MAX) > Duration::from_secs(1));
+    }
+}
+
+#[tokio::test]
+async fn reconnects_after_the_socket_drops() {
+    let mock = MockOnebot::new();
+    let config = mock.serve_ws().await.unwrap();
+    let bot = OnebotV11WsBot::try_new_with_reconnect(config, fast_reconnect())
+        .await
+        .unwrap();
+    mock.wait_connected().await;
+    let mut matchers = start_bot(&bot).await;
+
+    mock.disconnect();
+    assert!(matches!(
+        next_meta(&mut matchers).await,
+        MetaEvent::DisconnectEvent
+    ));
+    assert!(matches!(
+        next_meta(&mut matchers).await,
+        MetaEvent::ConnectEvent
+    ));
+    wait_connections(&mock, 1).await;
+    mock.respond_json(
+        "get_login_info",
+        json!({ "status": "ok", "retcode": 0, "data": { "user_id": 30003, "nickname": "bot" } }),
+    );
+    bot.call_api(ApiPayload::GetLoginInfo(GetLoginInfo {}))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn started_disconnected_bots_connect_in_the_background() {
+    let mock = MockOnebot::new();
+    let config = mock.serve_ws().await.unwrap();
+    let bot = OnebotV11WsBot::try_new_with_reconnect(
+        config,
+        ReconnectConfig {
+            start_disconnected: true,
+            ..fast_reconnect()
+        },
+    )
+    .await
+    .unwrap();
+    let mut matchers = start_bot(&bot).await;
+    assert!(matches!(
+        next_meta(&mut matchers).await,
+        MetaEvent::ConnectEvent
+    ));
+    assert_eq!(mock.connections(), 1);
+}
+
+#[tokio::test]
+async fn pending_calls_fail_when_the_socket_drops() {
+    let mock = MockOnebot::new();
+    let config = mock.serve_ws().await.unwrap();
+    let bot = OnebotV11WsBot::try_new_with_reconnect(config, fast_reconnect())
+        .await
+        .unwrap();
+    mock.wait_connected().await;
+    mock.respond_never("get_login_info");
+    let call = tokio::spawn({
+        let bot = bot.clone();
+        async move {
+            bot.call_api(ApiPayload::GetLoginInfo(GetLoginInfo {}))
+                .await
+        }
+    });
+    while mock.calls().is_empty() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    mock.disconnect();
+    let error = tokio::time::timeout(Duration::from_secs(5), call)
+        .await
+        .expect("failed before the api timeout")
+        .unwrap()
+        .unwrap_err();
+    assert!(
+        matches!(error, OnebotError::Disconnected { .. }),
+        "{:?}",
+        error
+    );
+    assert!(error.is_retryable());
+}
+
+#[tokio::test]
+async fn dropping_the_bot_closes_the_socket() {
+    let mock = MockOnebot::new();
+    let config = mock.serve_ws().await.unwrap();
+    let bot = OnebotV11WsBot::try_new_with_reconnect(config, fast_reconnect())
+        .await
+        .unwrap();
+    mock.wait_connected().await;
+    drop(bot);
+    wait_connections(&mock, 0).await;
+    // Nothing reconnects.
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(mock.connections(), 0);
+}
+
+#[tokio::test]
+async fn access_tokens_are_sent_where_configured() {
+    let mock = MockOnebot::new().with_access_token("t0k=n&");
+    let ws = mock.serve_ws().await.unwrap();
+    for token_placement in [TokenPlacement::Header, TokenPlacement::Query] {
+        let config = OnebotV11WsConfig {
+            ws: onebot_v11::connect::ws::WsConfig {
+                access_token: Some("t0k=n&".to_string()),
+                ..ws.clone()
+            },
+            token_placement,
+        };
+        let bot = OnebotV11WsBot::try_new(config).await;
+        assert!(bot.is_ok(), "{:?}", token_placement);
+    }
+
+    for access_token in [None, Some("wrong".to_string())] {
+        let config = onebot_v11::connect::ws::WsConfig {
+            access_token,
+            ..ws.clone()
+        };
+        let Err(error) = OnebotV11WsBot::try_new(config).await else {
+            panic!("connected without the token");
+        };
+        assert!(
+            matches!(error, ConnectError::Auth { status: 401, .. }),
+            "{:?}",
+            error
+        );
+    }
+}
@@ -0,0 +1,216 @@
+mod common;
+
+use std::{net::SocketAddr, time::Duration};
+
+use common::SELF_ID;
+use futures_util::StreamExt as _;
+use onebot_v11::{
+    api::payload::{ApiPayload, GetLoginInfo},
+    connect::ws_reverse::ReverseWsConfig,
+};
+use onebot_v11_oxidebot::{
+    error::OnebotError,
+    testing::{start_bot, unused_addr, MockOnebot},
+    OnebotV11ReverseWsBot,
+};
+use oxidebot::{
+    event::{Event, MetaEvent},
+    matcher::Matcher,
+};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::{self, client::IntoClientRequest as _};
+
+fn config(addr: SocketAddr, access_token: Option<&str>) -> ReverseWsConfig {
+    ReverseWsConfig {
+        host: addr.ip().to_string(),
+        port: addr.port(),
+        suffix: "/onebot/v11/ws".to_string(),
+        access_token: access_token.map(str::to_string),
+    }
+}
+
+async fn listen(access_token: Option<&str>) -> (SocketAddr, OnebotV11ReverseWsBot) {
+    let addr = unused_addr();
+    let bot = OnebotV11ReverseWsBot::try_new_disconnected(config(addr, access_token))
+        .await
+        .unwrap();
+    (addr, bot)
+}
+
+/// Connect with the token in the query string instead of a header.
+async fn connect_with_query(
+    config: &ReverseWsConfig,
+    token: &str,
+) -> Result<(), tungstenite::Error> {
+    let url = format!(
+        "ws://{}:{}{}?access_token={}",
+        config.host,
+        config.port,
+        config.suffix,
+        form_urlencoded::byte_serialize(token.as_bytes()).collect::<String>()
+    );
+    let mut request = url.into_client_request()?;
+    request.headers_mut().insert("X-Self-ID", SELF_ID.into());
+    tokio_tungstenite::connect_async(request).await.map(|_| ())
+}
+
+fn assert_unauthorized(result: Result<(), tungstenite::Error>) {
+    match result {
+        Err(tungstenite::Error::Http(resp)) => assert_eq!(resp.status(), 401),
+        result => panic!("unexpected result {:?}", result),
+    }
+}
+
+#[tokio::test]
+async fn access_token_in_a_header() {
+    let (addr, bot) = listen(Some("s3cret")).await;
+    let mock = MockOnebot::new();
+    for token in [Some("s3cres"), None] {
+        assert_unauthorized(mock.connect_reverse(&config(addr, token), SELF_ID).await);
+    }
+    assert!(bot.account(&SELF_ID.to_string()).await.is_none());
+    mock.connect_reverse(&config(addr, Some("s3cret")), SELF_ID)
+        .await
+        .unwrap();
+    assert!(bot.account(&SELF_ID.to_string()).await.is_some());
+}
+
+#[tokio::test]
+async fn access_token_in_the_query() {
+    let (addr, _bot) = listen(Some("s3 cr&t")).await;
+    let config = config(addr, None);
+    assert_unauthorized(connect_with_query(&config, "s3 cr").await);
+    connect_with_query(&config, "s3 cr&t").await.unwrap();
+}
+
+#[tokio::test]
+async fn anyone_connects_without_a_token() {
+    let (addr, _bot) = listen(None).await;
+    connect_with_query(&config(addr, None), "whatever")
+        .await
+        .unwrap();
+    MockOnebot::new()
+        .connect_reverse(&config(addr, Some("whatever")), SELF_ID)
+        .await
+        .unwrap();
+}
+
+fn group_message(self_id: i64, message_id: i64) -> Value {
+    let mut event = common::group_message(message_id, json!([common::text("hi")]));
+    event["self_id"] = json!(self_id);
+    event
+}
+
+/// The next message received, as the id of the bot that got it and the message id.
+async fn next_message(matchers: &mut broadcast::Receiver<Matcher>) -> (String, String) {
+    loop {
+        let matcher = tokio::time::timeout(Duration::from_secs(5), matchers.recv())
+            .await
+            .expect("event delivered")
+            .unwrap();
+        match matcher.event.as_ref() {
+            Event::MessageEvent(event) => {
+                let bot_id = matcher.bot.bot_info().await.id.unwrap_or_default();
+                return (bot_id, event.id.clone());
+            }
+            Event::MetaEvent(MetaEvent::ConnectEvent) => continue,
+            event => panic!("unexpected event {:?}", event),
+        }
+    }
+}
+
+#[tokio::test]
+async fn accounts_get_their_own_events() {
+    let (addr, bot) = listen(None).await;
+    let mock = MockOnebot::new();
+    mock.connect_reverse(&config(addr, None), 30003)
+        .await
+        .unwrap();
+    mock.connect_reverse(&config(addr, None), 30004)
+        .await
+        .unwrap();
+    let mut all = start_bot(&bot).await;
+    let mut first = start_bot(&bot.account("30003").await.unwrap()).await;
+    let mut second = start_bot(&bot.account("30004").await.unwrap()).await;
+
+    mock.push_event_as(30004, group_message(30004, 2));
+    mock.push_event_as(30003, group_message(30003, 1));
+    let mut received = vec![next_message(&mut all).await, next_message(&mut all).await];
+    received.sort();
+    assert_eq!(
+        received,
+        [
+            ("30003".to_string(), "1".to_string()),
+            ("30004".to_string(), "2".to_string())
+        ]
+    );
+    assert_eq!(
+        next_message(&mut first).await,
+        ("30003".to_string(), "1".to_string())
+    );
+    assert_eq!(
+        next_message(&mut second).await,
+        ("30004".to_string(), "2".to_string())
+    );
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    for matchers in [&mut first, &mut second] {
+        while let Ok(matcher) = matchers.try_recv() {
+            assert!(
+                matches!(*matcher.event, Event::MetaEvent(MetaEvent::ConnectEvent)),
+                "{:?}",
+                matcher.event
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn calls_go_to_their_account() {
+    let (addr, bot) = listen(None).await;
+    let login_info = || ApiPayload::GetLoginInfo(GetLoginInfo {});
+    let first = MockOnebot::new();
+    first
+        .connect_reverse(&config(addr, None), 30003)
+        .await
+        .unwrap();
+    // With one account connected, the listener's bot calls through it.
+    bot.call_api(login_info()).await.ok();
+    assert!(first.take_calls()[0].is(&login_info()));
+
+    let second = MockOnebot::new();
+    second
+        .connect_reverse(&config(addr, None), 30004)
+        .await
+        .unwrap();
+    let error = bot.call_api(login_info()).await.unwrap_err();
+    assert!(
+        matches!(error, OnebotError::InvalidArgument { .. }),
+        "{:?}",
+        error
+    );
+    assert!(first.calls().is_empty() && second.calls().is_empty());
+
+    let account = bot.account("30004").await.unwrap();
+    account.call_api(login_info()).await.ok();
+    assert!(first.calls().is_empty());
+    assert!(second.take_calls()[0].is(&login_info()));
+}
+
+#[tokio::test]
+async fn dropping_the_bot_unbinds_the_listener() {
+    let (addr, bot) = listen(None).await;
+    let mut request = format!("ws://{}/onebot/v11/ws", addr)
+        .into_client_request()
+        .unwrap();
+    request.headers_mut().insert("X-Self-ID", SELF_ID.into());
+    let (mut socket, _) = tokio_tungstenite::connect_async(request).await.unwrap();
+    drop(bot);
+    // The connection is closed, and the port free again.
+    let closed = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(Ok(_)) = socket.next().await {}
+    })
+    .await;
+    assert!(closed.is_ok(), "connection still open");
+    tokio::net::TcpListener::bind(addr).await.unwrap();
+}